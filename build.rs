@@ -0,0 +1,39 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The trimmed stdout of `program args`, if it ran and succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Bakes the build's metadata into `GET /version`.
+fn main() {
+    // Builds from a source archive have no repository; CI can pass the commit in.
+    let commit = env::var("GIT_COMMIT").ok()
+        .or_else(|| {
+            let commit = output("git", &["rev-parse", "HEAD"])?;
+            let dirty = output("git", &["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+            Some(if dirty { format!("{}-dirty", commit) } else { commit })
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+    // Reproducible builds pin the time with SOURCE_DATE_EPOCH.
+    let built_at = env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()));
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BUILD_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=BUILD_UNIX_TIME={}", built_at);
+
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for path in ["build.rs", "Cargo.toml", "src", "migrations", ".git/HEAD", ".git/refs/heads", ".git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS persons (
+    id BIGINT PRIMARY KEY,
+    name TEXT NOT NULL,
+    age SMALLINT NOT NULL,
+    date DATE NOT NULL
+);
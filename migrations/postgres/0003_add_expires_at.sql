@@ -0,0 +1 @@
+ALTER TABLE persons ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
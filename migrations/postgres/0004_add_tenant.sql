@@ -0,0 +1,3 @@
+ALTER TABLE persons ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE persons DROP CONSTRAINT persons_pkey;
+ALTER TABLE persons ADD PRIMARY KEY (tenant, id);
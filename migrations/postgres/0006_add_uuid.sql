@@ -0,0 +1,3 @@
+ALTER TABLE persons ADD COLUMN IF NOT EXISTS uuid UUID;
+
+CREATE UNIQUE INDEX IF NOT EXISTS persons_tenant_uuid ON persons (tenant, uuid);
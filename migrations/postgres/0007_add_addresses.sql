@@ -0,0 +1 @@
+ALTER TABLE persons ADD COLUMN IF NOT EXISTS addresses JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1 @@
+ALTER TABLE persons ALTER COLUMN date TYPE TIMESTAMPTZ USING date::timestamp AT TIME ZONE 'UTC';
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS persons (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    age INTEGER NOT NULL,
+    date TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE persons ADD COLUMN expires_at TEXT;
@@ -0,0 +1,17 @@
+CREATE TABLE persons_new (
+    tenant TEXT NOT NULL DEFAULT 'default',
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    age INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    deleted_at TEXT,
+    expires_at TEXT,
+    PRIMARY KEY (tenant, id)
+);
+
+INSERT INTO persons_new (tenant, id, name, age, date, deleted_at, expires_at)
+    SELECT 'default', id, name, age, date, deleted_at, expires_at FROM persons;
+
+DROP TABLE persons;
+
+ALTER TABLE persons_new RENAME TO persons;
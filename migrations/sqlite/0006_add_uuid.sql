@@ -0,0 +1,3 @@
+ALTER TABLE persons ADD COLUMN uuid BLOB;
+
+CREATE UNIQUE INDEX persons_tenant_uuid ON persons (tenant, uuid);
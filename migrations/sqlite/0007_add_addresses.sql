@@ -0,0 +1 @@
+ALTER TABLE persons ADD COLUMN addresses TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1 @@
+UPDATE persons SET date = date || 'T00:00:00+00:00' WHERE length(date) = 10;
@@ -0,0 +1,177 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use serde::Serialize;
+use crate::auth;
+use crate::ip_filter;
+use crate::refusal;
+use crate::request_id;
+
+const DEFAULT_MAX_MIB: u64 = 100;
+const DEFAULT_KEEP: u32 = 5;
+
+enum Format {
+    Common,
+    Combined,
+    Json,
+}
+
+/// One request as the `json` format writes it.
+#[derive(Serialize)]
+struct Entry<'a> {
+    time: DateTime<Utc>,
+    client_ip: Option<IpAddr>,
+    user: Option<&'a str>,
+    method: &'a str,
+    path: String,
+    status: u16,
+    /// `None` for streamed bodies, whose size isn't known up front.
+    bytes: Option<usize>,
+    latency_ms: f64,
+    referer: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    request_id: &'a str,
+}
+
+/// Where the lines go: stdout, or a file that is rotated once it grows past `max_bytes`,
+/// keeping `keep` older ones as `<path>.1` (the newest) to `<path>.<keep>`.
+enum Sink {
+    Stdout,
+    File { path: String, file: File, written: u64, max_bytes: u64, keep: u32 },
+}
+
+impl Sink {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Sink::Stdout => writeln!(io::stdout().lock(), "{}", line),
+            Sink::File { path, file, written, max_bytes, keep } => {
+                if *written > 0 && *written + line.len() as u64 + 1 > *max_bytes {
+                    for generation in (1..*keep).rev() {
+                        let _ = fs::rename(format!("{}.{}", path, generation), format!("{}.{}", path, generation + 1));
+                    }
+                    match *keep {
+                        0 => fs::remove_file(&*path)?,
+                        _ => fs::rename(&*path, format!("{}.1", path))?,
+                    }
+                    *file = OpenOptions::new().create(true).append(true).open(&*path)?;
+                    *written = 0;
+                }
+                writeln!(file, "{}", line)?;
+                *written += line.len() as u64 + 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An access log, on when `ACCESS_LOG` names its format: `common` or `combined` (as
+/// Apache and nginx write them, with the latency in microseconds appended) or `json`.
+/// Lines go to stdout, or to `ACCESS_LOG_PATH`, rotated past `ACCESS_LOG_MAX_MIB`
+/// (default 100) with `ACCESS_LOG_KEEP` (default 5) old files kept.
+pub struct AccessLog {
+    format: Format,
+    trusted_proxies: Vec<IpNet>,
+    sink: Mutex<Sink>,
+}
+
+impl AccessLog {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let format = match env::var("ACCESS_LOG").as_deref() {
+            Err(_) => return Ok(None),
+            Ok("common") => Format::Common,
+            Ok("combined") => Format::Combined,
+            Ok("json") => Format::Json,
+            Ok(format) => return Err(format!("ACCESS_LOG must be common, combined or json, got {:?}", format)),
+        };
+        let sink = match env::var("ACCESS_LOG_PATH") {
+            Ok(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(&path)
+                    .map_err(|e| format!("ACCESS_LOG_PATH {}: {}", path, e))?;
+                let written = file.metadata().map_or(0, |metadata| metadata.len());
+                let max_mib = env::var("ACCESS_LOG_MAX_MIB").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_MIB);
+                let keep = env::var("ACCESS_LOG_KEEP").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_KEEP);
+                Sink::File { path, file, written, max_bytes: max_mib.saturating_mul(1024 * 1024), keep }
+            }
+            Err(_) => Sink::Stdout,
+        };
+        // The client is resolved as the IP filter does.
+        let trusted_proxies = ip_filter::networks("TRUSTED_PROXIES")?;
+        Ok(Some(AccessLog { format, trusted_proxies, sink: Mutex::new(sink) }))
+    }
+
+    fn line(&self, request: &Request<'_>, response: &Response<'_>, latency_ms: f64) -> String {
+        let client_ip = ip_filter::client_ip(request, &self.trusted_proxies);
+        let user = auth::caller(request);
+        let path = match request.uri().query() {
+            Some(query) => format!("{}?{}", refusal::original_path(request), query),
+            None => refusal::original_path(request).to_string(),
+        };
+        let status = response.status().code;
+        let bytes = response.body().preset_size();
+        let referer = request.headers().get_one("Referer");
+        let user_agent = request.headers().get_one("User-Agent");
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        let quoted = |value: Option<&str>| or_dash(value.map(|value| format!("\"{}\"", value.escape_default())));
+        let common = || format!(
+            "{} - {} [{}] \"{} {}\" {} {}",
+            or_dash(client_ip.map(|ip| ip.to_string())),
+            or_dash(user.map(|user| user.replace(char::is_whitespace, "_"))),
+            Utc::now().format("%d/%b/%Y:%H:%M:%S %z"),
+            request.method(),
+            path.escape_default(),
+            status,
+            or_dash(bytes.map(|bytes| bytes.to_string())),
+        );
+        let micros = (latency_ms * 1000.0).round() as u64;
+        match self.format {
+            Format::Common => format!("{} {}", common(), micros),
+            Format::Combined => format!("{} {} {} {}", common(), quoted(referer), quoted(user_agent), micros),
+            Format::Json => {
+                let entry = Entry {
+                    time: Utc::now(),
+                    client_ip,
+                    user,
+                    method: request.method().as_str(),
+                    path,
+                    status,
+                    bytes,
+                    latency_ms,
+                    referer,
+                    user_agent,
+                    request_id: request_id::of(request),
+                };
+                serde_json::to_string(&entry).expect("an access log entry is strings and numbers")
+            }
+        }
+    }
+}
+
+struct Started(Instant);
+
+#[rocket::async_trait]
+impl Fairing for AccessLog {
+    fn info(&self) -> Info {
+        Info { name: "Access log", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| Started(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let latency = request.local_cache(|| Started(Instant::now())).0.elapsed();
+        let line = self.line(request, response, latency.as_secs_f64() * 1000.0);
+        if let Err(e) = self.sink.lock().unwrap_or_else(PoisonError::into_inner).write(&line) {
+            error!("Failed to write the access log: {}", e);
+        }
+    }
+}
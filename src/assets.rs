@@ -0,0 +1,56 @@
+use std::path::Path;
+use rocket::Route;
+
+/// Where the frontend's files are served.
+pub const STATIC_PATH: &str = "/static";
+
+/// Serves the files in `dir`, with `index.html` for directories; none if it doesn't exist.
+#[cfg(not(feature = "embed-static"))]
+pub fn routes(dir: &Path) -> Vec<Route> {
+    use rocket::fs::{FileServer, Options};
+    match dir.is_dir() {
+        true => FileServer::new(dir, Options::Index).into(),
+        false => {
+            info!("No {} directory, so nothing is served under {}", dir.display(), STATIC_PATH);
+            Vec::new()
+        }
+    }
+}
+
+/// Serves the files `static/` held at build time, for a deploy that is just the binary.
+#[cfg(feature = "embed-static")]
+pub fn routes(_: &Path) -> Vec<Route> {
+    routes![embedded::file]
+}
+
+#[cfg(feature = "embed-static")]
+mod embedded {
+    use std::borrow::Cow;
+    use std::path::{Path, PathBuf};
+    use rocket::http::ContentType;
+    use rust_embed::RustEmbed;
+
+    #[derive(RustEmbed)]
+    #[folder = "static/"]
+    struct Assets;
+
+    #[get("/<path..>")]
+    pub fn file(path: PathBuf) -> Option<(ContentType, Cow<'static, [u8]>)> {
+        let name = path.iter().map(|segment| segment.to_string_lossy()).collect::<Vec<_>>().join("/");
+        let index = match name.is_empty() {
+            true => "index.html".to_string(),
+            false => format!("{}/index.html", name),
+        };
+        let (name, file) = match Assets::get(&name) {
+            Some(file) => (name, file),
+            None => {
+                let file = Assets::get(&index)?;
+                (index, file)
+            }
+        };
+        let content_type = Path::new(&name).extension()
+            .and_then(|extension| ContentType::from_extension(&extension.to_string_lossy()))
+            .unwrap_or(ContentType::Binary);
+        Some((content_type, file.data))
+    }
+}
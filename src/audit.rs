@@ -0,0 +1,328 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use chrono::{DateTime, Utc};
+use rocket::fairing::AdHoc;
+use rocket::http::Method;
+use rocket::tokio::{self, sync::oneshot};
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::auth;
+use crate::person::Person;
+use crate::refusal;
+use crate::request_id;
+use crate::tenant::{self, DEFAULT_TENANT, TENANT_HEADER};
+use crate::AppState;
+
+/// One `POST`, `PUT`, `PATCH` or `DELETE`, whether it succeeded or not, or one purge of
+/// expired persons.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// The principal that sent it; `None` for unrestricted callers (authentication
+    /// off, or an API key without a name) and anonymous ones.
+    pub actor: Option<String>,
+    /// `None`, like `path`, for writes no request made, such as the expiry sweep's.
+    pub request_id: Option<String>,
+    pub tenant: String,
+    pub method: String,
+    pub path: Option<String>,
+    /// The handler that ran, e.g. `patch_person`, or `purge_expired` for the expiry sweep;
+    /// `None` when none did.
+    pub operation: Option<String>,
+    pub status: u16,
+    /// The persons it created, changed or deleted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub person_ids: Vec<u32>,
+    /// What a `PUT` or `PATCH` changed in each person it updated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<PersonChange>,
+}
+
+/// The fields of one person an update changed, by their JSON name.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PersonChange {
+    pub id: u32,
+    pub fields: BTreeMap<String, FieldChange>,
+}
+
+/// A field's value before and after an update; `null` when it was or became absent.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FieldChange {
+    pub before: Value,
+    pub after: Value,
+}
+
+impl PersonChange {
+    fn between(before: &Person, after: &Person) -> Self {
+        let object = |person| match serde_json::to_value(person) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Default::default(),
+        };
+        let id = after.id;
+        let (mut before, mut after) = (object(before), object(after));
+        let mut names: Vec<String> = before.keys().chain(after.keys()).cloned().collect();
+        names.sort_unstable();
+        names.dedup();
+        let fields = names.into_iter()
+            // Every update bumps the version; it says nothing about what changed.
+            .filter(|name| name != "version")
+            .filter_map(|name| {
+                let change = FieldChange { before: before.remove(&name).unwrap_or(Value::Null), after: after.remove(&name).unwrap_or(Value::Null) };
+                (change.before != change.after).then_some((name, change))
+            })
+            .collect();
+        PersonChange { id, fields }
+    }
+}
+
+/// Which entries `GET /admin/audit` returns.
+#[derive(Clone, Copy)]
+pub struct AuditFilter {
+    pub person_id: Option<u32>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.person_id.map_or(true, |id| entry.person_ids.contains(&id))
+            && self.from.map_or(true, |from| entry.at >= from)
+            && self.to.map_or(true, |to| entry.at <= to)
+    }
+}
+
+/// Entries kept in memory without `AUDIT_LOG_PATH`, unless `AUDIT_LOG_MEMORY` says otherwise.
+const DEFAULT_MEMORY: usize = 10_000;
+
+/// Append-only record of every write. With `AUDIT_LOG_PATH` set, entries are appended to
+/// that file as JSON lines and queries read it back; otherwise only the newest
+/// `AUDIT_LOG_MEMORY` are kept, in memory.
+pub struct AuditLog {
+    store: Store,
+}
+
+enum Store {
+    Memory { entries: Mutex<VecDeque<AuditEntry>>, capacity: usize },
+    // Written by its own thread, so responses never wait on the disk.
+    File { path: PathBuf, commands: Sender<Command> },
+}
+
+enum Command {
+    Append(AuditEntry),
+    /// Answered once everything queued before it is written.
+    Flush(oneshot::Sender<()>),
+}
+
+impl AuditLog {
+    pub fn from_env() -> io::Result<Self> {
+        let Ok(path) = env::var("AUDIT_LOG_PATH") else {
+            let capacity = match env::var("AUDIT_LOG_MEMORY") {
+                Ok(value) => value.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("AUDIT_LOG_MEMORY must be a number of entries, got {}", value)))?,
+                Err(_) => DEFAULT_MEMORY,
+            };
+            return Ok(AuditLog { store: Store::Memory { entries: Mutex::new(VecDeque::new()), capacity } });
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("AUDIT_LOG_PATH {}: {}", path, e)))?;
+        let (commands, received) = mpsc::channel();
+        thread::spawn(move || write_entries(file, received));
+        Ok(AuditLog { store: Store::File { path: path.into(), commands } })
+    }
+
+    /// Waits until every entry so far is written to the file, if there is one.
+    pub async fn close(&self) {
+        if let Store::File { commands, .. } = &self.store {
+            let (done, flushed) = oneshot::channel();
+            if commands.send(Command::Flush(done)).is_ok() {
+                let _ = flushed.await;
+            }
+        }
+    }
+
+    fn append(&self, entry: AuditEntry) {
+        match &self.store {
+            Store::Memory { entries, capacity } => {
+                let mut entries = entries.lock().unwrap_or_else(PoisonError::into_inner);
+                if entries.len() >= *capacity {
+                    entries.pop_front();
+                }
+                if *capacity > 0 {
+                    entries.push_back(entry);
+                }
+            }
+            Store::File { commands, .. } => {
+                if commands.send(Command::Append(entry)).is_err() {
+                    error!("The audit log writer has stopped; an entry is lost");
+                }
+            }
+        }
+    }
+
+    /// Records the expiry sweep's deletion of `ids` from `tenant`.
+    pub fn record_purge(&self, tenant: &str, ids: Vec<u32>) {
+        self.append(AuditEntry {
+            at: Utc::now(),
+            actor: None,
+            request_id: None,
+            tenant: tenant.to_string(),
+            method: Method::Delete.as_str().to_string(),
+            path: None,
+            operation: Some("purge_expired".to_string()),
+            status: 200,
+            person_ids: ids,
+            changes: Vec::new(),
+        });
+    }
+
+    /// Matching entries, newest first, at most `limit` of them.
+    pub async fn query(&self, filter: AuditFilter, limit: usize) -> io::Result<Vec<AuditEntry>> {
+        match &self.store {
+            Store::Memory { entries, .. } => {
+                let entries = entries.lock().unwrap_or_else(PoisonError::into_inner);
+                Ok(entries.iter().rev().filter(|entry| filter.matches(entry)).take(limit).cloned().collect())
+            }
+            Store::File { path, .. } => {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || read_newest(&path, &filter, limit)).await?
+            }
+        }
+    }
+}
+
+/// Writes what is queued in one go, then syncs it, until the log is dropped.
+fn write_entries(mut file: File, commands: Receiver<Command>) {
+    while let Ok(command) = commands.recv() {
+        let mut lines = String::new();
+        let mut flushed = Vec::new();
+        for command in iter::once(command).chain(commands.try_iter()) {
+            match command {
+                Command::Append(entry) => match serde_json::to_string(&entry) {
+                    Ok(line) => {
+                        lines.push_str(&line);
+                        lines.push('\n');
+                    }
+                    Err(e) => error!("Failed to serialize the audit entry of {}: {}", entry.at, e),
+                },
+                Command::Flush(done) => flushed.push(done),
+            }
+        }
+        if !lines.is_empty() {
+            if let Err(e) = file.write_all(lines.as_bytes()).and_then(|_| file.sync_data()) {
+                error!("Failed to write audit entries: {}", e);
+            }
+        }
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Scans the file oldest first, keeping only the newest `limit` matches.
+fn read_newest(path: &Path, filter: &AuditFilter, limit: usize) -> io::Result<Vec<AuditEntry>> {
+    let mut newest = VecDeque::with_capacity(limit.min(DEFAULT_MEMORY));
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A crash mid-write can leave the last line cut short; it is no entry.
+        let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+            warn!("Skipping unreadable audit entry in {}", path.display());
+            continue;
+        };
+        if filter.matches(&entry) {
+            if newest.len() == limit {
+                newest.pop_front();
+            }
+            newest.push_back(entry);
+        }
+    }
+    Ok(newest.into_iter().rev().collect())
+}
+
+/// The persons a write touched and what it changed in them.
+#[derive(Default)]
+struct Touched {
+    person_ids: Vec<u32>,
+    changes: Vec<PersonChange>,
+}
+
+/// The persons a write touched, collected by its handler for the audit entry.
+pub struct AuditTrail<'r>(&'r Mutex<Touched>);
+
+impl AuditTrail<'_> {
+    pub fn record(&self, id: u32) {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).person_ids.push(id);
+    }
+
+    pub fn record_all(&self, ids: impl IntoIterator<Item = u32>) {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).person_ids.extend(ids);
+    }
+
+    /// Records an update of `before` to `after`, along with the fields it changes.
+    pub fn record_change(&self, before: &Person, after: &Person) {
+        let mut touched = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        touched.person_ids.push(after.id);
+        touched.changes.push(PersonChange::between(before, after));
+    }
+}
+
+fn touched<'r>(request: &'r Request<'_>) -> &'r Mutex<Touched> {
+    request.local_cache(|| Mutex::new(Touched::default()))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuditTrail<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuditTrail(touched(request)))
+    }
+}
+
+fn is_write(method: Method) -> bool {
+    matches!(method, Method::Post | Method::Put | Method::Patch | Method::Delete)
+}
+
+/// Adds an [`AuditEntry`] to the [`AuditLog`] for every write once its response is final.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_response("Audit Log", |request, response| Box::pin(async move {
+        let Some(state) = request.rocket().state::<AppState>() else {
+            return;
+        };
+        if !is_write(request.method()) {
+            return;
+        }
+        let tenant = match request.headers().get_one(TENANT_HEADER) {
+            Some(tenant) if state.multi_tenant && tenant::is_valid(tenant) => tenant.to_string(),
+            _ => DEFAULT_TENANT.to_string(),
+        };
+        // Handlers note the persons they are about to write; a failed write changed none.
+        let Touched { mut person_ids, changes } = match response.status().class().is_success() {
+            true => std::mem::take(&mut *touched(request).lock().unwrap_or_else(PoisonError::into_inner)),
+            false => Touched::default(),
+        };
+        person_ids.sort_unstable();
+        person_ids.dedup();
+        state.audit.append(AuditEntry {
+            at: Utc::now(),
+            actor: auth::caller(request).map(str::to_string),
+            request_id: Some(request_id::of(request).to_string()),
+            tenant,
+            method: request.method().as_str().to_string(),
+            path: Some(refusal::original_path(request).to_string()),
+            operation: request.route().and_then(|route| route.name.as_deref()).map(str::to_string),
+            status: response.status().code,
+            person_ids,
+            changes,
+        });
+    }))
+}
@@ -0,0 +1,430 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rocket::http::{Cookie, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+use crate::rbac::{Permission, Policy};
+use crate::secrets;
+use crate::AppState;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+pub const SESSION_COOKIE: &str = "session";
+const DEFAULT_SESSION_TTL_SECS: u64 = 8 * 60 * 60;
+
+/// Keys accepted in [`API_KEY_HEADER`], from `API_KEYS`, or the `API_KEYS_FILE`
+/// it names: separated by commas or newlines, lines starting with `#` ignored. A key written
+/// `name:key` is the principal `name` of the [`Policy`]; keys without a name may do anything.
+#[derive(Default)]
+pub struct ApiKeys(Vec<ApiKey>);
+
+struct ApiKey {
+    name: Option<String>,
+    key: String,
+}
+
+impl ApiKey {
+    fn parse(entry: &str) -> Self {
+        match entry.split_once(':') {
+            Some((name, key)) => ApiKey { name: Some(name.trim().to_string()), key: key.trim().to_string() },
+            None => ApiKey { name: None, key: entry.to_string() },
+        }
+    }
+}
+
+impl ApiKeys {
+    pub fn from_env() -> io::Result<Self> {
+        let keys = secrets::var("API_KEYS")
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(ApiKey::parse)
+            .collect();
+        Ok(ApiKeys(keys))
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    /// Compares against every key without stopping early, so timing doesn't reveal how much matched.
+    fn find(&self, candidate: &str) -> Option<&ApiKey> {
+        self.0.iter().fold(None, |found, key| match constant_time_eq(key.key.as_bytes(), candidate.as_bytes()) {
+            true => found.or(Some(key)),
+            false => found,
+        })
+    }
+}
+
+fn read_file(path: &str, variable: &str) -> io::Result<String> {
+    fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("{} {}: {}", variable, path, e)))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+/// Verifies `Authorization: Bearer` tokens signed with `JWT_SECRET` (HS256) or the
+/// RSA public key in `JWT_PUBLIC_KEY_FILE` (RS256). Tokens must not be expired and,
+/// when `JWT_AUDIENCE` is set, must be issued for it.
+pub struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+impl JwtVerifier {
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let invalid = |e: jsonwebtoken::errors::Error| io::Error::new(io::ErrorKind::InvalidData, format!("JWT_PUBLIC_KEY_FILE: {}", e));
+        let (key, algorithm) = match (secrets::var("JWT_SECRET"), env::var("JWT_PUBLIC_KEY_FILE")) {
+            (Ok(secret), _) => (DecodingKey::from_secret(secret.as_bytes()), Algorithm::HS256),
+            (_, Ok(path)) => {
+                let pem = read_file(&path, "JWT_PUBLIC_KEY_FILE")?;
+                (DecodingKey::from_rsa_pem(pem.as_bytes()).map_err(invalid)?, Algorithm::RS256)
+            }
+            _ => return Ok(None),
+        };
+        let mut validation = Validation::new(algorithm);
+        validation.set_required_spec_claims(&["exp"]);
+        match env::var("JWT_AUDIENCE") {
+            Ok(audience) => validation.set_audience(&[audience]),
+            Err(_) => validation.validate_aud = false,
+        }
+        Ok(Some(JwtVerifier { key, validation }))
+    }
+
+    fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let claims = jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => AuthError::ExpiredToken,
+                ErrorKind::InvalidAudience => AuthError::WrongAudience,
+                _ => AuthError::InvalidToken,
+            })?
+            .claims;
+        Ok(Principal::Identified { subject: claims.sub, roles: claims.roles })
+    }
+}
+
+/// How long a login lasts, from `SESSION_TTL_SECS`.
+pub fn session_ttl_from_env() -> Duration {
+    env::var("SESSION_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map_or(Duration::from_secs(DEFAULT_SESSION_TTL_SECS), Duration::from_secs)
+}
+
+/// A signed-in browser, kept in the private (encrypted and signed) [`SESSION_COOKIE`].
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub subject: String,
+    pub roles: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    /// The cookie to add with `CookieJar::add_private`; `secure` when served over HTTPS.
+    pub fn cookie(&self, secure: bool) -> Cookie<'static> {
+        let value = serde_json::to_string(self).expect("a session is strings and a timestamp");
+        Cookie::build((SESSION_COOKIE, value))
+            .path("/")
+            .same_site(SameSite::Lax)
+            .secure(secure)
+            .http_only(true)
+            .build()
+    }
+
+    fn from_request(request: &Request<'_>) -> Option<Self> {
+        let cookie = request.cookies().get_private(SESSION_COOKIE)?;
+        serde_json::from_str::<Session>(cookie.value()).ok().filter(|session| session.expires_at > Utc::now())
+    }
+}
+
+/// How callers prove who they are, and what the [`Policy`] then lets them do.
+/// With neither API keys, a JWT key nor sessions configured, every request is
+/// allowed everything but the admin routes, unless `unauthenticated_admin`.
+pub struct Authentication {
+    pub api_keys: ApiKeys,
+    pub jwt: Option<JwtVerifier>,
+    /// Whether [`Session`] cookies are honoured, i.e. some login route can issue them.
+    pub sessions: bool,
+    /// Whether TLS asks for client certificates, whose subjects are then principals.
+    pub client_certs: bool,
+    pub policy: Policy,
+    /// `ALLOW_UNAUTHENTICATED_ADMIN`: lets anyone use the admin routes when nothing else is configured.
+    pub unauthenticated_admin: bool,
+}
+
+impl Authentication {
+    pub fn from_env() -> io::Result<Self> {
+        Ok(Authentication {
+            api_keys: ApiKeys::from_env()?,
+            jwt: JwtVerifier::from_env()?,
+            sessions: false,
+            client_certs: false,
+            policy: Policy::from_env()?,
+            unauthenticated_admin: env::var("ALLOW_UNAUTHENTICATED_ADMIN").is_ok_and(|v| v == "true" || v == "1"),
+        })
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.api_keys.is_enabled() || self.jwt.is_some() || self.sessions || self.client_certs
+    }
+
+    pub fn accepts_key(&self, key: &str) -> bool {
+        self.api_keys.find(key).is_some()
+    }
+
+    /// `client` is the subject of the client certificate, if one was presented.
+    fn authenticate(&self, request: &Request<'_>, client: Option<String>) -> Result<Principal, AuthError> {
+        if !self.is_enabled() {
+            return Ok(Principal::Unrestricted);
+        }
+        if let Some(authorization) = request.headers().get_one("Authorization") {
+            let (Some(jwt), Some(token)) = (&self.jwt, authorization.strip_prefix("Bearer ")) else {
+                return Err(AuthError::InvalidToken);
+            };
+            return jwt.verify(token.trim());
+        }
+        match request.headers().get_one(API_KEY_HEADER).map(|key| self.api_keys.find(key)) {
+            Some(Some(ApiKey { name: None, .. })) => Ok(Principal::Unrestricted),
+            Some(Some(ApiKey { name: Some(name), .. })) => Ok(Principal::Identified { subject: Some(name.clone()), roles: Vec::new() }),
+            Some(None) => Err(AuthError::InvalidKey),
+            None if client.is_some() => Ok(Principal::Identified { subject: client, roles: Vec::new() }),
+            None => match Session::from_request(request).filter(|_| self.sessions) {
+                Some(session) => Ok(Principal::Identified { subject: Some(session.subject), roles: session.roles }),
+                None => Err(AuthError::Missing),
+            },
+        }
+    }
+
+    /// Authenticates the caller and checks that the policy grants them `permission`.
+    fn authorize(&self, request: &Request<'_>, client: Option<String>, permission: Permission) -> Result<(), AuthError> {
+        // Restores, resets and reloads stay shut to whoever reaches the port until asked otherwise.
+        if permission == Permission::Admin && !self.is_enabled() && !self.unauthenticated_admin {
+            return Err(AuthError::AdminUnprotected);
+        }
+        let allowed = match self.authenticate(request, client) {
+            Ok(Principal::Unrestricted) => true,
+            Ok(Principal::Identified { subject, roles }) => {
+                let allowed = self.policy.permits(subject.as_deref(), &roles, permission);
+                request.local_cache(|| Caller(subject));
+                allowed
+            }
+            Err(AuthError::Missing) if self.policy.permits_anonymous(permission) => true,
+            Err(error) => return Err(error),
+        };
+        match allowed {
+            true => Ok(()),
+            false => Err(AuthError::MissingPermission(permission)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuthError {
+    Missing,
+    InvalidKey,
+    InvalidToken,
+    ExpiredToken,
+    WrongAudience,
+    /// Authenticated, but without this permission.
+    MissingPermission(Permission),
+    /// A page for signed-in browsers, requested without a session.
+    NoSession,
+    /// An admin route, with no authentication configured to protect it.
+    AdminUnprotected,
+    MissingSignature,
+    InvalidSignature,
+    StaleSignature,
+    ReplayedSignature,
+}
+
+impl AuthError {
+    pub fn message(self) -> &'static str {
+        match self {
+            AuthError::Missing => "this request requires a bearer token, an X-Api-Key header or a session",
+            AuthError::InvalidKey => "the API key is not valid",
+            AuthError::InvalidToken => "the bearer token is not valid",
+            AuthError::ExpiredToken => "the bearer token has expired",
+            AuthError::WrongAudience => "the bearer token is not issued for this API",
+            AuthError::MissingPermission(Permission::Read) => "this request requires the read permission",
+            AuthError::MissingPermission(Permission::Write) => "this request requires the write permission",
+            AuthError::MissingPermission(Permission::Admin) => "this request requires the admin permission",
+            AuthError::NoSession => "this page requires signing in at /login",
+            AuthError::AdminUnprotected => "admin routes are off until authentication is configured or ALLOW_UNAUTHENTICATED_ADMIN is set",
+            AuthError::MissingSignature => "this request requires the X-Signature and X-Signature-Timestamp headers",
+            AuthError::InvalidSignature => "the request signature is not valid",
+            AuthError::StaleSignature => "the request signature timestamp is too old or too far ahead",
+            AuthError::ReplayedSignature => "the request signature has already been used",
+        }
+    }
+
+    fn status(self) -> Status {
+        match self {
+            AuthError::MissingPermission(_) | AuthError::AdminUnprotected => Status::Forbidden,
+            _ => Status::Unauthorized,
+        }
+    }
+}
+
+/// Who is calling, as far as the credentials tell.
+enum Principal {
+    /// Authentication is off, or the caller used an API key without a name.
+    Unrestricted,
+    /// `roles` are those the credentials carry; the policy may add more for `subject`.
+    Identified { subject: Option<String>, roles: Vec<String> },
+}
+
+/// Checks `permission`; refusals answer `401` or `403`, with the reason cached for the catcher.
+async fn require(request: &Request<'_>, permission: Permission) -> Outcome<(), AuthError> {
+    let outcome = match request.rocket().state::<AppState>() {
+        Some(state) => state.auth.authorize(request, client_subject(request).await, permission),
+        None => Ok(()),
+    };
+    match outcome {
+        Ok(()) => Outcome::Success(()),
+        Err(error) => {
+            request.local_cache(|| Some(error));
+            Outcome::Error((error.status(), error))
+        }
+    }
+}
+
+#[cfg(feature = "mtls")]
+async fn client_subject(request: &Request<'_>) -> Option<String> {
+    request.guard::<crate::mtls::ClientIdentity>().await.succeeded().map(|identity| identity.subject)
+}
+
+#[cfg(not(feature = "mtls"))]
+async fn client_subject(_: &Request<'_>) -> Option<String> {
+    None
+}
+
+/// Guard of reads of persons.
+pub struct Reader;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Reader {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(request, Permission::Read).await.map(|()| Reader)
+    }
+}
+
+/// Guard of writes to persons.
+pub struct Editor;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(request, Permission::Write).await.map(|()| Editor)
+    }
+}
+
+/// Guard of the `/admin` endpoints.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(request, Permission::Admin).await.map(|()| Admin)
+    }
+}
+
+/// Guard of the admin HTML pages: a [`Session`] whose principal has the admin
+/// permission. Unlike the JSON API, these never take tokens or API keys.
+pub struct AdminSession(pub Session);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminSession {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(session) = Session::from_request(request) else {
+            request.local_cache(|| Some(AuthError::NoSession));
+            return Outcome::Error((Status::Unauthorized, AuthError::NoSession));
+        };
+        request.local_cache(|| Caller(Some(session.subject.clone())));
+        let permitted = request.rocket().state::<AppState>()
+            .map_or(true, |state| state.auth.policy.permits(Some(session.subject.as_str()), &session.roles, Permission::Admin));
+        match permitted {
+            true => Outcome::Success(AdminSession(session)),
+            false => {
+                let error = AuthError::MissingPermission(Permission::Admin);
+                request.local_cache(|| Some(error));
+                Outcome::Error((error.status(), error))
+            }
+        }
+    }
+}
+
+/// The subject a guard here authenticated, whether or not it was then let through.
+struct Caller(Option<String>);
+
+pub fn caller<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request.local_cache(|| Caller(None)).0.as_deref()
+}
+
+/// Why this request was refused with `401` or `403`, if a guard here refused it.
+pub fn recorded_error(request: &Request<'_>) -> Option<AuthError> {
+    *request.local_cache(|| None::<AuthError>)
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::local::asynchronous::Client;
+    use super::*;
+
+    /// What `LOGIN_USERS_FILE` alone configures: no keys or tokens, only the login's sessions.
+    fn password_login_only() -> Authentication {
+        Authentication { sessions: true, ..unconfigured() }
+    }
+
+    fn unconfigured() -> Authentication {
+        Authentication { api_keys: ApiKeys::default(), jwt: None, sessions: false, client_certs: false, policy: Policy::default(), unauthenticated_admin: false }
+    }
+
+    #[rocket::async_test]
+    async fn admin_is_shut_without_authentication() {
+        let client = Client::untracked(rocket::build()).await.expect("valid rocket");
+        let request = client.post("/admin/reset");
+
+        let error = unconfigured().authorize(request.inner(), None, Permission::Admin).unwrap_err();
+        assert_eq!(error.status(), Status::Forbidden);
+        assert!(unconfigured().authorize(request.inner(), None, Permission::Write).is_ok());
+        let opted_in = Authentication { unauthenticated_admin: true, ..unconfigured() };
+        assert!(opted_in.authorize(request.inner(), None, Permission::Admin).is_ok());
+    }
+
+    #[rocket::async_test]
+    async fn admin_needs_a_session_with_only_password_login() {
+        let auth = password_login_only();
+        let client = Client::untracked(rocket::build()).await.expect("valid rocket");
+
+        let anonymous = client.get("/admin/stats");
+        let error = auth.authorize(anonymous.inner(), None, Permission::Admin).unwrap_err();
+        assert_eq!(error.status(), Status::Unauthorized);
+
+        let session = Session { subject: "alice".to_string(), roles: vec!["admin".to_string()], expires_at: Utc::now() + chrono::Duration::hours(1) };
+        let signed_in = client.get("/admin/stats").private_cookie(session.cookie(false));
+        assert!(auth.authorize(signed_in.inner(), None, Permission::Admin).is_ok());
+    }
+}
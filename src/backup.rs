@@ -0,0 +1,65 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::person::Person;
+use crate::tenant::DEFAULT_TENANT;
+
+pub enum BackupError {
+    InvalidName,
+    NotFound,
+    /// The backup holds the persons of this other tenant.
+    WrongTenant(String),
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+#[derive(Serialize)]
+struct Contents<'a> {
+    tenant: &'a str,
+    persons: &'a [Person],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Tenant { tenant: String, persons: Vec<Person> },
+    // Backups written before they named their tenant hold a bare list, taken to be the default tenant's.
+    Legacy(Vec<Person>),
+}
+
+/// Writes the persons of `tenant` to a new timestamped file in `dir` and returns its file name.
+pub fn write_backup(dir: &Path, tenant: &str, persons: &[Person]) -> Result<String, BackupError> {
+    fs::create_dir_all(dir).map_err(BackupError::Io)?;
+    let name = format!("persons-{}.json", Utc::now().format("%Y%m%dT%H%M%S%.3fZ"));
+    let contents = serde_json::to_string_pretty(&Contents { tenant, persons }).map_err(BackupError::Parse)?;
+    fs::write(dir.join(&name), contents).map_err(BackupError::Io)?;
+    Ok(name)
+}
+
+/// The persons in the backup `name`, which must be of `tenant`.
+pub fn read_backup(dir: &Path, name: &str, tenant: &str) -> Result<Vec<Person>, BackupError> {
+    let path = resolve(dir, name)?;
+    let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => BackupError::NotFound,
+        _ => BackupError::Io(e),
+    })?;
+    let (owner, persons) = match serde_json::from_str(&contents).map_err(BackupError::Parse)? {
+        Stored::Tenant { tenant, persons } => (tenant, persons),
+        Stored::Legacy(persons) => (DEFAULT_TENANT.to_string(), persons),
+    };
+    match owner == tenant {
+        true => Ok(persons),
+        false => Err(BackupError::WrongTenant(owner)),
+    }
+}
+
+// Only bare file names are accepted so a restore request can't reach outside the backup directory.
+fn resolve(dir: &Path, name: &str) -> Result<PathBuf, BackupError> {
+    let is_bare_name = Path::new(name).file_name().is_some_and(|n| n == name);
+    if !is_bare_name {
+        return Err(BackupError::InvalidName);
+    }
+    Ok(dir.join(name))
+}
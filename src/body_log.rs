@@ -0,0 +1,133 @@
+use std::env;
+use std::io::Cursor;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use serde_json::Value;
+use crate::request_id;
+
+const DEFAULT_MAX_BYTES: usize = 4096;
+/// All Rocket lets a fairing see of a request body without consuming it.
+const PEEK_BYTES: usize = 512;
+/// Fields whose names contain any of these are logged as [`REDACTED`].
+const SENSITIVE: [&str; 7] = ["password", "secret", "token", "authorization", "api_key", "apikey", "credential"];
+const REDACTED: &str = "[redacted]";
+
+/// Logs request and response bodies when `DEBUG_HTTP_LOG=true`, for diagnosing clients
+/// in staging; never turn it on in production. Bodies are cut at `DEBUG_HTTP_LOG_MAX_BYTES`
+/// (default 4096; request bodies at 512 at most), streamed responses are left out, and
+/// fields that look like credentials are redacted from JSON and form bodies. Other bodies
+/// that mention such a field are withheld.
+pub struct BodyLog {
+    max_bytes: usize,
+}
+
+impl BodyLog {
+    pub fn from_env() -> Option<Self> {
+        match env::var("DEBUG_HTTP_LOG").as_deref() {
+            Ok("true") | Ok("1") => {
+                let max_bytes = env::var("DEBUG_HTTP_LOG_MAX_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_BYTES);
+                Some(BodyLog { max_bytes })
+            }
+            _ => None,
+        }
+    }
+
+    fn render(&self, content_type: Option<&ContentType>, body: &[u8], complete: bool) -> String {
+        let shown = &body[..body.len().min(self.max_bytes)];
+        let truncated = !complete || shown.len() < body.len();
+        let text = match content_type {
+            Some(content_type) if !truncated && content_type.is_json() => match serde_json::from_slice::<Value>(shown) {
+                Ok(mut json) => {
+                    redact_json(&mut json);
+                    return json.to_string();
+                }
+                Err(_) => unparsed(shown),
+            },
+            Some(content_type) if content_type.is_form() => redact_form(&String::from_utf8_lossy(shown)),
+            _ => unparsed(shown),
+        };
+        match truncated {
+            true => format!("{}…", text),
+            false => text,
+        }
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE.iter().any(|sensitive| name.contains(sensitive))
+}
+
+/// A body that couldn't be parsed, such as a cut or malformed one, whole or not at all:
+/// its credentials can't be told apart from the rest.
+fn unparsed(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    match is_sensitive(&text) {
+        true => "[withheld: mentions a sensitive field]".to_string(),
+        false => text.into_owned(),
+    }
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                match is_sensitive(name) {
+                    true => *value = Value::String(REDACTED.to_string()),
+                    false => redact_json(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+fn redact_form(form: &str) -> String {
+    form.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name) => format!("{}={}", name, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[rocket::async_trait]
+impl Fairing for BodyLog {
+    fn info(&self) -> Info {
+        Info { name: "Body log", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        // Peeked in full, so `render` tells a body it cut from one that ended.
+        if data.peek(PEEK_BYTES).await.is_empty() {
+            return;
+        }
+        let complete = data.peek_complete();
+        let body = self.render(request.content_type(), data.peek(PEEK_BYTES).await, complete);
+        tracing::info!(method = %request.method(), path = request.uri().path().as_str(), request_id = request_id::of(request), body, "request body");
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Reading a stream would hold all of it in memory; exports can be large.
+        if response.body().preset_size().is_none() {
+            return;
+        }
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!(request_id = request_id::of(request), "Failed to read the response body: {}", e);
+                return;
+            }
+        };
+        if !body.is_empty() {
+            let logged = self.render(response.content_type().as_ref(), &body, true);
+            tracing::info!(status = response.status().code, request_id = request_id::of(request), body = logged, "response body");
+        }
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
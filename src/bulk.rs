@@ -0,0 +1,43 @@
+use serde::Serialize;
+use uuid::Uuid;
+use crate::person::PersonPatch;
+use crate::validation::FieldErrors;
+
+/// Outcome of one item in a bulk request, reported in request order.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkStatus {
+    Created,
+    Updated,
+    NotFound,
+    /// The id is already taken in the store.
+    Conflict,
+    /// The id appears earlier in the same request.
+    Duplicate,
+    /// Valid on its own, but not applied because another item failed.
+    Skipped,
+    /// Fails the checks of a single create; `errors` says how.
+    Invalid,
+}
+
+/// One validated item of a bulk update: the id to change plus any subset of the person's fields.
+pub struct BulkPatch {
+    pub id: u32,
+    pub patch: PersonPatch,
+}
+
+#[derive(Serialize)]
+pub struct BulkDeleteResult {
+    pub deleted: Vec<u32>,
+    pub not_found: Vec<u32>,
+}
+
+#[derive(Serialize)]
+pub struct BulkResult {
+    pub id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<Uuid>,
+    pub status: BulkStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
+}
@@ -0,0 +1,66 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::Catcher;
+use crate::auth;
+use crate::errors::{ApiError, StatusError};
+use crate::listing;
+use crate::validation;
+
+pub fn get_catchers() -> Vec<Catcher> {
+    catchers![bad_request, unauthorized, forbidden, unprocessable, payload_too_large, unsupported_media_type, default_catcher]
+}
+
+/// Guards that check the query string record which parameter was wrong.
+#[catch(400)]
+fn bad_request(request: &Request<'_>) -> Result<ApiError, StatusError> {
+    match listing::recorded_errors(request) {
+        Some(errors) => Ok(ApiError::InvalidQuery(errors.clone())),
+        None => Err(StatusError(Status::BadRequest)),
+    }
+}
+
+#[catch(401)]
+fn unauthorized(request: &Request<'_>) -> ApiError {
+    match auth::recorded_error(request) {
+        Some(error) => ApiError::Unauthorized(error.message()),
+        None => ApiError::Unauthorized("authentication is required"),
+    }
+}
+
+#[catch(403)]
+fn forbidden(request: &Request<'_>) -> ApiError {
+    match auth::recorded_error(request) {
+        Some(error) => ApiError::Forbidden(error.message()),
+        None => ApiError::Forbidden("this request is not allowed"),
+    }
+}
+
+#[catch(422)]
+fn unprocessable(request: &Request<'_>) -> ApiError {
+    match validation::recorded_errors(request) {
+        Some(errors) => ApiError::Validation(errors.clone()),
+        None => ApiError::Unparsable,
+    }
+}
+
+/// Bodies over the limit of their format, e.g. `json` as set with `MAX_BODY_KIB`.
+#[catch(413)]
+fn payload_too_large(request: &Request<'_>) -> ApiError {
+    let format = match request.content_type() {
+        Some(content_type) if content_type.is_form_data() => "data-form",
+        _ => "json",
+    };
+    let limit = request.limits().get(format).unwrap_or_default();
+    ApiError::PayloadTooLarge(limit.as_u64() / 1024)
+}
+
+/// Raised by [`Valid`](crate::validation::Valid), so the body is always meant to be JSON.
+#[catch(415)]
+fn unsupported_media_type() -> ApiError {
+    ApiError::UnsupportedMediaType("application/json")
+}
+
+#[catch(default)]
+fn default_catcher(status: Status, _request: &Request<'_>) -> StatusError {
+    StatusError(status)
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use rocket::{Build, Rocket};
+use crate::export::{self, ExportFormat};
+use crate::repository::PersonRepository;
+use crate::seed;
+use crate::settings::{Overrides, Settings};
+use crate::tenant::DEFAULT_TENANT;
+
+#[derive(Parser)]
+#[command(version, about = "The persons API")]
+pub struct Cli {
+    /// Port to listen on, over `port` in Rocket.toml, ROCKET_PORT and PORT.
+    #[arg(long, global = true)]
+    port: Option<u16>,
+    /// Address to listen on, over `address` in Rocket.toml, ROCKET_ADDRESS and BIND_ADDRESS.
+    #[arg(long, global = true)]
+    address: Option<IpAddr>,
+    /// Start an in-memory store without a snapshot empty, ignoring SEED_FILE.
+    #[arg(long, global = true)]
+    no_seed: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn overrides(&self) -> Overrides {
+        Overrides { address: self.address, port: self.port, no_seed: self.no_seed }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the API (the default).
+    Serve,
+    /// Add the persons in a JSON array to the store.
+    Seed {
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long, default_value = DEFAULT_TENANT)]
+        tenant: String,
+    },
+    /// Write the stored persons to stdout.
+    Export {
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        #[arg(long, default_value = DEFAULT_TENANT)]
+        tenant: String,
+        /// Soft-deleted persons too.
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    /// Load and check the whole configuration, connect to the store, and exit.
+    CheckConfig,
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Refuses stores that would forget the persons as soon as `seed` exits.
+pub fn check_seedable(settings: &Settings) {
+    if settings.storage_backend == "memory" && env::var("PERSIST_PATH").is_err() && env::var("WAL_PATH").is_err() {
+        fail("The in-memory store keeps nothing after seed exits; set PERSIST_PATH or WAL_PATH".to_string());
+    }
+}
+
+pub async fn seed(file: &Path, tenant: &str, repository: Arc<dyn PersonRepository>) {
+    // The same checks and numbering as a `SEED_FILE` at startup.
+    let persons = seed::load(file).unwrap_or_else(|e| fail(format!("Failed to seed: {}", e)));
+    let count = persons.len();
+    if let Err(e) = repository.insert_many(tenant, persons).await {
+        fail(format!("Failed to seed {}: {}", tenant, e));
+    }
+    if let Err(e) = repository.close().await {
+        fail(format!("Failed to close the store: {}", e));
+    }
+    println!("Seeded {} persons into {}", count, tenant);
+}
+
+pub async fn export(format: ExportFormat, tenant: &str, include_deleted: bool, repository: Arc<dyn PersonRepository>) {
+    let mut persons = repository.list(tenant).await.unwrap_or_else(|e| fail(format!("Failed to list {}: {}", tenant, e)));
+    let now = Utc::now();
+    persons.retain(|p| !p.is_expired(now) && (include_deleted || p.deleted_at.is_none()));
+    let mut stdout = io::stdout().lock();
+    for row in export::rows(format, persons) {
+        if let Err(e) = stdout.write_all(row.as_bytes()) {
+            fail(format!("Failed to write the export: {}", e));
+        }
+    }
+}
+
+pub async fn check_config(rocket: Rocket<Build>) {
+    match rocket.ignite().await {
+        Ok(rocket) => {
+            let config = rocket.config();
+            println!("Configuration OK: {}:{} in the {} profile", config.address, config.port, config.profile);
+        }
+        Err(e) => fail(format!("Invalid configuration: {}", e.pretty_print())),
+    }
+}
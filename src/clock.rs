@@ -0,0 +1,121 @@
+use chrono::{DateTime, Datelike, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use serde::Serialize;
+use crate::errors::ApiError;
+
+/// One instant in the forms machine clients ask for, for `/api/time`.
+#[derive(Serialize)]
+pub struct Time {
+    pub rfc3339: String,
+    pub unix: i64,
+    pub unix_millis: i64,
+    /// The ISO 8601 week, such as `2026-W42`; its year differs from the calendar's around New Year.
+    pub iso_week: String,
+    pub day_of_year: u32,
+}
+
+impl Time {
+    pub fn at<Z: TimeZone>(time: &DateTime<Z>) -> Self
+    where
+        Z::Offset: std::fmt::Display,
+    {
+        let week = time.iso_week();
+        Time {
+            rfc3339: time.to_rfc3339(),
+            unix: time.timestamp(),
+            unix_millis: time.timestamp_millis(),
+            iso_week: format!("{}-W{:02}", week.year(), week.week()),
+            day_of_year: time.ordinal(),
+        }
+    }
+}
+
+/// The same instant on the clocks of `zone`, whose week and day may differ from UTC's.
+#[derive(Serialize)]
+pub struct LocalTime {
+    pub zone: &'static str,
+    #[serde(flatten)]
+    pub time: Time,
+}
+
+/// UTC's time, and that of the zone asked for if any.
+#[derive(Serialize)]
+pub struct TimeReport {
+    #[serde(flatten)]
+    pub utc: Time,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalTime>,
+}
+
+impl TimeReport {
+    pub fn at(time: DateTime<Utc>, zone: Option<Tz>) -> Self {
+        TimeReport {
+            utc: Time::at(&time),
+            local: zone.map(|zone| LocalTime { zone: zone.name(), time: Time::at(&time.with_timezone(&zone)) }),
+        }
+    }
+}
+
+/// Zones one `/api/time/world` request may ask for.
+pub const MAX_ZONES: usize = 50;
+
+/// The time in one zone of a world clock, with what a dashboard would label it by.
+#[derive(Serialize)]
+pub struct ZoneTime {
+    pub zone: &'static str,
+    pub time: String,
+    /// Such as `CEST`; none for zones the time zone database gives no letters, such as `Asia/Bangkok`.
+    pub abbreviation: Option<String>,
+    /// Such as `+02:00`, daylight saving time included.
+    pub utc_offset: String,
+    pub utc_offset_seconds: i32,
+    pub dst: bool,
+}
+
+#[derive(Serialize)]
+pub struct WorldClock {
+    pub utc: String,
+    pub zones: Vec<ZoneTime>,
+}
+
+impl WorldClock {
+    pub fn at(time: DateTime<Utc>, zones: &[Tz]) -> Self {
+        let zones = zones.iter()
+            .map(|zone| {
+                let local = time.with_timezone(zone);
+                let offset = local.offset();
+                ZoneTime {
+                    zone: zone.name(),
+                    time: local.to_rfc3339(),
+                    abbreviation: offset.abbreviation().map(str::to_string),
+                    utc_offset: offset.fix().to_string(),
+                    utc_offset_seconds: offset.fix().local_minus_utc(),
+                    dst: !offset.dst_offset().is_zero(),
+                }
+            })
+            .collect();
+        WorldClock { utc: time.to_rfc3339(), zones }
+    }
+}
+
+/// The IANA zones in the comma-separated `zones` parameter, in the order given.
+pub fn zones(list: Option<&str>) -> Result<Vec<Tz>, ApiError> {
+    let names: Vec<&str> = list.unwrap_or("").split(',').map(str::trim).filter(|name| !name.is_empty()).collect();
+    if names.is_empty() {
+        return Err(ApiError::query("zones", "must list time zone names, such as UTC,Asia/Bangkok"));
+    }
+    if names.len() > MAX_ZONES {
+        return Err(ApiError::query("zones", format!("must list at most {} zones", MAX_ZONES)));
+    }
+    names.into_iter()
+        .map(|name| name.parse().map_err(|_| ApiError::query("zones", format!("{} is not a time zone name", name))))
+        .collect()
+}
+
+/// The IANA zone named by the `tz` parameter, such as `Europe/Berlin`, or else `default`.
+pub fn zone(tz: Option<&str>, default: Option<Tz>) -> Result<Option<Tz>, ApiError> {
+    match tz {
+        Some(name) => name.parse().map(Some).map_err(|_| ApiError::query("tz", "must be a time zone name such as Europe/Berlin")),
+        None => Ok(default),
+    }
+}
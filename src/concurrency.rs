@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use crate::errors::ApiError;
+use crate::refusal;
+
+/// Answers a `503` with `Retry-After: 1` to requests beyond `max_concurrent_requests` in
+/// flight, so an overloaded instance sheds load instead of queueing it. Rocket 0.5 can't
+/// cap connections themselves; idle keep-alive connections aren't counted.
+pub struct ConcurrencyLimit {
+    max: usize,
+    in_flight: AtomicUsize,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize) -> Self {
+        ConcurrencyLimit { max, in_flight: AtomicUsize::new(0) }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ConcurrencyLimit {
+    fn info(&self) -> Info {
+        Info { name: "Concurrency limit", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        // Refused requests count too until answered, which is soon.
+        if self.in_flight.fetch_add(1, Ordering::AcqRel) >= self.max {
+            refusal::refuse(request, ApiError::Overloaded);
+        }
+    }
+
+    async fn on_response<'r>(&self, _: &'r Request<'_>, _: &mut Response<'r>) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,103 @@
+use std::env;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Method, Status};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Route;
+
+const DEFAULT_METHODS: &str = "GET, HEAD, POST, PUT, PATCH, DELETE";
+const DEFAULT_HEADERS: &str = "Accept, Authorization, Content-Type, If-Match, If-None-Match, X-Api-Key, X-Request-Id, X-Signature, X-Signature-Timestamp, X-Tenant-Id";
+/// Response headers besides the CORS-safelisted ones that browser code may read.
+const EXPOSED_HEADERS: &str = "Content-Disposition, ETag, Location, Retry-After, X-Next-Cursor, X-Next-Offset, X-Request-Id, X-Total-Count";
+const DEFAULT_MAX_AGE_SECS: u64 = 600;
+
+/// Preflight requests are answered here, for any path; the fairing adds the headers.
+pub fn routes() -> Vec<Route> {
+    routes![preflight]
+}
+
+#[options("/<_..>")]
+fn preflight() -> Status {
+    Status::NoContent
+}
+
+/// Cross-origin access for browser frontends, enabled by `CORS_ALLOWED_ORIGINS`
+/// (comma-separated, or `*` for any; read with the [settings](crate::settings::Settings)). `CORS_ALLOWED_METHODS` and `CORS_ALLOWED_HEADERS`
+/// narrow or widen what preflights allow, `CORS_ALLOW_CREDENTIALS` lets cookies
+/// through and `CORS_MAX_AGE_SECS` says how long browsers may cache a preflight.
+pub struct Cors {
+    origins: Vec<String>,
+    methods: String,
+    headers: String,
+    credentials: bool,
+    max_age: u64,
+}
+
+fn split(items: &str) -> Vec<String> {
+    items.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn list(variable: &str, default: &str) -> Vec<String> {
+    split(&env::var(variable).unwrap_or_else(|_| default.to_string()))
+}
+
+impl Cors {
+    pub fn from_env(origins: &str) -> Option<Self> {
+        let origins = split(origins);
+        if origins.is_empty() {
+            return None;
+        }
+        let methods = list("CORS_ALLOWED_METHODS", DEFAULT_METHODS).iter().map(|m| m.to_ascii_uppercase()).collect::<Vec<_>>();
+        Some(Cors {
+            origins: origins.into_iter().map(|origin| origin.trim_end_matches('/').to_string()).collect(),
+            methods: methods.join(", "),
+            headers: list("CORS_ALLOWED_HEADERS", DEFAULT_HEADERS).join(", "),
+            credentials: env::var("CORS_ALLOW_CREDENTIALS").is_ok_and(|v| v == "true" || v == "1"),
+            max_age: env::var("CORS_MAX_AGE_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_AGE_SECS),
+        })
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.split(", ").any(|allowed| allowed.eq_ignore_ascii_case(method))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info { name: "CORS", kind: Kind::Response }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.adjoin_raw_header("Vary", "Origin");
+        let Some(origin) = request.headers().get_one("Origin").filter(|origin| self.allows_origin(origin)) else {
+            return;
+        };
+        // With credentials browsers reject `*`, so the origin is always echoed.
+        response.set_raw_header("Access-Control-Allow-Origin", origin.to_string());
+        if self.credentials {
+            response.set_raw_header("Access-Control-Allow-Credentials", "true");
+        }
+        let requested_method = request.headers().get_one("Access-Control-Request-Method");
+        match requested_method {
+            Some(method) if request.method() == Method::Options => {
+                if self.allows_method(method) {
+                    response.set_raw_header("Access-Control-Allow-Methods", self.methods.clone());
+                    response.set_raw_header("Access-Control-Allow-Headers", self.headers.clone());
+                    response.set_raw_header("Access-Control-Max-Age", self.max_age.to_string());
+                }
+            }
+            _ => {
+                response.set_raw_header("Access-Control-Expose-Headers", EXPOSED_HEADERS);
+            }
+        }
+    }
+}
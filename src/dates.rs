@@ -0,0 +1,30 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+/// Formats without an offset that older clients and stored data use; they are read as UTC.
+const LEGACY_DATE_TIMES: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y/%m/%d %H:%M:%S"];
+const LEGACY_DATES: [&str; 3] = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y"];
+
+/// Reads an RFC 3339 timestamp, or one of the legacy formats: a bare date
+/// (`1981-02-21`, `1981/02/21`, `21.02.1981`) is midnight UTC.
+pub fn parse(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    LEGACY_DATE_TIMES.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            LEGACY_DATES.iter()
+                .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|naive| naive.and_utc())
+}
+
+/// `deserialize_with` for stored and uploaded persons, so data written before
+/// `date` became a timestamp still loads. Serialization is plain RFC 3339.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid date {:?}", text)))
+}
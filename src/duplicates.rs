@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use crate::person::Person;
+use crate::search;
+
+/// Persons that probably describe the same individual.
+#[derive(Serialize)]
+pub struct DuplicateCluster {
+    /// The normalized name the members share.
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<u8>,
+    pub persons: Vec<Person>,
+}
+
+/// Groups persons whose names are equal once case, accents, punctuation and
+/// spacing are ignored (and, with `by_age`, whose ages match too). Only groups
+/// with more than one member are returned, largest first.
+pub fn find(persons: Vec<Person>, by_age: bool) -> Vec<DuplicateCluster> {
+    let mut groups: BTreeMap<(String, Option<u8>), Vec<Person>> = BTreeMap::new();
+    for person in persons {
+        let name = search::tokenize(&person.name).join(" ");
+        if name.is_empty() {
+            continue;
+        }
+        let age = by_age.then_some(person.age);
+        groups.entry((name, age)).or_default().push(person);
+    }
+    let mut clusters: Vec<DuplicateCluster> = groups.into_iter()
+        .filter(|(_, persons)| persons.len() > 1)
+        .map(|((name, age), persons)| DuplicateCluster { name, age, persons })
+        .collect();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.persons.len()));
+    clusters
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use rocket::async_trait;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::person::Person;
+use crate::repository::{AppliedMigration, ComponentCheck, LockStats, PersonRepository, RepositoryError};
+use crate::secrets;
+
+/// Starts the stored `name` of an encrypted person, followed by `<key id>:<base64 nonce and ciphertext>`.
+const PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+/// The fields sealed together into the stored `name`.
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    name: String,
+    date: DateTime<Utc>,
+}
+
+/// AES-256-GCM keys from `FIELD_ENCRYPTION_KEYS`, comma-separated `<key id>:<base64 key>`
+/// entries. The first key encrypts; all of them decrypt, so a new key goes in front and
+/// the old one stays until every person has been written again.
+pub struct FieldEncryption {
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl FieldEncryption {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(value) = secrets::var("FIELD_ENCRYPTION_KEYS") else {
+            return Ok(None);
+        };
+        let mut keys: Vec<(String, Aes256Gcm)> = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let Some((id, key)) = entry.split_once(':') else {
+                return Err(format!("FIELD_ENCRYPTION_KEYS: {:?} is not <key id>:<base64 key>", entry));
+            };
+            if id.is_empty() || keys.iter().any(|(known, _)| known == id) {
+                return Err(format!("FIELD_ENCRYPTION_KEYS: key ids must be unique and not empty, got {:?}", id));
+            }
+            let key = STANDARD.decode(key.trim()).ok()
+                .filter(|key| key.len() == 32)
+                .ok_or_else(|| format!("FIELD_ENCRYPTION_KEYS: key {} must be 32 bytes in base64", id))?;
+            keys.push((id.to_string(), Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))));
+        }
+        if keys.is_empty() {
+            return Err("FIELD_ENCRYPTION_KEYS holds no keys".to_string());
+        }
+        Ok(Some(FieldEncryption { keys }))
+    }
+
+    /// Binds the ciphertext to its person, so it can't be copied onto another one.
+    fn associated_data(tenant: &str, id: u32) -> String {
+        format!("{}/{}", tenant, id)
+    }
+
+    fn seal(&self, tenant: &str, mut person: Person) -> Result<Person, RepositoryError> {
+        let (id, cipher) = &self.keys[0];
+        let sealed = Sealed { name: person.name, date: person.date };
+        let plaintext = serde_json::to_vec(&sealed).expect("a name and a timestamp serialize");
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = Self::associated_data(tenant, person.id);
+        let ciphertext = cipher.encrypt(&nonce, Payload { msg: &plaintext, aad: aad.as_bytes() })
+            .map_err(|_| RepositoryError::Internal(format!("person {} could not be encrypted", person.id)))?;
+        let mut stored = nonce.to_vec();
+        stored.extend(ciphertext);
+        person.name = format!("{}{}:{}", PREFIX, id, STANDARD.encode(stored));
+        // The real date is sealed with the name; the column only needs some valid value.
+        person.date = DateTime::UNIX_EPOCH;
+        Ok(person)
+    }
+
+    /// Persons written before encryption was turned on are passed through as they are.
+    fn open(&self, tenant: &str, mut person: Person) -> Result<Person, RepositoryError> {
+        let Some(sealed) = person.name.strip_prefix(PREFIX) else {
+            return Ok(person);
+        };
+        let failed = |reason: &str| RepositoryError::Internal(format!("person {} could not be decrypted: {}", person.id, reason));
+        let (key_id, data) = sealed.split_once(':').ok_or_else(|| failed("no key id"))?;
+        let (_, cipher) = self.keys.iter().find(|(id, _)| id == key_id).ok_or_else(|| failed(&format!("unknown key {}", key_id)))?;
+        let data = STANDARD.decode(data).map_err(|_| failed("invalid base64"))?;
+        if data.len() < NONCE_LEN {
+            return Err(failed("too short"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let aad = Self::associated_data(tenant, person.id);
+        let plaintext = cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| failed(&format!("wrong key {} or tampered data", key_id)))?;
+        let sealed: Sealed = serde_json::from_slice(&plaintext).map_err(|e| failed(&e.to_string()))?;
+        person.name = sealed.name;
+        person.date = sealed.date;
+        Ok(person)
+    }
+
+    fn seal_all(&self, tenant: &str, persons: Vec<Person>) -> Result<Vec<Person>, RepositoryError> {
+        persons.into_iter().map(|person| self.seal(tenant, person)).collect()
+    }
+}
+
+/// Stores every person's name and date encrypted with [`FieldEncryption`] and
+/// decrypts them on the way out, so the layers above only ever see plaintext.
+pub struct EncryptedRepository {
+    inner: Arc<dyn PersonRepository>,
+    encryption: FieldEncryption,
+}
+
+impl EncryptedRepository {
+    pub fn new(inner: Arc<dyn PersonRepository>, encryption: FieldEncryption) -> Self {
+        EncryptedRepository { inner, encryption }
+    }
+}
+
+#[async_trait]
+impl PersonRepository for EncryptedRepository {
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        self.inner.migrations().await
+    }
+
+    async fn check(&self) -> Vec<ComponentCheck> {
+        self.inner.check().await
+    }
+
+    fn lock_waits(&self) -> Option<LockStats> {
+        self.inner.lock_waits()
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.inner.close().await
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        self.inner.tenants().await
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        self.inner.list(tenant).await?.into_iter().map(|person| self.encryption.open(tenant, person)).collect()
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        self.inner.get(tenant, id).await?.map(|person| self.encryption.open(tenant, person)).transpose()
+    }
+
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        self.inner.find_by_uuid(tenant, uuid).await?.map(|person| self.encryption.open(tenant, person)).transpose()
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.inner.insert(tenant, self.encryption.seal(tenant, person)?).await
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.insert_many(tenant, self.encryption.seal_all(tenant, persons)?).await
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.inner.update(tenant, self.encryption.seal(tenant, person)?).await
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.update_many(tenant, self.encryption.seal_all(tenant, persons)?).await
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        self.inner.delete(tenant, id).await
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        self.inner.delete_many(tenant, ids).await
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.replace_all(tenant, self.encryption.seal_all(tenant, persons)?).await
+    }
+}
@@ -0,0 +1,64 @@
+use std::env;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use sentry::types::Dsn;
+use sentry::{ClientInitGuard, ClientOptions, Level};
+use crate::errors;
+use crate::refusal;
+use crate::request_id;
+
+/// Reports 5xx responses and panics to Sentry at `SENTRY_DSN`. Events carry the request
+/// id, method, route, path and status, but no query string, headers, body or client IP.
+pub struct ErrorReporting {
+    /// Sends what is still queued when the service exits.
+    _guard: ClientInitGuard,
+}
+
+impl ErrorReporting {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(dsn) = env::var("SENTRY_DSN") else {
+            return Ok(None);
+        };
+        let dsn: Dsn = dsn.parse().map_err(|e| format!("SENTRY_DSN: {}", e))?;
+        // `SENTRY_ENVIRONMENT` and `SENTRY_RELEASE` are read by the client itself.
+        let guard = sentry::init(ClientOptions {
+            dsn: Some(dsn),
+            release: sentry::release_name!(),
+            send_default_pii: false,
+            ..ClientOptions::default()
+        });
+        Ok(Some(ErrorReporting { _guard: guard }))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ErrorReporting {
+    fn info(&self) -> Info {
+        Info { name: "Sentry", kind: Kind::Response }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let status = response.status();
+        if status.code < 500 {
+            return;
+        }
+        let route = request.route().map(|route| route.uri.path().to_string());
+        let message = match errors::server_error(request) {
+            Some(error) => error.to_string(),
+            // Panics, whose own event holds the details, and errors answered without an `ApiError`.
+            None => format!("{} {}", status.code, status.reason().unwrap_or("Server Error")),
+        };
+        sentry::with_scope(
+            |scope| {
+                scope.set_transaction(route.as_deref().map(|route| format!("{} {}", request.method(), route)).as_deref());
+                scope.set_tag("request_id", request_id::of(request));
+                scope.set_tag("method", request.method());
+                scope.set_tag("route", route.as_deref().unwrap_or("unmatched"));
+                scope.set_tag("path", refusal::original_path(request));
+                scope.set_tag("status", status.code);
+            },
+            || sentry::capture_message(&message, Level::Error),
+        );
+    }
+}
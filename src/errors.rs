@@ -1,2 +1,247 @@
-// Rocket uses Status enum directly, no custom error handling needed
-// This file is kept for compatibility but not used
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::content::RawHtml;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use serde::Serialize;
+use thiserror::Error;
+use crate::backup::BackupError;
+use crate::refusal;
+use crate::repository::RepositoryError;
+use crate::request_id;
+use crate::validation::FieldErrors;
+use crate::AppState;
+
+/// Everything a handler can fail with. Each variant answers one status with
+/// the same JSON envelope the catchers use.
+#[derive(Debug, Clone, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    /// Query parameters that were rejected, with messages keyed by parameter.
+    #[error("the query string is invalid")]
+    InvalidQuery(FieldErrors),
+    #[error("{0}")]
+    Unauthorized(&'static str),
+    #[error("{0}")]
+    Forbidden(&'static str),
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("{0}")]
+    Conflict(&'static str),
+    #[error("the name is already used by person {0}")]
+    NameTaken(u32),
+    #[error("If-Match does not match the current version")]
+    PreconditionFailed,
+    #[error("this write requires an If-Match header")]
+    PreconditionRequired,
+    #[error("the request body failed validation")]
+    Validation(FieldErrors),
+    #[error("the request body could not be parsed")]
+    Unparsable,
+    #[error("the request body is larger than {0} KiB")]
+    PayloadTooLarge(u64),
+    /// Names the content types the endpoint accepts.
+    #[error("the request body must be sent with Content-Type: {0}")]
+    UnsupportedMediaType(&'static str),
+    /// Seconds until the client's next request will be accepted.
+    #[error("too many requests; retry in {0} s")]
+    TooManyRequests(u64),
+    /// Seconds until a client that failed to authenticate too often may try again.
+    #[error("too many failed authentication attempts; retry in {0} s")]
+    LockedOut(u64),
+    /// Too many requests in flight; the client may retry in a second.
+    #[error("the server is busy; retry in 1 s")]
+    Overloaded,
+    /// The maintenance notice, and seconds until the service should be back.
+    #[error("{0}")]
+    Maintenance(String, u64),
+    #[error("storage unavailable: {0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    /// A single rejected query parameter.
+    pub fn query(parameter: &str, message: impl Into<String>) -> Self {
+        let mut errors = FieldErrors::default();
+        errors.add(parameter, message);
+        ApiError::InvalidQuery(errors)
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) | ApiError::InvalidQuery(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(_) | ApiError::NameTaken(_) => Status::Conflict,
+            ApiError::PreconditionFailed => Status::PreconditionFailed,
+            ApiError::PreconditionRequired => Status::PreconditionRequired,
+            ApiError::Validation(_) | ApiError::Unparsable => Status::UnprocessableEntity,
+            ApiError::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            ApiError::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            ApiError::TooManyRequests(_) | ApiError::LockedOut(_) => Status::TooManyRequests,
+            ApiError::Overloaded | ApiError::Maintenance(..) | ApiError::Unavailable(_) => Status::ServiceUnavailable,
+            ApiError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+/// The body of every error answer, so API clients never have to parse Rocket's HTML pages.
+#[derive(Serialize)]
+struct ErrorBody<'r> {
+    code: u16,
+    message: String,
+    request_id: &'r str,
+    #[serde(flatten)]
+    details: Details<'r>,
+}
+
+/// Members only some errors carry.
+#[derive(Serialize, Default)]
+struct Details<'r> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'r FieldErrors>,
+    /// The person already holding a unique value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflicting_id: Option<u32>,
+}
+
+/// The same answer as an RFC 7807 problem, for clients that accept `application/problem+json`.
+#[derive(Serialize)]
+struct Problem<'r> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    instance: String,
+    request_id: &'r str,
+    #[serde(flatten)]
+    details: Details<'r>,
+}
+
+/// Browsers, which list `text/html` first, rather than API clients.
+fn wants_html(request: &Request<'_>) -> bool {
+    request.accept().is_some_and(|accept| accept.preferred().is_html())
+}
+
+fn wants_problem(request: &Request<'_>) -> bool {
+    request.accept().is_some_and(|accept| {
+        accept.media_types().any(|media| media.top() == "application" && media.sub() == "problem+json")
+    })
+}
+
+/// Answers `status` in whichever error format the client accepts.
+fn respond(request: &Request<'_>, status: Status, message: String, details: Details<'_>) -> response::Result<'static> {
+    let request_id = request_id::of(request);
+    let mut response = match wants_problem(request) {
+        true => {
+            let problem = Problem {
+                kind: "about:blank",
+                title: status.reason().unwrap_or("Unknown error"),
+                status: status.code,
+                detail: message,
+                instance: refusal::original_path(request).to_string(),
+                request_id,
+                details,
+            };
+            let json = serde_json::to_string(&problem).map_err(|_| Status::InternalServerError)?;
+            Response::build_from((ContentType::new("application", "problem+json"), json).respond_to(request)?).finalize()
+        }
+        false => Json(ErrorBody { code: status.code, message, request_id, details }).respond_to(request)?,
+    };
+    response.set_status(status);
+    response.set_raw_header("Vary", "Accept");
+    Ok(response)
+}
+
+/// What went wrong on the server for a request answered with a 5xx [`ApiError`].
+#[cfg(feature = "sentry")]
+struct ServerError(Option<String>);
+
+#[cfg(feature = "sentry")]
+pub fn server_error<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request.local_cache(|| ServerError(None)).0.as_deref()
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        // Storage details go to the log, not to clients, unless `error_detail` is on.
+        let message = match &self {
+            ApiError::Unavailable(_) | ApiError::Internal(_) => {
+                error!("{} {} (request {}): {}", request.method(), request.uri(), request_id::of(request), self);
+                #[cfg(feature = "sentry")]
+                request.local_cache(|| ServerError(Some(self.to_string())));
+                match request.rocket().state::<AppState>().is_some_and(|state| state.error_detail) {
+                    true => self.to_string(),
+                    false => status.reason().unwrap_or("Internal Server Error").to_string(),
+                }
+            }
+            _ => self.to_string(),
+        };
+        let details = match &self {
+            ApiError::InvalidQuery(errors) | ApiError::Validation(errors) => Details { errors: Some(errors), ..Details::default() },
+            ApiError::NameTaken(id) => Details { conflicting_id: Some(*id), ..Details::default() },
+            _ => Details::default(),
+        };
+        let mut response = match &self {
+            ApiError::Maintenance(notice, _) if wants_html(request) => {
+                let page = format!(
+                    "<!DOCTYPE html><title>Down for maintenance</title><h1>Down for maintenance</h1><p>{}</p>",
+                    quick_xml::escape::escape(notice.as_str()),
+                );
+                let mut response = RawHtml(page).respond_to(request)?;
+                response.set_status(status);
+                response.set_raw_header("Vary", "Accept");
+                response
+            }
+            _ => respond(request, status, message, details)?,
+        };
+        let retry_after = match self {
+            ApiError::TooManyRequests(seconds) | ApiError::LockedOut(seconds) | ApiError::Maintenance(_, seconds) => Some(seconds),
+            ApiError::Overloaded => Some(1),
+            _ => None,
+        };
+        if let Some(seconds) = retry_after {
+            response.set_raw_header("Retry-After", seconds.to_string());
+        }
+        Ok(response)
+    }
+}
+
+/// A status with nothing more to say than its reason, as answered by the default catcher.
+pub struct StatusError(pub Status);
+
+impl<'r> Responder<'r, 'static> for StatusError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        respond(request, self.0, self.0.reason().unwrap_or("Unknown error").to_string(), Details::default())
+    }
+}
+
+impl From<RepositoryError> for ApiError {
+    fn from(error: RepositoryError) -> Self {
+        match error {
+            RepositoryError::NotFound => ApiError::NotFound("person"),
+            RepositoryError::Conflict => ApiError::Conflict("the person already exists or was changed concurrently"),
+            RepositoryError::NameTaken(id) => ApiError::NameTaken(id),
+            RepositoryError::Unavailable(message) => ApiError::Unavailable(message),
+            RepositoryError::Internal(message) => ApiError::Internal(message),
+        }
+    }
+}
+
+impl From<BackupError> for ApiError {
+    fn from(error: BackupError) -> Self {
+        match error {
+            BackupError::InvalidName => ApiError::BadRequest("backup file names may not contain a path".to_string()),
+            BackupError::Parse(e) => ApiError::BadRequest(format!("the backup is not a valid person list: {}", e)),
+            BackupError::NotFound => ApiError::NotFound("backup"),
+            BackupError::WrongTenant(tenant) => ApiError::BadRequest(format!("the backup is of tenant {}", tenant)),
+            BackupError::Io(e) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use serde::Serialize;
+use crate::errors::ApiError;
+use crate::links::Hypermedia;
+use crate::negotiate;
+use crate::person::Person;
+use crate::AppState;
+
+/// What a strong validator is derived from: the JSON form of a value, so any change to
+/// what a client would receive changes it, and for a person its version as well.
+pub struct Tag {
+    version: Option<u32>,
+    hash: u64,
+}
+
+impl Tag {
+    pub fn of(value: &impl Serialize) -> Self {
+        let mut hasher = DefaultHasher::new();
+        // Tagged bodies are persons and pages of them, which always serialize.
+        hasher.write(&serde_json::to_vec(value).unwrap_or_default());
+        Tag { version: None, hash: hasher.finish() }
+    }
+
+    /// Such as `"3-…"`: the version, for people reading it, and a hash of everything
+    /// sent, so a person deleted and created again with the same id gets new tags.
+    pub fn person(body: &impl Serialize, person: &Person) -> Self {
+        Tag { version: Some(person.version), ..Tag::of(body) }
+    }
+
+    /// The `ETag` of a response of `content_type`, so JSON and XML bodies differ.
+    fn header(&self, content_type: Option<&ContentType>) -> String {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.hash);
+        if let Some(content_type) = content_type {
+            hasher.write(content_type.to_string().as_bytes());
+        }
+        match self.version {
+            Some(version) => format!("\"{}-{:016x}\"", version, hasher.finish()),
+            None => format!("\"{:016x}\"", hasher.finish()),
+        }
+    }
+}
+
+/// `If-None-Match` compares weakly, ignoring `W/`; `If-Match` only takes strong tags.
+fn matches(header: &str, tag: &str, weak: bool) -> bool {
+    header.split(',')
+        .map(str::trim)
+        .map(|candidate| match weak {
+            true => candidate.trim_start_matches("W/"),
+            false => candidate,
+        })
+        .any(|candidate| candidate == "*" || candidate == tag)
+}
+
+/// Every tag `person` is currently served with, in each format, with links or without.
+fn person_tags(person: &Person) -> Vec<String> {
+    let content_types = [ContentType::JSON, negotiate::xml()];
+    [false, true].into_iter()
+        .map(|links| Tag::person(&Hypermedia(links).wrap(person.clone()), person))
+        .flat_map(|tag| content_types.iter().map(move |content_type| tag.header(Some(content_type))).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Wraps a response with an `ETag` header and answers `304 Not Modified`
+/// when the request's `If-None-Match` already names that tag.
+pub struct Tagged<R> {
+    inner: R,
+    tag: Tag,
+}
+
+impl<R> Tagged<R> {
+    pub fn new(inner: R, tag: Tag) -> Self {
+        Tagged { inner, tag }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Tagged<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        // Rendered first, as only the response knows the format negotiated.
+        let response = self.inner.respond_to(request)?;
+        let tag = self.tag.header(response.content_type().as_ref());
+        let not_modified = request.headers()
+            .get("If-None-Match")
+            .any(|value| matches(value, &tag, true));
+        let mut response = match not_modified {
+            true => Response::build().status(Status::NotModified).raw_header("Vary", "Accept").finalize(),
+            false => response,
+        };
+        response.set_header(Header::new("ETag", tag));
+        Ok(response)
+    }
+}
+
+/// The `If-Match` header of a write. With `REQUIRE_IF_MATCH` set, writes
+/// without one are refused with `428 Precondition Required`.
+pub struct IfMatch {
+    value: Option<String>,
+    required: bool,
+}
+
+impl IfMatch {
+    pub fn check(&self, person: &Person) -> Result<(), ApiError> {
+        match &self.value {
+            None if self.required => Err(ApiError::PreconditionRequired),
+            None => Ok(()),
+            Some(value) if person_tags(person).iter().any(|tag| matches(value, tag, false)) => Ok(()),
+            Some(_) => Err(ApiError::PreconditionFailed),
+        }
+    }
+
+    /// For writes that create the person: any `If-Match` refers to a
+    /// representation that doesn't exist, so it can only fail.
+    pub fn check_absent(&self) -> Result<(), ApiError> {
+        match self.value {
+            Some(_) => Err(ApiError::PreconditionFailed),
+            None => Ok(()),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let required = request.rocket().state::<AppState>().is_some_and(|state| state.require_if_match);
+        let value = request.headers().get_one("If-Match").map(str::to_string);
+        Outcome::Success(IfMatch { value, required })
+    }
+}
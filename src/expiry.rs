@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+use rocket::tokio;
+use crate::audit::AuditLog;
+use crate::repository::{PersonRepository, RepositoryError};
+
+/// Spawns a background task that deletes persons whose `expires_at` has passed, recording
+/// each tenant's purge in the audit log.
+pub fn fairing(repository: Arc<dyn PersonRepository>, audit: Arc<AuditLog>, interval: Duration) -> AdHoc {
+    AdHoc::on_liftoff("Person Expiry", move |_| Box::pin(async move {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match purge_expired(repository.as_ref(), &audit).await {
+                    Ok(0) => {}
+                    Ok(purged) => info!("Purged {} expired person(s)", purged),
+                    Err(e) => warn!("Failed to purge expired persons: {}", e),
+                }
+            }
+        });
+    }))
+}
+
+async fn purge_expired(repository: &dyn PersonRepository, audit: &AuditLog) -> Result<usize, RepositoryError> {
+    let now = Utc::now();
+    let mut purged = 0;
+    for tenant in repository.tenants().await? {
+        let expired: Vec<u32> = repository.list(&tenant).await?.into_iter()
+            .filter(|person| person.is_expired(now))
+            .map(|person| person.id)
+            .collect();
+        if expired.is_empty() {
+            continue;
+        }
+        // Those another replica or request removed first aren't among the deleted.
+        let deleted = repository.delete_many(&tenant, expired).await?;
+        purged += deleted.len();
+        if !deleted.is_empty() {
+            audit.record_purge(&tenant, deleted);
+        }
+    }
+    Ok(purged)
+}
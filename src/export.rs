@@ -0,0 +1,70 @@
+use rocket::futures::stream;
+use rocket::http::ContentType;
+use rocket::request::Request;
+use rocket::response::stream::TextStream;
+use rocket::response::{self, Responder, Response};
+use crate::person::Person;
+
+const CSV_HEADER: &str = "id,name,age,date,expires_at,version,uuid\n";
+
+#[derive(FromFormField, clap::ValueEnum, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+/// A full download of the collection, encoded row by row as the body is sent.
+pub struct Export {
+    pub format: ExportFormat,
+    pub persons: Vec<Person>,
+}
+
+/// One JSON object per line, so consumers can parse the download incrementally.
+fn ndjson_row(person: Person) -> String {
+    let mut line = serde_json::to_string(&person).expect("a person has no maps with non-string keys");
+    line.push('\n');
+    line
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(person: Person) -> String {
+    format!(
+        "{},{},{},{},{},{},{}\n",
+        person.id,
+        csv_field(&person.name),
+        person.age,
+        person.date.to_rfc3339(),
+        person.expires_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        person.version,
+        person.uuid.map(|u| u.to_string()).unwrap_or_default(),
+    )
+}
+
+/// `persons` encoded as `format`, a header (for CSV) and then one chunk per person.
+pub fn rows(format: ExportFormat, persons: Vec<Person>) -> Box<dyn Iterator<Item = String> + Send> {
+    match format {
+        ExportFormat::Csv => Box::new(std::iter::once(CSV_HEADER.to_string()).chain(persons.into_iter().map(csv_row))),
+        ExportFormat::Ndjson => Box::new(persons.into_iter().map(ndjson_row)),
+    }
+}
+
+impl<'r> Responder<'r, 'r> for Export {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let (content_type, extension) = match self.format {
+            ExportFormat::Csv => (ContentType::CSV, "csv"),
+            ExportFormat::Ndjson => (ContentType::new("application", "x-ndjson"), "ndjson"),
+        };
+        let rows = rows(self.format, self.persons);
+        Response::build_from(TextStream(stream::iter(rows)).respond_to(request)?)
+            .header(content_type)
+            .raw_header("Content-Disposition", format!("attachment; filename=\"persons.{}\"", extension))
+            .ok()
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Serialize;
+use serde_json::Value;
+use crate::errors::ApiError;
+
+/// Person fields a client may select with `?fields=`.
+pub const FIELDS: [&str; 9] = ["id", "name", "age", "date", "deleted_at", "expires_at", "version", "uuid", "addresses"];
+
+/// Members kept in every projection because they are not data fields.
+const ALWAYS_KEPT: [&str; 1] = ["_links"];
+
+/// A sparse fieldset parsed from a comma-separated `?fields=` value.
+pub struct FieldSet(Vec<String>);
+
+impl FieldSet {
+    /// Naming anything but [`FIELDS`] is a `400` that lists the valid ones.
+    pub fn parse(spec: &str) -> Result<Self, ApiError> {
+        let requested: Vec<String> = spec.split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+        let unknown: Vec<String> = requested.iter()
+            .filter(|field| !FIELDS.contains(&field.as_str()))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() || requested.is_empty() {
+            let mut message = format!("must name fields among {}", FIELDS.join(", "));
+            if !unknown.is_empty() {
+                message = format!("{}; unknown: {}", message, unknown.join(", "));
+            }
+            return Err(ApiError::query("fields", message));
+        }
+        Ok(FieldSet(requested))
+    }
+
+    pub fn project<T: Serialize>(&self, value: T) -> Projected<T> {
+        // Only structs have fields to pick; anything else is sent whole.
+        let Ok(Value::Object(mut members)) = serde_json::to_value(&value) else {
+            return Projected::Full(value);
+        };
+        members.retain(|key, _| self.0.contains(key) || ALWAYS_KEPT.contains(&key.as_str()));
+        Projected::Partial(Value::Object(members))
+    }
+}
+
+/// An item as requested: whole, or cut down to a fieldset.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Projected<T> {
+    Full(T),
+    Partial(Value),
+}
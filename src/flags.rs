@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome};
+use rocket::{Data, Route};
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+
+/// Serves the v1 person routes under `/api/v2` as a preview.
+pub const API_V2: &str = "api_v2";
+/// The flags the code checks, all off unless configured.
+pub const KNOWN: [&str; 1] = [API_V2];
+
+/// Feature flags from the `flags` table in `Rocket.toml` and `FEATURE_<NAME>` variables,
+/// reloaded with the rest of the [`Live`](crate::reload::Live) settings.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Flags(BTreeMap<String, bool>);
+
+impl Flags {
+    pub fn enabled(&self, name: &str) -> bool {
+        self.0.get(name).copied().unwrap_or(false)
+    }
+
+    /// Names the code doesn't check, most likely misspelt.
+    pub fn unknown(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str).filter(|name| !KNOWN.contains(name))
+    }
+
+    /// Every known flag and every configured one, for `/admin/flags`.
+    pub fn list(&self) -> BTreeMap<String, bool> {
+        let mut flags: BTreeMap<String, bool> = KNOWN.iter().map(|name| (name.to_string(), false)).collect();
+        flags.extend(self.0.iter().map(|(name, enabled)| (name.clone(), *enabled)));
+        flags
+    }
+}
+
+#[derive(Clone)]
+struct Gated {
+    flag: &'static str,
+    handler: Box<dyn Handler>,
+}
+
+#[rocket::async_trait]
+impl Handler for Gated {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let enabled = request.rocket().state::<AppState>().is_some_and(|state| state.live.current().flags.enabled(self.flag));
+        match enabled {
+            true => self.handler.handle(request, data).await,
+            false => Outcome::Forward((data, Status::NotFound)),
+        }
+    }
+}
+
+/// Answers `routes` with a 404, as if they weren't mounted, while `flag` is off.
+pub fn gated(flag: &'static str, routes: Vec<Route>) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Gated { flag, handler: route.handler });
+            route
+        })
+        .collect()
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use rocket::request::FromParam;
+use uuid::Uuid;
+use crate::person::Person;
+use crate::repository::{PersonRepository, RepositoryError};
+// Ids taken by another replica are only noticed on insert; give up after this many re-syncs.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Who picks the ids of new persons, configured with `ID_MODE`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IdMode {
+    /// Clients send the `u32` id (the default).
+    Client,
+    /// The server assigns every new person a UUID, plus an internal `u32` id.
+    Uuid,
+}
+
+/// The `<id>` segment of person routes: either the numeric id or the UUID.
+pub enum PersonKey {
+    Id(u32),
+    Uuid(Uuid),
+}
+
+impl<'a> FromParam<'a> for PersonKey {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        if let Ok(id) = param.parse() {
+            return Ok(PersonKey::Id(id));
+        }
+        param.parse().map(PersonKey::Uuid).map_err(|_| param)
+    }
+}
+
+pub async fn find(repository: &dyn PersonRepository, tenant: &str, key: &PersonKey) -> Result<Option<Person>, RepositoryError> {
+    match key {
+        PersonKey::Id(id) => repository.get(tenant, *id).await,
+        PersonKey::Uuid(uuid) => repository.find_by_uuid(tenant, *uuid).await,
+    }
+}
+
+/// Hands out ids to persons created without one. Counters are kept per tenant,
+/// seeded from the highest stored id, and only ever move forward, so an id is
+/// never handed out twice by this process even after its person is deleted.
+/// Counters are only ever written whole, so they stay usable after a panic
+/// poisons the lock.
+#[derive(Default)]
+pub struct IdAllocator {
+    next: Mutex<HashMap<String, u32>>,
+}
+
+impl IdAllocator {
+    /// Moves the tenant's counter past every id currently in the store.
+    async fn resync(&self, repository: &dyn PersonRepository, tenant: &str) -> Result<(), RepositoryError> {
+        let seed = repository.list(tenant).await?.iter().map(|p| p.id).max().map_or(1, |max| max.saturating_add(1));
+        self.next.lock().unwrap_or_else(PoisonError::into_inner)
+            .entry(tenant.to_string())
+            .and_modify(|next| *next = (*next).max(seed))
+            .or_insert(seed);
+        Ok(())
+    }
+
+    /// Reserves `count` consecutive ids and returns the first.
+    pub async fn reserve(&self, repository: &dyn PersonRepository, tenant: &str, count: usize) -> Result<u32, RepositoryError> {
+        if !self.next.lock().unwrap_or_else(PoisonError::into_inner).contains_key(tenant) {
+            self.resync(repository, tenant).await?;
+        }
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let counter = next.entry(tenant.to_string()).or_insert(1);
+        let first = *counter;
+        *counter = u32::try_from(count).ok()
+            .and_then(|count| first.checked_add(count))
+            .ok_or_else(|| RepositoryError::Internal("person id space exhausted".to_string()))?;
+        Ok(first)
+    }
+
+    /// Lets the tenant's counter start over from the store, after its persons were replaced.
+    pub fn forget(&self, tenant: &str) {
+        self.next.lock().unwrap_or_else(PoisonError::into_inner).remove(tenant);
+    }
+
+    /// Keeps the counter ahead of ids that clients chose themselves.
+    pub fn observe(&self, tenant: &str, id: u32) {
+        if let Some(next) = self.next.lock().unwrap_or_else(PoisonError::into_inner).get_mut(tenant) {
+            *next = (*next).max(id.saturating_add(1));
+        }
+    }
+
+    /// Assigns fresh ids (and UUIDs when `with_uuids`) to all of `persons` and
+    /// inserts them at once. Ids taken behind this process's back, e.g. by another
+    /// replica, surface as a conflict and are retried after re-reading the store.
+    pub async fn insert_new(
+        &self,
+        repository: &dyn PersonRepository,
+        tenant: &str,
+        persons: &mut [Person],
+        with_uuids: bool,
+    ) -> Result<(), RepositoryError> {
+        for _ in 0..MAX_ATTEMPTS {
+            let first = self.reserve(repository, tenant, persons.len()).await?;
+            for (id, person) in (first..).zip(persons.iter_mut()) {
+                person.id = id;
+                if with_uuids {
+                    person.uuid = Some(Uuid::new_v4());
+                }
+            }
+            match repository.insert_many(tenant, persons.to_vec()).await {
+                Err(RepositoryError::Conflict) => self.resync(repository, tenant).await?,
+                result => return result,
+            }
+        }
+        Err(RepositoryError::Conflict)
+    }
+}
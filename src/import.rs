@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::io;
+use rocket::data::{self, Data, FromData};
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::Request;
+use rocket::tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+use serde::Serialize;
+use crate::person::{Person, INITIAL_VERSION};
+use crate::signature;
+use crate::validation::{self, FieldErrors};
+
+/// Uploads are spooled to disk by Rocket, so this only bounds what a client may send.
+pub const UPLOAD_LIMIT_MIB: u64 = 64;
+
+/// How a JSON import treats the current collection.
+#[derive(FromFormField, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Swap the whole collection for the upload.
+    Replace,
+    /// Add the uploaded persons whose ids are free and leave the rest untouched.
+    Merge,
+}
+
+#[derive(FromForm)]
+pub struct CsvUpload<'r> {
+    pub file: TempFile<'r>,
+}
+
+/// A [`CsvUpload`] that must be signed when request signing is on. The multipart
+/// framing is gone once the form is parsed, so the signature covers the file alone.
+pub struct SignedUpload<'r>(pub CsvUpload<'r>);
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for SignedUpload<'r> {
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let upload = match Form::<CsvUpload<'r>>::from_data(request, data).await {
+            Outcome::Success(form) => form.into_inner(),
+            Outcome::Error((status, _)) => return Outcome::Error((status, ())),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+        let verified = match upload.file.open().await {
+            Ok(reader) => signature::verify_stream(request, reader).await,
+            Err(e) => Err(e),
+        };
+        match verified {
+            Ok(Ok(())) => Outcome::Success(SignedUpload(upload)),
+            Ok(Err(_)) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Created,
+    Invalid,
+    /// The id is already taken in the store.
+    Conflict,
+}
+
+/// Outcome of one data row; `line` is where the record starts in the file, the header being line 1.
+#[derive(Serialize)]
+pub struct ImportRow {
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub status: ImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportReport {
+    pub created: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRow>,
+}
+
+impl ImportReport {
+    pub fn push(&mut self, row: ImportRow) {
+        match row.status {
+            ImportStatus::Created => self.created += 1,
+            _ => self.failed += 1,
+        }
+        self.rows.push(row);
+    }
+}
+
+/// Splits one CSV record, or returns `None` while a quoted field is still open
+/// and the record continues on the next line.
+fn split_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    (!quoted).then_some(fields)
+}
+
+/// Reads CSV records one at a time, so an upload is never held in memory as a whole.
+pub struct CsvRecords<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> CsvRecords<R> {
+    pub fn new(reader: R) -> Self {
+        CsvRecords { lines: reader.lines(), line: 0 }
+    }
+
+    /// The next non-blank record with the line it starts on.
+    pub async fn next(&mut self) -> io::Result<Option<(usize, Vec<String>)>> {
+        let mut record = String::new();
+        let mut start = 0;
+        while let Some(line) = self.lines.next_line().await? {
+            self.line += 1;
+            if record.is_empty() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                start = self.line;
+            } else {
+                record.push('\n');
+            }
+            record.push_str(&line);
+            if let Some(fields) = split_record(&record) {
+                return Ok(Some((start, fields)));
+            }
+        }
+        match record.is_empty() {
+            true => Ok(None),
+            false => Err(io::Error::new(io::ErrorKind::InvalidData, "unterminated quoted field")),
+        }
+    }
+}
+
+/// Column positions taken from the header row; `id` and `expires_at` are optional,
+/// and unknown columns (such as `version` or `uuid` from an export) are ignored.
+pub struct Columns {
+    positions: HashMap<String, usize>,
+    width: usize,
+}
+
+const REQUIRED_COLUMNS: [&str; 3] = ["name", "age", "date"];
+
+impl Columns {
+    pub fn from_header(header: &[String]) -> Option<Self> {
+        let positions: HashMap<String, usize> = header.iter()
+            .enumerate()
+            .map(|(position, name)| (name.trim().to_ascii_lowercase(), position))
+            .collect();
+        REQUIRED_COLUMNS.iter().all(|column| positions.contains_key(*column))
+            .then_some(Columns { positions, width: header.len() })
+    }
+
+    fn get<'a>(&self, fields: &'a [String], column: &str) -> Option<&'a str> {
+        self.positions.get(column)
+            .map(|position| fields[*position].trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Builds a new person from a record; a missing or `0` id is left for the server to assign.
+    pub fn person(&self, fields: &[String]) -> Result<Person, String> {
+        if fields.len() != self.width {
+            return Err(format!("expected {} fields, found {}", self.width, fields.len()));
+        }
+        let id = match self.get(fields, "id") {
+            Some(id) => id.parse().map_err(|_| format!("invalid id {:?}", id))?,
+            None => 0,
+        };
+        let mut errors = FieldErrors::default();
+        let name = self.get(fields, "name").unwrap_or_default();
+        let name = validation::check_name(&mut errors, "name", name);
+        let age = match self.get(fields, "age").map(str::parse) {
+            Some(Ok(age)) => validation::check_age(&mut errors, "age", age),
+            Some(Err(_)) => {
+                errors.add("age", "must be a whole number");
+                None
+            }
+            None => {
+                errors.add("age", "is required");
+                None
+            }
+        };
+        let date = match self.get(fields, "date") {
+            Some(date) => validation::check_date(&mut errors, "date", date),
+            None => {
+                errors.add("date", "is required");
+                None
+            }
+        };
+        let expires_at = self.get(fields, "expires_at").and_then(|t| validation::check_timestamp(&mut errors, "expires_at", t));
+        let (Some(name), Some(age), Some(date)) = (name, age, date) else {
+            return Err(errors.summary());
+        };
+        if !errors.is_empty() {
+            return Err(errors.summary());
+        }
+        Ok(Person {
+            id,
+            name,
+            age,
+            date,
+            deleted_at: None,
+            expires_at,
+            version: INITIAL_VERSION,
+            uuid: None,
+            addresses: Vec::new(),
+        })
+    }
+}
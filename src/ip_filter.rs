@@ -0,0 +1,117 @@
+use std::convert::Infallible;
+use std::env;
+use std::net::IpAddr;
+use ipnet::IpNet;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Data;
+use crate::errors::ApiError;
+use crate::refusal;
+use crate::AppState;
+
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Lets through only clients in `IP_ALLOWLIST` (when set) and never those in
+/// `IP_DENYLIST`; both take comma-separated CIDR blocks or single addresses.
+/// The client is the connecting peer unless that is one of `TRUSTED_PROXIES`,
+/// in which case it is the last `X-Forwarded-For` hop no trusted proxy added.
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    trusted_proxies: Vec<IpNet>,
+}
+
+pub fn networks(variable: &str) -> Result<Vec<IpNet>, String> {
+    let Ok(value) = env::var(variable) else {
+        return Ok(Vec::new());
+    };
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse::<IpNet>()
+                .or_else(|_| item.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("{}: {:?} is neither a CIDR block nor an IP address", variable, item))
+        })
+        .collect()
+}
+
+fn contains(networks: &[IpNet], ip: IpAddr) -> bool {
+    networks.iter().any(|network| network.contains(&ip))
+}
+
+/// The client that sent `request`: the connecting peer unless that is one of
+/// `trusted_proxies`, in which case the last `X-Forwarded-For` hop none of them added.
+pub fn client_ip(request: &Request<'_>, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let peer = request.remote()?.ip().to_canonical();
+    if !contains(trusted_proxies, peer) {
+        return Some(peer);
+    }
+    // Each proxy appends the address it got the request from; anything left of the
+    // first hop a trusted proxy didn't add could have been made up by the client.
+    let hops: Vec<&str> = request.headers().get(FORWARDED_FOR_HEADER).flat_map(|value| value.split(',')).collect();
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = hop.to_canonical();
+        if !contains(trusted_proxies, client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+/// [`client_ip`] behind the `TRUSTED_PROXIES` in [`AppState`], for whatever is counted per
+/// client; Rocket's own `client_ip` believes any `X-Real-IP` header a client sends.
+pub fn client(request: &Request<'_>) -> Option<IpAddr> {
+    match request.rocket().state::<AppState>() {
+        Some(state) => client_ip(request, &state.trusted_proxies),
+        None => client_ip(request, &[]),
+    }
+}
+
+/// The [`client`] for handlers, which can't see the request itself.
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientIp {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientIp(client(request)))
+    }
+}
+
+impl IpFilter {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let allow = networks("IP_ALLOWLIST")?;
+        let deny = networks("IP_DENYLIST")?;
+        let trusted_proxies = networks("TRUSTED_PROXIES")?;
+        if allow.is_empty() && deny.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(IpFilter { allow, deny, trusted_proxies }))
+    }
+
+    fn permits(&self, ip: Option<IpAddr>) -> bool {
+        match ip {
+            Some(ip) => !contains(&self.deny, ip) && (self.allow.is_empty() || contains(&self.allow, ip)),
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for IpFilter {
+    fn info(&self) -> Info {
+        Info { name: "IP Filter", kind: Kind::Request }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if !self.permits(client_ip(request, &self.trusted_proxies)) {
+            refusal::refuse(request, ApiError::Forbidden("requests from this address are not allowed"));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::{Orbit, Rocket};
+use crate::AppState;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Starting,
+    Serving,
+    /// Shutting down: requests in flight are finished, new ones should go elsewhere.
+    Draining,
+}
+
+/// Where the service is between launch and exit, as `/readyz` reports it, and how long
+/// and how much it has served, as `/api/uptime` does.
+pub struct Lifecycle {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    launched: AtomicBool,
+    draining: AtomicBool,
+    served: AtomicU64,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            launched: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            served: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Lifecycle {
+    /// Measured by the monotonic clock, so changes to the system's don't skew it.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Responses sent since the start, whatever their status.
+    pub fn served(&self) -> u64 {
+        self.served.load(Ordering::Relaxed)
+    }
+
+    pub fn phase(&self) -> Phase {
+        match (self.launched.load(Ordering::Acquire), self.draining.load(Ordering::Acquire)) {
+            (_, true) => Phase::Draining,
+            (false, false) => Phase::Starting,
+            (true, false) => Phase::Serving,
+        }
+    }
+}
+
+/// Marks the [`Lifecycle`] launched once Rocket is up, and draining once shutdown begins,
+/// and counts the responses in between.
+pub struct LifecycleEvents;
+
+#[rocket::async_trait]
+impl Fairing for LifecycleEvents {
+    fn info(&self) -> Info {
+        Info { name: "Lifecycle", kind: Kind::Liftoff | Kind::Response | Kind::Shutdown }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        if let Some(state) = rocket.state::<AppState>() {
+            state.lifecycle.launched.store(true, Ordering::Release);
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, _: &mut Response<'r>) {
+        if let Some(state) = request.rocket().state::<AppState>() {
+            state.lifecycle.served.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        if let Some(state) = rocket.state::<AppState>() {
+            state.lifecycle.draining.store(true, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::Serialize;
+use crate::person::Person;
+use crate::routes::API_V1;
+
+#[derive(Serialize)]
+pub struct Link {
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<&'static str>,
+}
+
+impl Link {
+    fn new(href: String, method: Option<&'static str>) -> Self {
+        Link { href, method }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PersonLinks {
+    #[serde(rename = "self")]
+    pub this: Link,
+    pub update: Link,
+    pub delete: Link,
+    pub collection: Link,
+}
+
+/// A person as sent to clients, with `_links` when hypermedia was requested.
+#[derive(Serialize)]
+pub struct PersonBody {
+    #[serde(flatten)]
+    pub person: Person,
+    #[serde(rename = "_links", skip_serializing_if = "Option::is_none")]
+    pub links: Option<PersonLinks>,
+}
+
+/// Canonical path of a person: by UUID when it has one, otherwise by id.
+pub fn person_path(person: &Person) -> String {
+    match person.uuid {
+        Some(uuid) => format!("{}/person/{}", API_V1, uuid),
+        None => format!("{}/person/{}", API_V1, person.id),
+    }
+}
+
+/// Whether the client opted into hypermedia links, with `?links=true` or by
+/// accepting `application/hal+json` (or any JSON type with `profile=hal`).
+pub struct Hypermedia(pub bool);
+
+impl Hypermedia {
+    pub fn wrap(&self, person: Person) -> PersonBody {
+        let links = self.0.then(|| {
+            let href = person_path(&person);
+            PersonLinks {
+                this: Link::new(href.clone(), None),
+                update: Link::new(href.clone(), Some("PATCH")),
+                delete: Link::new(href, Some("DELETE")),
+                collection: Link::new(format!("{}/persons", API_V1), None),
+            }
+        });
+        PersonBody { person, links }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Hypermedia {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Some(Ok(links)) = request.query_value::<bool>("links") {
+            return Outcome::Success(Hypermedia(links));
+        }
+        let hal = request.accept().is_some_and(|accept| {
+            accept.media_types().any(|media| {
+                media.sub() == "hal+json" || media.param("profile").is_some_and(|profile| profile.trim_matches('"') == "hal")
+            })
+        });
+        Outcome::Success(Hypermedia(hal))
+    }
+}
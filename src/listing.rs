@@ -0,0 +1,134 @@
+use std::cmp::Ordering;
+use chrono::NaiveDate;
+use rocket::form::FromFormField;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use crate::person::Person;
+use crate::validation::FieldErrors;
+
+pub enum SortField {
+    Id,
+    Name,
+    Age,
+    Date,
+}
+
+/// Why a listing guard refused the query string, for the `400` catcher.
+struct QueryErrors(Option<FieldErrors>);
+
+/// Records what was wrong with `parameter`, as a handler's [`ApiError::query`](crate::errors::ApiError::query) would say.
+fn refuse<T, E>(request: &Request<'_>, parameter: &str, message: impl Into<String>, error: E) -> Outcome<T, E> {
+    let mut errors = FieldErrors::default();
+    errors.add(parameter, message);
+    request.local_cache(|| QueryErrors(Some(errors)));
+    Outcome::Error((Status::BadRequest, error))
+}
+
+/// The query parameter errors recorded for this request by [`Sorting`] or [`PersonFilter`], if any.
+pub fn recorded_errors<'r>(request: &'r Request<'_>) -> Option<&'r FieldErrors> {
+    request.local_cache(|| QueryErrors(None)).0.as_ref()
+}
+
+/// Request guard for `?sort=id|name|age|date&order=asc|desc`, read straight from
+/// the query string so unknown values can be rejected with 400.
+pub struct Sorting {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+#[derive(Debug)]
+pub struct InvalidSort;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Sorting {
+    type Error = InvalidSort;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let field = match request.query_value::<&str>("sort") {
+            None | Some(Ok("id")) => SortField::Id,
+            Some(Ok("name")) => SortField::Name,
+            Some(Ok("age")) => SortField::Age,
+            Some(Ok("date")) => SortField::Date,
+            Some(_) => return refuse(request, "sort", "must be one of id, name, age, date", InvalidSort),
+        };
+        let descending = match request.query_value::<&str>("order") {
+            None | Some(Ok("asc")) => false,
+            Some(Ok("desc")) => true,
+            Some(_) => return refuse(request, "order", "must be asc or desc", InvalidSort),
+        };
+        Outcome::Success(Sorting { field, descending })
+    }
+}
+
+impl Sorting {
+    pub fn apply(&self, persons: &mut [Person]) {
+        let compare: fn(&Person, &Person) -> Ordering = match self.field {
+            SortField::Id => |a, b| a.id.cmp(&b.id),
+            SortField::Name => |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()).then(a.id.cmp(&b.id)),
+            SortField::Age => |a, b| a.age.cmp(&b.age).then(a.id.cmp(&b.id)),
+            SortField::Date => |a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)),
+        };
+        persons.sort_by(|a, b| if self.descending { compare(b, a) } else { compare(a, b) });
+    }
+}
+
+/// Request guard for `?name=&min_age=&max_age=&date_from=&date_to=`. Every
+/// criterion is optional and they combine with AND; dates use `YYYY-MM-DD`.
+pub struct PersonFilter {
+    pub name: Option<String>,
+    pub min_age: Option<u8>,
+    pub max_age: Option<u8>,
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+}
+
+/// The parameter that was wrong, and how.
+#[derive(Debug)]
+pub struct InvalidFilter(&'static str, &'static str);
+
+fn query_param<'r, T: FromFormField<'r>>(request: &'r Request<'_>, name: &'static str, expected: &'static str) -> Result<Option<T>, InvalidFilter> {
+    request.query_value::<T>(name).transpose().map_err(|_| InvalidFilter(name, expected))
+}
+
+fn query_age(request: &Request<'_>, name: &'static str) -> Result<Option<u8>, InvalidFilter> {
+    query_param(request, name, "must be a whole number from 0 to 255")
+}
+
+fn query_date(request: &Request<'_>, name: &'static str) -> Result<Option<NaiveDate>, InvalidFilter> {
+    const EXPECTED: &str = "must be a date such as 1981-02-21";
+    query_param::<&str>(request, name, EXPECTED)?
+        .map(|value| value.parse().map_err(|_| InvalidFilter(name, EXPECTED)))
+        .transpose()
+}
+
+impl PersonFilter {
+    fn from_query(request: &Request<'_>) -> Result<Self, InvalidFilter> {
+        Ok(PersonFilter {
+            name: query_param::<String>(request, "name", "must be text")?.map(|name| name.to_lowercase()),
+            min_age: query_age(request, "min_age")?,
+            max_age: query_age(request, "max_age")?,
+            date_from: query_date(request, "date_from")?,
+            date_to: query_date(request, "date_to")?,
+        })
+    }
+
+    pub fn matches(&self, person: &Person) -> bool {
+        self.name.as_ref().map_or(true, |name| person.name.to_lowercase().contains(name))
+            && self.min_age.map_or(true, |min| person.age >= min)
+            && self.max_age.map_or(true, |max| person.age <= max)
+            && self.date_from.map_or(true, |from| person.date.date_naive() >= from)
+            && self.date_to.map_or(true, |to| person.date.date_naive() <= to)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PersonFilter {
+    type Error = InvalidFilter;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match PersonFilter::from_query(request) {
+            Ok(filter) => Outcome::Success(filter),
+            Err(InvalidFilter(parameter, message)) => refuse(request, parameter, message, InvalidFilter(parameter, message)),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use crate::auth::{self, AuthError};
+use crate::errors::ApiError;
+use crate::ip_filter;
+use crate::refusal;
+use crate::AppState;
+
+/// Clients past this many are pruned of those neither locked nor failing lately.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+const DEFAULT_WINDOW_SECS: u64 = 5 * 60;
+const DEFAULT_DURATION_SECS: u64 = 15 * 60;
+
+struct Failures {
+    count: u32,
+    since: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Locks a client out for `LOCKOUT_DURATION_SECS` once it has failed to authenticate
+/// `LOCKOUT_MAX_FAILURES` times within `LOCKOUT_WINDOW_SECS`. Clients are IPs for
+/// bad keys, tokens and signatures, and both IPs and usernames for password logins.
+pub struct Lockout {
+    max_failures: u32,
+    window: Duration,
+    duration: Duration,
+    clients: Mutex<HashMap<String, Failures>>,
+}
+
+fn seconds(variable: &str, default: u64) -> Duration {
+    Duration::from_secs(env::var(variable).ok().and_then(|v| v.parse().ok()).unwrap_or(default))
+}
+
+/// Rounded up, so clients never retry a moment too early.
+pub fn whole_seconds(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+impl Lockout {
+    pub fn from_env() -> Option<Self> {
+        let max_failures = env::var("LOCKOUT_MAX_FAILURES").ok()?.parse().ok().filter(|max| *max > 0)?;
+        Some(Lockout {
+            max_failures,
+            window: seconds("LOCKOUT_WINDOW_SECS", DEFAULT_WINDOW_SECS),
+            duration: seconds("LOCKOUT_DURATION_SECS", DEFAULT_DURATION_SECS),
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// How much longer `client` is locked out, if it is.
+    pub fn locked(&self, client: &str) -> Option<Duration> {
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        let locked_until = clients.get(client)?.locked_until?;
+        locked_until.checked_duration_since(Instant::now()).filter(|wait| !wait.is_zero())
+    }
+
+    pub fn fail(&self, client: String) {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if clients.len() > MAX_TRACKED_CLIENTS {
+            clients.retain(|_, failures| self.is_current(failures, now));
+        }
+        let failures = clients.entry(client).or_insert(Failures { count: 0, since: now, locked_until: None });
+        if !self.is_current(failures, now) {
+            *failures = Failures { count: 0, since: now, locked_until: None };
+        }
+        failures.count += 1;
+        if failures.count >= self.max_failures {
+            failures.locked_until = Some(now + self.duration);
+        }
+    }
+
+    pub fn succeed(&self, client: &str) {
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner).remove(client);
+    }
+
+    /// Whether `failures` still count: locked, or started within the window.
+    fn is_current(&self, failures: &Failures, now: Instant) -> bool {
+        match failures.locked_until {
+            Some(until) => until > now,
+            None => now.saturating_duration_since(failures.since) < self.window,
+        }
+    }
+}
+
+pub fn ip_client(ip: IpAddr) -> String {
+    format!("ip {}", ip)
+}
+
+/// Refuses locked-out IPs before anything else looks at their credentials, and
+/// counts the credentials the guards rejected.
+pub struct Lockouts;
+
+#[rocket::async_trait]
+impl Fairing for Lockouts {
+    fn info(&self) -> Info {
+        Info { name: "Lockout", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let wait = request.rocket().state::<AppState>()
+            .and_then(|state| state.lockout.as_ref())
+            .zip(ip_filter::client(request).map(ip_client))
+            .and_then(|(lockout, client)| lockout.locked(&client));
+        if let Some(wait) = wait {
+            refusal::refuse(request, ApiError::LockedOut(whole_seconds(wait)));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, _: &mut Response<'r>) {
+        let Some(lockout) = request.rocket().state::<AppState>().and_then(|state| state.lockout.as_ref()) else {
+            return;
+        };
+        // Expired tokens, missing credentials and missing permissions aren't guesses.
+        let guessed = matches!(
+            auth::recorded_error(request),
+            Some(AuthError::InvalidKey | AuthError::InvalidToken | AuthError::InvalidSignature)
+        );
+        if let (true, Some(ip)) = (guessed, ip_filter::client(request)) {
+            lockout.fail(ip_client(ip));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use super::*;
+
+    #[rocket::async_test]
+    async fn rotating_x_real_ip_still_locks_out() {
+        let lockout = Lockout { max_failures: 2, window: Duration::from_secs(60), duration: Duration::from_secs(60), clients: Mutex::default() };
+        let client = Client::untracked(rocket::build()).await.expect("valid rocket");
+        let peer = "192.0.2.7:40000".parse().unwrap();
+        let mut locked = Vec::new();
+        for n in 1..=3 {
+            let request = client.get("/api/v1/persons")
+                .remote(peer)
+                .header(Header::new("X-Real-IP", format!("203.0.113.{}", n)));
+            let key = ip_client(ip_filter::client(request.inner()).expect("a peer address"));
+            locked.push(lockout.locked(&key).is_some());
+            lockout.fail(key);
+        }
+        assert_eq!(locked, [false, false, true]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
+use crate::metrics::Metrics;
+use crate::refusal;
+use crate::request_id;
+
+/// Where log lines are written.
+pub enum Output {
+    Stdout,
+    /// For commands whose own output goes to stdout, such as `export`.
+    Stderr,
+}
+
+type SetLevel = Box<dyn Fn(LevelFilter) -> Result<(), String> + Send + Sync>;
+
+/// Changes the level [`init`] set, once it has.
+static SET_LEVEL: OnceLock<SetLevel> = OnceLock::new();
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| format!("the log level must be off, error, warn, info, debug or trace, got {:?}", level))
+}
+
+fn targets(level: LevelFilter) -> Targets {
+    // Rocket logs every request in several lines at info; one line from [`RequestLog`] replaces them.
+    let rocket_level = match level >= LevelFilter::DEBUG {
+        true => level,
+        false => level.min(LevelFilter::WARN),
+    };
+    // Targets match by prefix, and ours starts with "rocket" too.
+    Targets::new()
+        .with_default(level)
+        .with_target("rocket", rocket_level)
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+}
+
+/// Sends Rocket's logs and ours through `tracing` to `output`, at `LOG_LEVEL` (default `info`)
+/// and as `LOG_FORMAT` says: `pretty` lines for people (the default) or `json` objects for collectors.
+pub fn init(output: Output) -> Result<(), String> {
+    let level = match env::var("LOG_LEVEL") {
+        Ok(level) => parse_level(&level)?,
+        Err(_) => LevelFilter::INFO,
+    };
+    let (filter, handle) = reload::Layer::new(targets(level));
+    let _ = SET_LEVEL.set(Box::new(move |level| handle.reload(targets(level)).map_err(|e| e.to_string())));
+    let registry = tracing_subscriber::registry();
+    #[cfg(feature = "otel")]
+    let registry = registry.with(crate::telemetry::layer()?);
+    let (writer, terminal) = match output {
+        Output::Stdout => (BoxMakeWriter::new(io::stdout), io::stdout().is_terminal()),
+        Output::Stderr => (BoxMakeWriter::new(io::stderr), io::stderr().is_terminal()),
+    };
+    let initialized = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => registry.with(fmt::layer().json().flatten_event(true).with_writer(writer).with_filter(filter)).try_init(),
+        Ok("pretty") | Err(_) => registry.with(fmt::layer().with_ansi(terminal).with_writer(writer).with_filter(filter)).try_init(),
+        Ok(format) => return Err(format!("LOG_FORMAT must be json or pretty, got {:?}", format)),
+    };
+    initialized.map_err(|e| e.to_string())
+}
+
+pub fn set_level(level: LevelFilter) -> Result<(), String> {
+    match SET_LEVEL.get() {
+        Some(set_level) => set_level(level),
+        None => Ok(()),
+    }
+}
+
+const DEFAULT_SLOW_REQUEST_MS: u64 = 1000;
+
+struct Started(Instant);
+
+/// Logs one line per request with its method, route, status, latency and request id, and
+/// warns of those slower than `SLOW_REQUEST_MS` (default 1000, `0` for never).
+pub struct RequestLog {
+    slow: Option<Duration>,
+}
+
+impl RequestLog {
+    pub fn from_env() -> Result<Self, String> {
+        let millis = match env::var("SLOW_REQUEST_MS") {
+            Ok(millis) => millis.parse::<u64>()
+                .map_err(|_| format!("SLOW_REQUEST_MS must be a number of milliseconds, got {:?}", millis))?,
+            Err(_) => DEFAULT_SLOW_REQUEST_MS,
+        };
+        Ok(RequestLog { slow: Some(Duration::from_millis(millis)).filter(|slow| !slow.is_zero()) })
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLog {
+    fn info(&self) -> Info {
+        Info { name: "Request log", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| Started(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let latency = request.local_cache(|| Started(Instant::now())).0.elapsed();
+        let route = request.route().map_or("unmatched".to_string(), |route| route.uri.path().to_string());
+        tracing::info!(
+            method = %request.method(),
+            path = refusal::original_path(request),
+            route,
+            status = response.status().code,
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            request_id = request_id::of(request),
+            "request",
+        );
+        if self.slow.is_some_and(|slow| latency > slow) {
+            tracing::warn!(
+                method = %request.method(),
+                route,
+                latency_ms = latency.as_secs_f64() * 1000.0,
+                request_id = request_id::of(request),
+                "slow request",
+            );
+            if let Some(metrics) = request.rocket().state::<Metrics>() {
+                metrics.slow_request(request.method().as_str().to_string(), route);
+            }
+        }
+    }
+}
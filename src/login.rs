@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::Duration;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use rand::rngs::OsRng;
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, Status};
+use rocket::response::content::RawHtml;
+use rocket::response::status::Custom;
+use rocket::response::Redirect;
+use rocket::{tokio, Config, Either, Route, State};
+use serde::Deserialize;
+use crate::auth::{self, Session, SESSION_COOKIE};
+use crate::errors::ApiError;
+use crate::ip_filter::ClientIp;
+use crate::lockout;
+use crate::AppState;
+
+pub fn routes() -> Vec<Route> {
+    routes![login_page, login, logout]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct User {
+    /// An Argon2 hash in PHC format, `$argon2id$v=19$...`.
+    password_hash: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Username and password login for the HTML pages, with the users in the JSON file
+/// `LOGIN_USERS_FILE`: `{"alice": {"password_hash": "$argon2id$...", "roles": ["admin"]}}`.
+/// Signed-in browsers get the same [`Session`] cookie as with OIDC.
+pub struct PasswordLogin {
+    users: HashMap<String, User>,
+    session_ttl: Duration,
+    /// Checked against for unknown usernames, so they take as long as wrong passwords.
+    decoy_hash: String,
+}
+
+impl PasswordLogin {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(path) = env::var("LOGIN_USERS_FILE") else {
+            return Ok(None);
+        };
+        let contents = fs::read_to_string(&path).map_err(|e| format!("LOGIN_USERS_FILE {}: {}", path, e))?;
+        let users: HashMap<String, User> = serde_json::from_str(&contents).map_err(|e| format!("LOGIN_USERS_FILE {}: {}", path, e))?;
+        for (name, user) in &users {
+            PasswordHash::new(&user.password_hash).map_err(|e| format!("LOGIN_USERS_FILE {}: the password hash of {} is not valid: {}", path, name, e))?;
+        }
+        let decoy_hash = Argon2::default()
+            .hash_password(b"decoy", &SaltString::generate(&mut OsRng))
+            .map_err(|e| e.to_string())?
+            .to_string();
+        Ok(Some(PasswordLogin { users, session_ttl: auth::session_ttl_from_env(), decoy_hash }))
+    }
+}
+
+#[derive(FromForm)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+fn login_form(message: Option<&str>) -> RawHtml<String> {
+    let message = message.map(|message| format!("<p>{}</p>", message)).unwrap_or_default();
+    RawHtml(format!(
+        "<!DOCTYPE html><title>Sign in</title>{}<form method=\"post\" action=\"/login\">\
+         <label>Username <input name=\"username\" autocomplete=\"username\" required></label> \
+         <label>Password <input name=\"password\" type=\"password\" autocomplete=\"current-password\" required></label> \
+         <button>Sign in</button></form>",
+        message,
+    ))
+}
+
+#[get("/login")]
+fn login_page() -> RawHtml<String> {
+    login_form(None)
+}
+
+#[post("/login", data = "<credentials>")]
+async fn login(
+    credentials: Form<Credentials>,
+    cookies: &CookieJar<'_>,
+    config: &Config,
+    login: &State<PasswordLogin>,
+    state: &State<AppState>,
+    client: ClientIp,
+) -> Result<Redirect, Either<Custom<RawHtml<String>>, ApiError>> {
+    let Credentials { username, password } = credentials.into_inner();
+    let user_client = format!("user {}", username);
+    if let Some(wait) = state.lockout.as_ref().and_then(|lockout| lockout.locked(&user_client)) {
+        return Err(Either::Right(ApiError::LockedOut(lockout::whole_seconds(wait))));
+    }
+    let user = login.users.get(&username);
+    let hash = user.map_or(&login.decoy_hash, |user| &user.password_hash).clone();
+    // Argon2 is slow on purpose; keep it off the async workers.
+    let verified = tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+    }).await.unwrap_or(false);
+    let Some(user) = user.filter(|_| verified) else {
+        if let Some(lockout) = &state.lockout {
+            lockout.fail(user_client);
+            if let Some(ip) = client.0 {
+                lockout.fail(lockout::ip_client(ip));
+            }
+        }
+        return Err(Either::Left(Custom(Status::Unauthorized, login_form(Some("The username or password is wrong.")))));
+    };
+    if let Some(lockout) = &state.lockout {
+        lockout.succeed(&user_client);
+    }
+    let session = Session {
+        subject: username,
+        roles: user.roles.clone(),
+        expires_at: Utc::now() + login.session_ttl,
+    };
+    cookies.add_private(session.cookie(config.tls_enabled()));
+    Ok(Redirect::to("/admin"))
+}
+
+#[post("/logout")]
+fn logout(cookies: &CookieJar<'_>) -> Redirect {
+    cookies.remove_private(Cookie::build(SESSION_COOKIE).path("/"));
+    Redirect::to("/login")
+}
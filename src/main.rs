@@ -1,31 +1,541 @@
 #[macro_use] extern crate rocket;
 
+mod access_log;
+mod assets;
+mod audit;
+mod auth;
+mod backup;
+mod body_log;
+mod bulk;
+mod catchers;
+mod cli;
+mod clock;
+mod concurrency;
+mod cors;
+mod dates;
+mod duplicates;
+mod encryption;
+#[cfg(feature = "sentry")]
+mod error_reporting;
+mod errors;
+mod etag;
+mod expiry;
+mod export;
+mod fields;
+mod flags;
+mod ids;
+mod import;
+mod ip_filter;
+mod lifecycle;
+mod links;
+mod listing;
+mod lockout;
+mod logging;
+mod maintenance;
+mod login;
+mod metrics;
+#[cfg(feature = "mtls")]
+mod mtls;
+mod negotiate;
+#[cfg(feature = "oidc")]
+mod oidc;
+mod pagination;
+mod panics;
 mod person;
+mod rate_limit;
+mod rbac;
+mod refusal;
+mod reload;
+mod repository;
+mod request_id;
 mod routes;
+mod search;
+mod secrets;
+mod security_headers;
+mod seed;
+mod settings;
+mod signature;
+mod stats;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod tenant;
+#[cfg(feature = "tls")]
+mod tls;
+mod unique;
+#[cfg(unix)]
+mod unix_socket;
+mod validation;
 
-use std::sync::RwLock;
 use std::env;
-use rocket::Config;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use rocket::config::SecretKey;
+use rocket::figment::Figment;
+use rand::Rng;
+use clap::Parser;
+use rocket::{Build, Config, Rocket};
+use rocket_dyn_templates::Template;
+use access_log::AccessLog;
+use body_log::BodyLog;
+use concurrency::ConcurrencyLimit;
+use cli::{Cli, Command};
+use audit::AuditLog;
+use auth::Authentication;
+use cors::Cors;
+use encryption::{EncryptedRepository, FieldEncryption};
+use ids::{IdAllocator, IdMode};
+use ip_filter::IpFilter;
+use lifecycle::{Lifecycle, LifecycleEvents};
+use lockout::{Lockout, Lockouts};
+use logging::RequestLog;
+use login::PasswordLogin;
+use metrics::{Metrics, RequestMetrics};
+use rate_limit::RateLimiter;
+use maintenance::{Maintenance, MaintenanceMode};
+use refusal::Refusals;
+#[cfg(unix)]
+use reload::ReloadOnHangup;
+use reload::{Live, Reloadable};
+use request_id::RequestIds;
+use repository::PersonRepository;
+use search::{IndexedRepository, SearchIndex};
+use security_headers::SecurityHeaders;
+use settings::{Overrides, Settings};
+use signature::RequestSigning;
+use unique::UniqueNames;
+#[cfg(unix)]
+use unix_socket::UnixSocket;
 
 pub struct AppState {
-    pub person_collection: RwLock<Vec<person::Person>>,
-    pub greeting_text: String,
+    pub repository: Arc<dyn PersonRepository>,
+    pub search_index: Arc<SearchIndex>,
+    pub live: Arc<Reloadable>,
+    pub backup_dir: PathBuf,
+    /// Read again by `/admin/reset`, so edits apply without a restart.
+    pub seed_file: Option<PathBuf>,
+    pub sample_persons: bool,
+    pub tz_default: Option<Tz>,
+    /// Whether 5xx responses carry the error itself; see [`Settings::error_detail`].
+    pub error_detail: bool,
+    pub soft_delete: bool,
+    pub require_if_match: bool,
+    pub id_mode: IdMode,
+    pub ids: IdAllocator,
+    pub multi_tenant: bool,
+    pub reject_unknown_fields: bool,
+    pub auth: Authentication,
+    pub audit: Arc<AuditLog>,
+    pub signing: Option<RequestSigning>,
+    pub lockout: Option<Lockout>,
+    /// Whose `X-Forwarded-For` tells the client apart; see [`ip_filter::client`].
+    pub trusted_proxies: Vec<IpNet>,
+    pub lifecycle: Lifecycle,
+    pub maintenance: Maintenance,
 }
 
-#[launch]
-fn rocket() -> _ {
-    let greeting_text = env::var("GREETING_TEXT").unwrap_or_else(|_| "Hi!".to_string());
+fn main() {
+    let cli = Cli::parse();
+    let output = match cli.command {
+        None | Some(Command::Serve) | Some(Command::CheckConfig) => logging::Output::Stdout,
+        Some(Command::Seed { .. }) | Some(Command::Export { .. }) => logging::Output::Stderr,
+    };
+    if let Err(e) = logging::init(output) {
+        eprintln!("Failed to set up logging: {}", e);
+        std::process::exit(1);
+    }
+    panics::install_hook();
+    let overrides = cli.overrides();
+    // As `#[rocket::main]` would, but sized by our settings rather than Rocket's own sources.
+    let config = match settings::load(&overrides) {
+        Ok((config, _)) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let runtime = rocket::tokio::runtime::Builder::new_multi_thread()
+        .thread_name("rocket-worker-thread")
+        .worker_threads(config.workers)
+        .max_blocking_threads(config.max_blocking)
+        .enable_all()
+        .build();
+    let runtime = match runtime {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start the runtime: {}", e);
+            std::process::exit(1);
+        }
+    };
+    runtime.block_on(run(cli.command.unwrap_or(Command::Serve), overrides));
+    if config.shutdown.force {
+        runtime.shutdown_timeout(Duration::from_millis(500));
+    }
+}
+
+async fn run(command: Command, overrides: Overrides) {
+    match command {
+        // Returns once a shutdown has let the requests in flight finish, or the grace
+        // period has run out; the store is closed after the last of them.
+        Command::Serve => match rocket(&overrides).await.launch().await {
+            Ok(rocket) => {
+                if let Some(state) = rocket.state::<AppState>() {
+                    state.audit.close().await;
+                    if let Err(e) = state.repository.close().await {
+                        eprintln!("Failed to close the store: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                info!("Shut down");
+            }
+            Err(e) => {
+                eprintln!("Failed to launch: {}", e.pretty_print());
+                std::process::exit(1);
+            }
+        },
+        Command::CheckConfig => cli::check_config(rocket(&overrides).await).await,
+        Command::Seed { file, tenant } => {
+            let (_, settings) = load_settings(&overrides).await;
+            cli::check_seedable(&settings);
+            cli::seed(&file, &tenant, open_repository(&settings, &overrides, Arc::default()).await).await;
+        }
+        Command::Export { format, tenant, include_deleted } => {
+            let (_, settings) = load_settings(&overrides).await;
+            cli::export(format, &tenant, include_deleted, open_repository(&settings, &overrides, Arc::default()).await).await;
+        }
+    }
+}
+
+/// Secrets first, as settings such as the greeting may come from them.
+async fn load_settings(overrides: &Overrides) -> (Config, Settings) {
+    if let Err(e) = secrets::load().await {
+        eprintln!("Failed to load secrets: {}", e);
+        std::process::exit(1);
+    }
+    match settings::load(overrides) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The configured store, wrapped in the encryption, unique names, index and tracing it is configured with.
+async fn open_repository(settings: &Settings, overrides: &Overrides, search_index: Arc<SearchIndex>) -> Arc<dyn PersonRepository> {
+    let seed = match settings.storage_backend.as_str() {
+        "memory" => match seed::initial_persons(settings, overrides.no_seed) {
+            Ok(seed) => seed,
+            Err(e) => {
+                eprintln!("Failed to load the seed file: {}", e);
+                std::process::exit(1);
+            }
+        },
+        backend => {
+            if settings.seed_file.is_some() {
+                warn!("SEED_FILE only seeds the in-memory store; use the seed command for the {} store", backend);
+            }
+            Vec::new()
+        }
+    };
+    let encryption = match FieldEncryption::from_env() {
+        Ok(encryption) => encryption,
+        Err(e) => {
+            eprintln!("Failed to load the field encryption keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let unique_names = env::var("UNIQUE_NAMES").is_ok_and(|v| v == "true" || v == "1");
+    let repository = repository::from_env(&settings.storage_backend, seed).await.map(|repository| match encryption {
+        Some(encryption) => Arc::new(EncryptedRepository::new(repository, encryption)),
+        None => repository,
+    });
+    let repository = match repository {
+        Ok(repository) if unique_names => IndexedRepository::build(Arc::new(UniqueNames::new(repository)), search_index).await,
+        Ok(repository) => IndexedRepository::build(repository, search_index).await,
+        Err(e) => Err(e),
+    };
+    let repository: Arc<dyn PersonRepository> = match repository {
+        Ok(repository) => Arc::new(repository),
+        Err(e) => {
+            eprintln!("Failed to initialize person storage: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "otel")]
+    let repository: Arc<dyn PersonRepository> = match telemetry::enabled() {
+        true => Arc::new(telemetry::TracedRepository::new(repository)),
+        false => repository,
+    };
+    repository
+}
+
+async fn rocket(overrides: &Overrides) -> Rocket<Build> {
+    #[cfg(feature = "sentry")]
+    let error_reporting = match error_reporting::ErrorReporting::from_env() {
+        Ok(error_reporting) => error_reporting,
+        Err(e) => {
+            eprintln!("Failed to set up error reporting: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (config, settings) = load_settings(overrides).await;
+    let live = match Live::from_settings(&settings).and_then(|live| logging::set_level(live.log_level).map(|_| live)) {
+        Ok(live) => Arc::new(Reloadable::new(live)),
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let soft_delete = env::var("SOFT_DELETE").is_ok_and(|v| v == "true" || v == "1");
+    let require_if_match = env::var("REQUIRE_IF_MATCH").is_ok_and(|v| v == "true" || v == "1");
+    let id_mode = match env::var("ID_MODE").as_deref() {
+        Ok("uuid") => IdMode::Uuid,
+        _ => IdMode::Client,
+    };
+    let multi_tenant = env::var("MULTI_TENANT").is_ok_and(|v| v == "true" || v == "1");
+    let reject_unknown_fields = env::var("REJECT_UNKNOWN_FIELDS").is_ok_and(|v| v == "true" || v == "1");
+    // `tokio::time::interval` panics on a zero period, and would do so out of sight in the sweep task.
+    let expiry_interval = match env::var("EXPIRY_SWEEP_SECS").map(|v| v.parse::<u64>()) {
+        Ok(Ok(secs)) if secs > 0 => Duration::from_secs(secs),
+        Ok(_) => {
+            eprintln!("EXPIRY_SWEEP_SECS must be a whole number of seconds above 0");
+            std::process::exit(1);
+        }
+        Err(_) => Duration::from_secs(60),
+    };
+    let backup_dir = env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".to_string()).into();
+    #[cfg(feature = "tls")]
+    let tls = match tls::from_env() {
+        Ok(tls) => tls.or_else(|| config.tls.clone()),
+        Err(e) => {
+            eprintln!("Failed to configure TLS: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "oidc")]
+    let oidc = match oidc::Oidc::discover_from_env().await {
+        Ok(oidc) => oidc,
+        Err(e) => {
+            eprintln!("Failed to set up OIDC login: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "mtls")]
+    let client_certs = tls.as_ref().is_some_and(|tls| tls.mutual().is_some());
+    #[cfg(not(feature = "mtls"))]
+    let client_certs = false;
+    let password_login = match PasswordLogin::from_env() {
+        Ok(password_login) => password_login,
+        Err(e) => {
+            eprintln!("Failed to load the login users: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Either login hands out session cookies, which must then be asked for.
+    #[cfg(feature = "oidc")]
+    let sessions = oidc.is_some() || password_login.is_some();
+    #[cfg(not(feature = "oidc"))]
+    let sessions = password_login.is_some();
+    let auth = match Authentication::from_env() {
+        Ok(auth) => Authentication { sessions, client_certs, ..auth },
+        Err(e) => {
+            eprintln!("Failed to load authentication keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Session cookies are encrypted with this key; without one they don't survive a restart.
+    let secret_key = match secrets::var("SESSION_SECRET") {
+        Ok(secret) if secret.len() >= 32 => SecretKey::derive_from(secret.as_bytes()),
+        Ok(_) => {
+            eprintln!("SESSION_SECRET must be at least 32 bytes");
+            std::process::exit(1);
+        }
+        // `SecretKey::generate` would count as not provided, which release builds refuse.
+        Err(_) => {
+            let mut key = [0u8; 64];
+            rand::thread_rng().fill(&mut key[..]);
+            SecretKey::from(&key)
+        }
+    };
+    let trusted_proxies = match ip_filter::networks("TRUSTED_PROXIES") {
+        Ok(trusted_proxies) => trusted_proxies,
+        Err(e) => {
+            eprintln!("Failed to load the trusted proxies: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let ip_filter = match IpFilter::from_env() {
+        Ok(ip_filter) => ip_filter,
+        Err(e) => {
+            eprintln!("Failed to load the IP allow and deny lists: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let request_log = match RequestLog::from_env() {
+        Ok(request_log) => request_log,
+        Err(e) => {
+            eprintln!("Failed to set up the request log: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let access_log = match AccessLog::from_env() {
+        Ok(access_log) => access_log,
+        Err(e) => {
+            eprintln!("Failed to open the access log: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let audit = match AuditLog::from_env() {
+        Ok(audit) => Arc::new(audit),
+        Err(e) => {
+            eprintln!("Failed to open the audit log: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let search_index = Arc::new(SearchIndex::default());
+    let repository = open_repository(&settings, overrides, search_index.clone()).await;
+    #[cfg(feature = "otel")]
+    let traced = telemetry::traced;
+    #[cfg(not(feature = "otel"))]
+    let traced = std::convert::identity;
 
     let config = Config {
-        address: "0.0.0.0".parse().unwrap(),
-        port: 8080,
-        ..Config::default()
+        secret_key,
+        // Colors would end up as escape codes inside the log lines.
+        cli_colors: false,
+        #[cfg(feature = "tls")]
+        tls,
+        ..config
+    };
+    #[cfg(unix)]
+    let unix_socket = match UnixSocket::from_env() {
+        Ok(unix_socket) => unix_socket,
+        Err(e) => {
+            eprintln!("Failed to set up the Unix socket: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(unix)]
+    let config = match unix_socket {
+        Some(_) => UnixSocket::tcp_config(config),
+        None => config,
     };
+    #[cfg(not(unix))]
+    if env::var("LISTEN_SOCKET").is_ok() {
+        eprintln!("LISTEN_SOCKET is only supported on Unix");
+        std::process::exit(1);
+    }
 
-    rocket::custom(config)
+    #[cfg(feature = "tls")]
+    let redirect = tls::redirect_from_env(&config);
+    // Response fairings run in the order they were attached, so refusals are answered
+    // before the others add their headers or count them.
+    // The template fairing reads its directory from the figment, which `Config` leaves out.
+    let figment = Figment::from(config).merge(("template_dir", &settings.template_dir));
+    let rocket = rocket::custom(figment).attach(Refusals).attach(RequestIds);
+    // First, so the latencies cover every other fairing too.
+    let rocket = rocket.attach(request_log);
+    let rocket = match access_log {
+        Some(access_log) => rocket.attach(access_log),
+        None => rocket,
+    };
+    let rocket = match BodyLog::from_env() {
+        Some(body_log) => rocket.attach(body_log),
+        None => rocket,
+    };
+    #[cfg(feature = "sentry")]
+    let rocket = match error_reporting {
+        Some(error_reporting) => rocket.attach(error_reporting),
+        None => rocket,
+    };
+    #[cfg(feature = "otel")]
+    let rocket = match telemetry::enabled() {
+        true => rocket.attach(telemetry::TraceRequests).attach(telemetry::shutdown()),
+        false => rocket,
+    };
+    let rocket = match Metrics::from_env() {
+        Some(metrics) => rocket.manage(metrics).attach(RequestMetrics).mount("/", metrics::routes()),
+        None => rocket,
+    };
+    #[cfg(feature = "tls")]
+    let rocket = match redirect {
+        Some(redirect) => rocket.attach(redirect),
+        None => rocket,
+    };
+    let rocket = match settings.max_concurrent_requests {
+        Some(max) => rocket.attach(ConcurrencyLimit::new(max)),
+        None => rocket,
+    };
+    // Before the rate limiter, so refused clients don't use up anyone's bucket.
+    let rocket = match ip_filter {
+        Some(ip_filter) => rocket.attach(ip_filter),
+        None => rocket,
+    };
+    let lockout = Lockout::from_env();
+    let rocket = match lockout {
+        Some(_) => rocket.attach(Lockouts),
+        None => rocket,
+    };
+    let rocket = rocket.attach(MaintenanceMode).attach(RateLimiter::default());
+    #[cfg(unix)]
+    let rocket = rocket.attach(ReloadOnHangup(live.clone()));
+    #[cfg(unix)]
+    let rocket = match unix_socket {
+        Some(unix_socket) => rocket.attach(unix_socket),
+        None => rocket,
+    };
+    let rocket = match SecurityHeaders::from_env() {
+        Some(headers) => rocket.attach(headers),
+        None => rocket,
+    };
+    let rocket = match Cors::from_env(&settings.cors_allowed_origins) {
+        Some(cors) => rocket.attach(cors).mount("/", cors::routes()),
+        None => rocket,
+    };
+    #[cfg(feature = "oidc")]
+    let rocket = match oidc {
+        Some(oidc) => rocket.manage(oidc).mount("/auth", oidc::routes()),
+        None => rocket,
+    };
+    let rocket = match password_login {
+        Some(password_login) => rocket.manage(password_login).mount("/", login::routes()),
+        None => rocket,
+    };
+    rocket
+        .attach(audit::fairing())
+        .attach(LifecycleEvents)
+        .attach(Template::fairing())
+        .attach(expiry::fairing(repository.clone(), audit.clone(), expiry_interval))
         .manage(AppState {
-            person_collection: RwLock::new(person::create_person_collection()),
-            greeting_text,
+            repository,
+            search_index,
+            live,
+            backup_dir,
+            seed_file: settings.seed_file.clone(),
+            sample_persons: settings.sample_persons,
+            tz_default: settings.tz_default,
+            error_detail: settings.error_detail,
+            soft_delete,
+            require_if_match,
+            id_mode,
+            ids: IdAllocator::default(),
+            multi_tenant,
+            reject_unknown_fields,
+            auth,
+            audit,
+            signing: RequestSigning::from_env(),
+            lockout,
+            trusted_proxies,
+            lifecycle: Lifecycle::default(),
+            maintenance: Maintenance::default(),
         })
-        .mount("/", routes::get_routes())
+        .register("/", catchers::get_catchers())
+        .mount("/", traced(panics::caught(routes::get_routes())))
+        .mount(routes::API_V1, traced(panics::caught(routes::v1_routes())))
+        .mount(routes::API_LEGACY, traced(panics::caught(routes::v1_routes())))
+        .mount(routes::API_V2, traced(panics::caught(flags::gated(flags::API_V2, routes::v2_routes()))))
+        .mount(assets::STATIC_PATH, assets::routes(&settings.static_dir))
 }
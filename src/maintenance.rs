@@ -0,0 +1,63 @@
+use std::sync::{PoisonError, RwLock};
+use chrono::{DateTime, Utc};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::Data;
+use serde::Serialize;
+use crate::errors::ApiError;
+use crate::refusal;
+use crate::AppState;
+
+/// Paths still served during maintenance: the admin endpoints that turn it off, signing
+/// in to reach them, and what probes and scrapers ask for.
+const EXEMPT: [&str; 9] = ["/admin", "/auth", "/login", "/logout", "/health", "/livez", "/readyz", "/metrics", "/version"];
+
+#[derive(Clone, Serialize)]
+pub struct Notice {
+    pub message: String,
+    /// Seconds clients are told to wait, in `Retry-After`.
+    pub retry_after: u64,
+    pub since: DateTime<Utc>,
+}
+
+/// Whether the service is down for maintenance, as an admin set it with
+/// `PUT /admin/maintenance`; it starts off and is forgotten on restart.
+#[derive(Default)]
+pub struct Maintenance(RwLock<Option<Notice>>);
+
+impl Maintenance {
+    pub fn current(&self) -> Option<Notice> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub fn set(&self, notice: Option<Notice>) {
+        match &notice {
+            Some(notice) => warn!("Maintenance mode on: {}", notice.message),
+            None => warn!("Maintenance mode off"),
+        }
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = notice;
+    }
+}
+
+fn exempt(path: &str) -> bool {
+    EXEMPT.iter().any(|prefix| path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+/// Answers every request but the [`EXEMPT`] ones with a 503 while [`Maintenance`] is on.
+pub struct MaintenanceMode;
+
+#[rocket::async_trait]
+impl Fairing for MaintenanceMode {
+    fn info(&self) -> Info {
+        Info { name: "Maintenance mode", kind: Kind::Request }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(notice) = request.rocket().state::<AppState>().and_then(|state| state.maintenance.current()) else {
+            return;
+        };
+        if !exempt(request.uri().path().as_str()) {
+            refusal::refuse(request, ApiError::Maintenance(notice.message, notice.retry_after));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::Response;
+use rocket::{Data, Route, State};
+use serde::Serialize;
+use crate::auth::Admin;
+use crate::errors::ApiError;
+use crate::AppState;
+
+/// Upper bounds, in seconds, of the latency histogram buckets (Prometheus' defaults).
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub fn routes() -> Vec<Route> {
+    routes![metrics]
+}
+
+/// Requests of one method and route answered with one status.
+#[derive(Default)]
+struct Series {
+    count: u64,
+    seconds: f64,
+    /// Requests that took at most the matching [`BUCKETS`] bound.
+    buckets: [u64; BUCKETS.len()],
+}
+
+/// Requests answered by one route, for `/admin/stats`.
+#[derive(Serialize)]
+pub struct RouteRequests {
+    pub method: String,
+    pub route: String,
+    pub requests: u64,
+    /// Those answered with a 5xx status.
+    pub server_errors: u64,
+}
+
+/// [`Metrics::route_requests`], or none with `METRICS=false`. A `&State<Metrics>` guard
+/// would stop Rocket from launching without them.
+pub struct RouteCounts(pub Vec<RouteRequests>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RouteCounts {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let requests = request.rocket().state::<Metrics>().map(Metrics::route_requests).unwrap_or_default();
+        request::Outcome::Success(RouteCounts(requests))
+    }
+}
+
+/// Request counts and latencies per method, route and status, collected by
+/// [`RequestMetrics`]; on unless `METRICS=false`.
+#[derive(Default)]
+pub struct Metrics {
+    in_flight: AtomicI64,
+    panics: AtomicU64,
+    /// Keyed by method, route (its URI pattern, so ids don't multiply the series) and status.
+    series: Mutex<BTreeMap<(String, String, u16), Series>>,
+    /// Requests slower than [`RequestLog`](crate::logging::RequestLog)'s threshold, by method and route.
+    slow: Mutex<BTreeMap<(String, String), u64>>,
+}
+
+impl Metrics {
+    pub fn from_env() -> Option<Self> {
+        match env::var("METRICS").as_deref() {
+            Ok("false") | Ok("0") => None,
+            _ => Some(Metrics::default()),
+        }
+    }
+
+    fn observe(&self, method: String, route: String, status: u16, seconds: f64) {
+        let mut series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        let series = series.entry((method, route, status)).or_default();
+        series.count += 1;
+        series.seconds += seconds;
+        for (bucket, bound) in series.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    pub fn panicked(&self) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn slow_request(&self, method: String, route: String) {
+        *self.slow.lock().unwrap_or_else(PoisonError::into_inner).entry((method, route)).or_default() += 1;
+    }
+
+    /// Request counts by method and route, their statuses added up.
+    pub fn route_requests(&self) -> Vec<RouteRequests> {
+        let mut routes: BTreeMap<(&str, &str), RouteRequests> = BTreeMap::new();
+        let series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        for ((method, route, status), series) in series.iter() {
+            let requests = routes.entry((method, route)).or_insert_with(|| RouteRequests {
+                method: method.clone(),
+                route: route.clone(),
+                requests: 0,
+                server_errors: 0,
+            });
+            requests.requests += series.count;
+            if *status >= 500 {
+                requests.server_errors += series.count;
+            }
+        }
+        routes.into_values().collect()
+    }
+
+    fn render(&self, persons: &[(String, usize)]) -> String {
+        let mut text = String::new();
+        // Writing to a String cannot fail.
+        let _ = writeln!(text, "# HELP http_requests_total Requests answered, by method, route and status.");
+        let _ = writeln!(text, "# TYPE http_requests_total counter");
+        let series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        for ((method, route, status), series) in series.iter() {
+            let _ = writeln!(text, "http_requests_total{{{}}} {}", labels(method, route, *status), series.count);
+        }
+        let _ = writeln!(text, "# HELP http_request_duration_seconds Time from request to response, by method, route and status.");
+        let _ = writeln!(text, "# TYPE http_request_duration_seconds histogram");
+        for ((method, route, status), series) in series.iter() {
+            let labels = labels(method, route, *status);
+            for (bound, count) in BUCKETS.iter().zip(series.buckets) {
+                let _ = writeln!(text, "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, count);
+            }
+            let _ = writeln!(text, "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, series.count);
+            let _ = writeln!(text, "http_request_duration_seconds_sum{{{}}} {}", labels, series.seconds);
+            let _ = writeln!(text, "http_request_duration_seconds_count{{{}}} {}", labels, series.count);
+        }
+        drop(series);
+        let _ = writeln!(text, "# HELP http_slow_requests_total Requests slower than SLOW_REQUEST_MS, by method and route.");
+        let _ = writeln!(text, "# TYPE http_slow_requests_total counter");
+        for ((method, route), count) in self.slow.lock().unwrap_or_else(PoisonError::into_inner).iter() {
+            let _ = writeln!(text, "http_slow_requests_total{{method=\"{}\",route=\"{}\"}} {}", method, escape(route), count);
+        }
+        let _ = writeln!(text, "# HELP http_requests_in_flight Requests being handled.");
+        let _ = writeln!(text, "# TYPE http_requests_in_flight gauge");
+        let _ = writeln!(text, "http_requests_in_flight {}", self.in_flight.load(Ordering::Relaxed));
+        let _ = writeln!(text, "# HELP panics_total Handlers that panicked and were answered with a 500.");
+        let _ = writeln!(text, "# TYPE panics_total counter");
+        let _ = writeln!(text, "panics_total {}", self.panics.load(Ordering::Relaxed));
+        let _ = writeln!(text, "# HELP persons Persons stored, soft-deleted ones included, by tenant.");
+        let _ = writeln!(text, "# TYPE persons gauge");
+        for (tenant, count) in persons {
+            let _ = writeln!(text, "persons{{tenant=\"{}\"}} {}", escape(tenant), count);
+        }
+        text
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(method: &str, route: &str, status: u16) -> String {
+    format!("method=\"{}\",route=\"{}\",status=\"{}\"", method, escape(route), status)
+}
+
+#[get("/metrics")]
+async fn metrics(_admin: Admin, metrics: &State<Metrics>, state: &State<AppState>) -> Result<(ContentType, String), ApiError> {
+    let mut persons = Vec::new();
+    for tenant in state.repository.tenants().await? {
+        let count = state.repository.list(&tenant).await?.len();
+        persons.push((tenant, count));
+    }
+    let content_type = ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    Ok((content_type, metrics.render(&persons)))
+}
+
+struct Started(Instant);
+
+/// Feeds [`Metrics`] from every request, whichever route answers it.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info { name: "Metrics", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if let Some(metrics) = request.rocket().state::<Metrics>() {
+            metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+            request.local_cache(|| Started(Instant::now()));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(metrics) = request.rocket().state::<Metrics>() else {
+            return;
+        };
+        metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+        let seconds = request.local_cache(|| Started(Instant::now())).0.elapsed().as_secs_f64();
+        let route = request.route().map_or("unmatched".to_string(), |route| route.uri.path().to_string());
+        metrics.observe(request.method().as_str().to_string(), route, response.status().code, seconds);
+    }
+}
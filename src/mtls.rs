@@ -0,0 +1,26 @@
+use rocket::http::Status;
+use rocket::mtls::Certificate;
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// The caller as named by its client certificate, which the TLS layer has already
+/// checked against `TLS_CLIENT_CA_PATH`. Forwards when no certificate was sent.
+pub struct ClientIdentity {
+    /// The certificate subject's common name.
+    pub subject: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientIdentity {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<Certificate<'_>>().await {
+            Outcome::Success(certificate) => match certificate.subject().common_name() {
+                Some(subject) => Outcome::Success(ClientIdentity { subject: subject.to_string() }),
+                None => Outcome::Error((Status::Unauthorized, ())),
+            },
+            Outcome::Error((status, _)) => Outcome::Error((status, ())),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Whether the client asked for XML, with `?format=xml` or an `Accept` header preferring it.
+fn wants_xml(request: &Request<'_>) -> bool {
+    if let Some(Ok(format)) = request.query_value::<&str>("format") {
+        return format.eq_ignore_ascii_case("xml");
+    }
+    request.accept().is_some_and(|accept| {
+        let preferred = accept.preferred().media_type();
+        matches!(preferred.top().as_str(), "application" | "text") && preferred.sub() == "xml"
+    })
+}
+
+/// The type of XML bodies, which their `ETag`s are derived with.
+pub fn xml() -> ContentType {
+    ContentType::new("application", "xml")
+}
+
+/// A sequence serialized as repeated `item` elements; quick-xml needs the element name up front.
+struct Items<'a, T> {
+    item: &'static str,
+    value: &'a T,
+}
+
+impl<T: Serialize> Serialize for Items<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut items = serializer.serialize_struct("items", 1)?;
+        items.serialize_field(self.item, self.value)?;
+        items.end()
+    }
+}
+
+/// A body sent as JSON by default, or as XML under the `root` element when negotiated.
+pub struct Negotiated<T> {
+    value: T,
+    root: &'static str,
+    item: Option<&'static str>,
+}
+
+impl<T> Negotiated<T> {
+    pub fn one(root: &'static str, value: T) -> Self {
+        Negotiated { value, root, item: None }
+    }
+
+    /// A list, rendered in XML as one `item` element per entry inside `root`.
+    pub fn many(root: &'static str, item: &'static str, value: T) -> Self {
+        Negotiated { value, root, item: Some(item) }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Negotiated<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match wants_xml(request) {
+            true => {
+                let xml_body = match self.item {
+                    Some(item) => quick_xml::se::to_string_with_root(self.root, &Items { item, value: &self.value }),
+                    None => quick_xml::se::to_string_with_root(self.root, &self.value),
+                };
+                let xml_body = xml_body.map_err(|_| Status::InternalServerError)?;
+                Response::build_from((xml(), xml_body).respond_to(request)?).finalize()
+            }
+            false => Json(self.value).respond_to(request)?,
+        };
+        response.set_raw_header("Vary", "Accept");
+        Ok(response)
+    }
+}
@@ -0,0 +1,222 @@
+use std::env;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rand::distributions::{Alphanumeric, DistString};
+use reqwest::Url;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::response::Redirect;
+use rocket::tokio::sync::{Mutex, RwLock};
+use rocket::{Route, State};
+use serde::Deserialize;
+use crate::auth::{self, Session};
+use crate::errors::ApiError;
+use crate::secrets;
+
+/// Holds `state` and `nonce` of a login between `/auth/login` and `/auth/callback`.
+const FLOW_COOKIE: &str = "oidc_flow";
+
+/// The least time between two fetches of the provider's keys for tokens naming an unknown one.
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn routes() -> Vec<Route> {
+    routes![login, callback]
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdClaims {
+    sub: String,
+    nonce: Option<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Login through an external OpenID Connect provider, configured with
+/// `OIDC_ISSUER_URL`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET` and `OIDC_REDIRECT_URL`
+/// (this service's `/auth/callback` as registered with the provider). Signed-in
+/// browsers get a [`Session`] carrying the ID token's `roles` claim.
+pub struct Oidc {
+    client_id: String,
+    client_secret: String,
+    redirect_url: String,
+    session_ttl: Duration,
+    provider: Discovery,
+    http: reqwest::Client,
+    /// Refetched when a token names a key id that isn't in it, as after a key rotation.
+    jwks: RwLock<JwkSet>,
+    /// When the keys were last fetched; held while they are fetched, so one fetch serves
+    /// every token waiting on it.
+    jwks_fetched: Mutex<Instant>,
+}
+
+impl Oidc {
+    /// Reads the provider's discovery document and keys; `None` when OIDC isn't configured.
+    pub async fn discover_from_env() -> Result<Option<Self>, String> {
+        let Ok(issuer) = env::var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+        let setting = |name: &str| env::var(name).map_err(|_| format!("{} is required with OIDC_ISSUER_URL", name));
+        let client_secret = secrets::var("OIDC_CLIENT_SECRET").map_err(|_| "OIDC_CLIENT_SECRET is required with OIDC_ISSUER_URL".to_string())?;
+        let (client_id, redirect_url) = (setting("OIDC_CLIENT_ID")?, setting("OIDC_REDIRECT_URL")?);
+        let session_ttl = auth::session_ttl_from_env();
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let discovery_url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let provider: Discovery = fetch_json(&http, &discovery_url).await?;
+        let jwks = fetch_json(&http, &provider.jwks_uri).await?;
+        Ok(Some(Oidc {
+            client_id,
+            client_secret,
+            redirect_url,
+            session_ttl,
+            provider,
+            http,
+            jwks: RwLock::new(jwks),
+            jwks_fetched: Mutex::new(Instant::now()),
+        }))
+    }
+
+    fn is_secure(&self) -> bool {
+        self.redirect_url.starts_with("https://")
+    }
+
+    fn authorize_url(&self, state: &str, nonce: &str) -> Result<String, ApiError> {
+        let mut url = Url::parse(&self.provider.authorization_endpoint).map_err(|e| ApiError::Internal(e.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", "openid profile email")
+            .append_pair("state", state)
+            .append_pair("nonce", nonce);
+        Ok(url.into())
+    }
+
+    /// Trades the authorization code for the provider's ID token.
+    async fn exchange(&self, code: &str) -> Result<String, ApiError> {
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+        ];
+        let response = self.http.post(&self.provider.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| ApiError::Unavailable(format!("OIDC token endpoint: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(ApiError::Unauthorized("the identity provider rejected the authorization code"));
+        }
+        let tokens: TokenResponse = response.json().await
+            .map_err(|e| ApiError::Unavailable(format!("OIDC token endpoint: {}", e)))?;
+        Ok(tokens.id_token)
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, ApiError> {
+        let unknown_key = || ApiError::Unauthorized("the ID token is signed with an unknown key");
+        if let Some(jwk) = self.jwks.read().await.find(kid) {
+            return DecodingKey::from_jwk(jwk).map_err(|_| unknown_key());
+        }
+        // Made-up key ids must not make every request a fetch from the provider.
+        let mut fetched = self.jwks_fetched.lock().await;
+        if fetched.elapsed() >= JWKS_REFETCH_INTERVAL {
+            *fetched = Instant::now();
+            let jwks: JwkSet = fetch_json(&self.http, &self.provider.jwks_uri).await.map_err(ApiError::Unavailable)?;
+            *self.jwks.write().await = jwks;
+        }
+        drop(fetched);
+        match self.jwks.read().await.find(kid) {
+            Some(jwk) => DecodingKey::from_jwk(jwk).map_err(|_| unknown_key()),
+            None => Err(unknown_key()),
+        }
+    }
+
+    /// Checks the ID token's signature against the provider's keys, and its issuer,
+    /// audience, expiry and nonce.
+    async fn verify(&self, id_token: &str, nonce: &str) -> Result<IdClaims, ApiError> {
+        let invalid = || ApiError::Unauthorized("the ID token is not valid");
+        let header = jsonwebtoken::decode_header(id_token).map_err(|_| invalid())?;
+        // Provider tokens are signed with the provider's private key, never a shared secret.
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(invalid());
+        }
+        let key = self.decoding_key(&header.kid.ok_or_else(invalid)?).await?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.provider.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        let claims = jsonwebtoken::decode::<IdClaims>(id_token, &key, &validation).map_err(|_| invalid())?.claims;
+        match claims.nonce.as_deref() == Some(nonce) {
+            true => Ok(claims),
+            false => Err(invalid()),
+        }
+    }
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(http: &reqwest::Client, url: &str) -> Result<T, String> {
+    let response = http.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| format!("{}: {}", url, e))?;
+    response.json().await.map_err(|e| format!("{}: {}", url, e))
+}
+
+/// Sends the browser to the provider, remembering what the callback must see again.
+#[get("/login")]
+fn login(cookies: &CookieJar<'_>, oidc: &State<Oidc>) -> Result<Redirect, ApiError> {
+    let mut rng = rand::thread_rng();
+    let (state, nonce) = (Alphanumeric.sample_string(&mut rng, 32), Alphanumeric.sample_string(&mut rng, 32));
+    let url = oidc.authorize_url(&state, &nonce)?;
+    let flow = Cookie::build((FLOW_COOKIE, format!("{} {}", state, nonce)))
+        .path("/auth")
+        .same_site(SameSite::Lax)
+        .secure(oidc.is_secure())
+        .http_only(true)
+        .build();
+    cookies.add_private(flow);
+    Ok(Redirect::to(url))
+}
+
+#[get("/callback?<code>&<state>&<error>")]
+async fn callback(
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+    cookies: &CookieJar<'_>,
+    oidc: &State<Oidc>,
+) -> Result<Redirect, ApiError> {
+    let flow = cookies.get_private(FLOW_COOKIE).map(|cookie| cookie.value().to_string());
+    cookies.remove_private(Cookie::build(FLOW_COOKIE).path("/auth"));
+    if error.is_some() {
+        return Err(ApiError::Unauthorized("the identity provider refused the login"));
+    }
+    let (Some(code), Some(state), Some(flow)) = (code, state, flow) else {
+        return Err(ApiError::Unauthorized("the login was not started here or has expired"));
+    };
+    let Some((_, nonce)) = flow.split_once(' ').filter(|(expected, _)| *expected == state) else {
+        return Err(ApiError::Unauthorized("the login state does not match"));
+    };
+    let id_token = oidc.exchange(code).await?;
+    let claims = oidc.verify(&id_token, nonce).await?;
+    let session = Session {
+        subject: claims.sub,
+        roles: claims.roles,
+        expires_at: Utc::now() + oidc.session_ttl,
+    };
+    cookies.add_private(session.cookie(oidc.is_secure()));
+    Ok(Redirect::to("/"))
+}
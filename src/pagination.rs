@@ -0,0 +1,102 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use serde::Serialize;
+use crate::errors::ApiError;
+use crate::negotiate::Negotiated;
+use crate::person::Person;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+/// Offset (`limit`/`offset`) or cursor (`cursor`/`page_size`) pagination. Any cursor
+/// parameter switches to cursor mode, which always walks the collection in id order
+/// so concurrent inserts and deletes never shift a page.
+#[derive(FromForm)]
+pub struct Pagination {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub cursor: Option<String>,
+    pub page_size: Option<usize>,
+}
+
+impl Pagination {
+    fn is_cursor(&self) -> bool {
+        self.cursor.is_some() || self.page_size.is_some()
+    }
+
+    pub fn apply(&self, mut persons: Vec<Person>) -> Result<Page<Person>, ApiError> {
+        let total = persons.len();
+        if self.is_cursor() {
+            let after = self.cursor.as_deref().map(decode_cursor).transpose()?;
+            let page_size = clamp_limit(self.page_size);
+            persons.sort_by_key(|p| p.id);
+            let mut items: Vec<Person> = persons.into_iter()
+                .filter(|p| after.map_or(true, |after| p.id > after))
+                .take(page_size + 1)
+                .collect();
+            let has_more = items.len() > page_size;
+            items.truncate(page_size);
+            let next = items.last().filter(|_| has_more).map(|p| Next::Cursor(encode_cursor(p.id)));
+            return Ok(Page { items, total, next });
+        }
+
+        let offset = self.offset.unwrap_or(0);
+        let items: Vec<Person> = persons.into_iter().skip(offset).take(clamp_limit(self.limit)).collect();
+        let next = Some(offset + items.len()).filter(|next| *next < total).map(Next::Offset);
+        Ok(Page { items, total, next })
+    }
+}
+
+fn clamp_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+fn encode_cursor(last_id: u32) -> String {
+    URL_SAFE_NO_PAD.encode(format!("after:{}", last_id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<u32, ApiError> {
+    URL_SAFE_NO_PAD.decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|text| text.strip_prefix("after:").and_then(|id| id.parse().ok()))
+        .ok_or_else(|| ApiError::query("cursor", "is not a cursor from a previous page"))
+}
+
+pub enum Next {
+    Offset(usize),
+    Cursor(String),
+}
+
+/// A slice of a listing; the body stays a plain JSON array (or XML list) and the pagination
+/// metadata travels in `X-Total-Count` plus `X-Next-Offset` or `X-Next-Cursor`.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub next: Option<Next>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page { items: self.items.into_iter().map(f).collect(), total: self.total, next: self.next }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Page<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build_from(Negotiated::many("persons", "person", self.items).respond_to(request)?);
+        response.raw_header("X-Total-Count", self.total.to_string());
+        match self.next {
+            Some(Next::Offset(offset)) => {
+                response.raw_header("X-Next-Offset", offset.to_string());
+            }
+            Some(Next::Cursor(cursor)) => {
+                response.raw_header("X-Next-Cursor", cursor);
+            }
+            None => {}
+        }
+        response.ok()
+    }
+}
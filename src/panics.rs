@@ -0,0 +1,62 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::{self, AssertUnwindSafe};
+use rocket::futures::FutureExt;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome};
+use rocket::{Data, Route};
+use crate::errors::ApiError;
+use crate::metrics::Metrics;
+
+/// Logs every panic with its location and a backtrace, in place of the default
+/// message on stderr. Installed before Sentry's hook, which chains to it.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().map(|location| location.to_string()).unwrap_or_default();
+        tracing::error!(
+            panic = message(info.payload()),
+            location,
+            backtrace = %Backtrace::force_capture(),
+            "panic",
+        );
+    }));
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "non-string panic payload",
+    }
+}
+
+#[derive(Clone)]
+struct CatchPanics(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for CatchPanics {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        // Nothing a handler holds is left half-written for the next request: the store's
+        // locks don't poison and our own mutexes are recovered from poisoning.
+        match AssertUnwindSafe(self.0.handle(request, data)).catch_unwind().await {
+            Ok(outcome) => outcome,
+            Err(payload) => {
+                if let Some(metrics) = request.rocket().state::<Metrics>() {
+                    metrics.panicked();
+                }
+                Outcome::from(request, ApiError::Internal(format!("the handler panicked: {}", message(&*payload))))
+            }
+        }
+    }
+}
+
+/// Answers a panic in any of `routes` with the usual JSON 500 carrying the request id,
+/// and counts it in the `panics_total` metric.
+pub fn caught(routes: Vec<Route>) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(CatchPanics(route.handler));
+            route
+        })
+        .collect()
+}
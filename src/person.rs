@@ -1,17 +1,101 @@
-use chrono::NaiveDate;
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::dates;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Person {
+    // Optional in request bodies so servers that assign ids can accept persons without one.
+    #[serde(default)]
     pub id: u32,
     pub name: String,
     pub age: u8,
-    pub date: NaiveDate,
+    /// RFC 3339 on the wire; legacy formats are still read, see [`dates::parse`].
+    #[serde(deserialize_with = "dates::deserialize")]
+    pub date: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Bumped by the server on every change; clients' values are ignored.
+    #[serde(default = "initial_version")]
+    pub version: u32,
+    /// Server-assigned public identifier when running with `ID_MODE=uuid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<Uuid>,
+    /// Managed through `/person/<id>/addresses`; ids are unique within the person.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<Address>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Address {
+    // Assigned by the server when the address is added.
+    #[serde(default)]
+    pub id: u32,
+    pub street: String,
+    pub city: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+pub const INITIAL_VERSION: u32 = 1;
+
+// Data stored before versioning existed starts at the initial version.
+fn initial_version() -> u32 {
+    INITIAL_VERSION
+}
+
+/// Validated partial update for `PATCH`; only the fields present are applied.
+pub struct PersonPatch {
+    pub name: Option<String>,
+    pub age: Option<u8>,
+    pub date: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl PersonPatch {
+    pub fn apply(self, person: &mut Person) {
+        if let Some(name) = self.name {
+            person.name = name;
+        }
+        if let Some(age) = self.age {
+            person.age = age;
+        }
+        if let Some(date) = self.date {
+            person.date = date;
+        }
+        if let Some(expires_at) = self.expires_at {
+            person.expires_at = Some(expires_at);
+        }
+    }
+}
+
+impl Person {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Stores `address` under the next address id of this person and returns it with that id.
+    pub fn add_address(&mut self, mut address: Address) -> Address {
+        address.id = self.addresses.iter().map(|a| a.id).max().unwrap_or(0) + 1;
+        self.addresses.push(address.clone());
+        address
+    }
+
+    /// Gives the addresses of a newly created person the ids `1..`.
+    pub fn renumber_addresses(&mut self) {
+        for (id, address) in (1..).zip(self.addresses.iter_mut()) {
+            address.id = id;
+        }
+    }
 }
 
 impl std::fmt::Display for Person {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{} {} {} {:?}", self.id, self.name, self.age, self.date)
+        write!(f, "{} {} {} {}", self.id, self.name, self.age, self.date.to_rfc3339())
     }
 }
 
@@ -21,13 +105,23 @@ pub fn create_person_collection() -> Vec<Person> {
             id: 1,
             name: "Mario".to_string(),
             age: 43,
-            date: NaiveDate::from_ymd_opt(1981, 2, 21).unwrap(),
+            date: Utc.with_ymd_and_hms(1981, 2, 21, 0, 0, 0).unwrap(),
+            deleted_at: None,
+            expires_at: None,
+            version: INITIAL_VERSION,
+            uuid: None,
+            addresses: Vec::new(),
         },
         Person {
             id: 2,
             name: "Luigi".to_string(),
             age: 41,
-            date: NaiveDate::from_ymd_opt(1983, 3, 25).unwrap(),
+            date: Utc.with_ymd_and_hms(1983, 3, 25, 0, 0, 0).unwrap(),
+            deleted_at: None,
+            expires_at: None,
+            version: INITIAL_VERSION,
+            uuid: None,
+            addresses: Vec::new(),
         },
     ]
 }
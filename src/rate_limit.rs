@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::Data;
+use serde::Serialize;
+use crate::auth::API_KEY_HEADER;
+use crate::errors::ApiError;
+use crate::ip_filter;
+use crate::refusal;
+use crate::settings::Settings;
+use crate::AppState;
+
+/// Buckets past this many are pruned of those that have filled up again.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Each client's bucket holds up to `burst` requests and refills at `rate` per second.
+#[derive(Clone, Copy, Serialize)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl RateLimit {
+    /// On with `rate_limit_rps` (`RATE_LIMIT_RPS`); `rate_limit_burst` defaults to one second's worth.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        let rate = settings.rate_limit_rps?;
+        Some(RateLimit { rate, burst: settings.rate_limit_burst.unwrap_or(rate.max(1.0)) })
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+}
+
+/// A token bucket per client: its API key if it sends a valid one, otherwise its IP,
+/// limited by the current [`RateLimit`], if any.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from `client`'s bucket, or says how long until one is available.
+    fn take(&self, limit: RateLimit, client: String, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| limit.refilled(bucket, now) < limit.burst);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: limit.burst, updated: now });
+        bucket.tokens = limit.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / limit.rate))
+    }
+}
+
+/// Only accepted keys count, or clients could dodge the limit by making up new ones.
+fn client_key(request: &Request<'_>) -> String {
+    let key = request.headers().get_one(API_KEY_HEADER)
+        .filter(|key| request.rocket().state::<AppState>().is_some_and(|state| state.auth.accepts_key(key)));
+    match (key, ip_filter::client(request)) {
+        (Some(key), _) => format!("key {}", key),
+        (None, Some(ip)) => format!("ip {}", ip),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info { name: "Rate Limit", kind: Kind::Request }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(limit) = request.rocket().state::<AppState>().and_then(|state| state.live.current().rate_limit) else {
+            return;
+        };
+        if let Err(wait) = self.take(limit, client_key(request), Instant::now()) {
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            refusal::refuse(request, ApiError::TooManyRequests(seconds));
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use serde::Deserialize;
+
+/// What a route guard asks of the caller.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Reading persons.
+    Read,
+    /// Creating, changing and deleting persons.
+    Write,
+    /// The `/admin` endpoints.
+    Admin,
+}
+
+/// Which roles grant which permissions, and which principals hold which roles
+/// beyond those their credentials carry. Loaded from the JSON file in `RBAC_FILE`:
+///
+/// ```json
+/// {
+///   "roles": { "viewer": ["read"], "editor": ["read", "write"], "ops": ["read", "admin"] },
+///   "principals": { "ci-bot": ["editor"], "alice@example.com": ["ops"] },
+///   "anonymous": ["viewer"]
+/// }
+/// ```
+///
+/// Principals are API key names and token or session subjects; `anonymous` are the
+/// roles of callers without credentials.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    roles: HashMap<String, HashSet<Permission>>,
+    #[serde(default)]
+    principals: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    anonymous: Vec<String>,
+}
+
+/// Without `RBAC_FILE`: anyone may read, `editor` may write and `admin` may use `/admin`.
+impl Default for Policy {
+    fn default() -> Self {
+        let role = |name: &str, permissions: &[Permission]| (name.to_string(), permissions.iter().copied().collect());
+        Policy {
+            roles: HashMap::from([
+                role("reader", &[Permission::Read]),
+                role("editor", &[Permission::Read, Permission::Write]),
+                role("admin", &[Permission::Read, Permission::Admin]),
+            ]),
+            principals: HashMap::new(),
+            anonymous: vec!["reader".to_string()],
+        }
+    }
+}
+
+impl Policy {
+    pub fn from_env() -> io::Result<Self> {
+        let Ok(path) = env::var("RBAC_FILE") else {
+            return Ok(Policy::default());
+        };
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("RBAC_FILE {}: {}", path, message));
+        let text = fs::read_to_string(&path).map_err(|e| io::Error::new(e.kind(), format!("RBAC_FILE {}: {}", path, e)))?;
+        let policy: Policy = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        let granted = policy.principals.iter()
+            .flat_map(|(principal, roles)| roles.iter().map(move |role| (principal.as_str(), role)))
+            .chain(policy.anonymous.iter().map(|role| ("anonymous", role)));
+        for (principal, role) in granted {
+            if !policy.roles.contains_key(role) {
+                return Err(invalid(format!("{} is given the undefined role {:?}", principal, role)));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Whether `roles`, or those the policy gives `subject`, grant `permission`.
+    pub fn permits(&self, subject: Option<&str>, roles: &[String], permission: Permission) -> bool {
+        let assigned = subject.and_then(|subject| self.principals.get(subject)).into_iter().flatten();
+        roles.iter().chain(assigned).any(|role| self.grants(role, permission))
+    }
+
+    pub fn permits_anonymous(&self, permission: Permission) -> bool {
+        self.anonymous.iter().any(|role| self.grants(role, permission))
+    }
+
+    fn grants(&self, role: &str, permission: Permission) -> bool {
+        self.roles.get(role).is_some_and(|permissions| permissions.contains(&permission))
+    }
+}
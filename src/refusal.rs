@@ -0,0 +1,49 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::request::Request;
+use rocket::response::{Responder, Response};
+use crate::errors::ApiError;
+
+/// Where refused requests are rerouted so no handler runs for them; nothing is mounted here.
+const REFUSED_PATH: &str = "/__refused";
+
+struct Refusal {
+    error: ApiError,
+    /// The path the client asked for, before the reroute.
+    path: String,
+}
+
+/// Refuses `request` from a request fairing, which cannot answer by itself:
+/// the request is routed nowhere and [`Refusals`] answers `error` instead.
+pub fn refuse(request: &mut Request<'_>, error: ApiError) {
+    let path = request.uri().path().to_string();
+    request.local_cache(|| Some(Refusal { error, path }));
+    request.set_uri(Origin::path_only(REFUSED_PATH));
+}
+
+/// The path the client asked for, even if the request was refused.
+pub fn original_path<'r>(request: &'r Request<'_>) -> &'r str {
+    match request.local_cache(|| None::<Refusal>) {
+        Some(refusal) => &refusal.path,
+        None => request.uri().path().as_str(),
+    }
+}
+
+/// Answers the requests refused with [`refuse`].
+pub struct Refusals;
+
+#[rocket::async_trait]
+impl Fairing for Refusals {
+    fn info(&self) -> Info {
+        Info { name: "Refusals", kind: Kind::Response }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(refusal) = request.local_cache(|| None::<Refusal>) else {
+            return;
+        };
+        if let Ok(answer) = refusal.error.clone().respond_to(request) {
+            *response = answer;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::{Arc, PoisonError, RwLock};
+#[cfg(unix)]
+use rocket::fairing::{Fairing, Info, Kind};
+#[cfg(unix)]
+use rocket::{Orbit, Rocket};
+use tracing_subscriber::filter::LevelFilter;
+use crate::flags::Flags;
+use crate::logging;
+use crate::rate_limit::RateLimit;
+use crate::secrets;
+use crate::settings::{self, Overrides, Settings};
+
+/// The settings that can change while serving, without a restart.
+pub struct Live {
+    pub greeting_text: String,
+    pub rate_limit: Option<RateLimit>,
+    pub log_level: LevelFilter,
+    pub flags: Flags,
+}
+
+impl Live {
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        for name in settings.flags.unknown() {
+            warn!("Unknown feature flag '{}'", name);
+        }
+        Ok(Live {
+            // Secrets are only resolved at startup, so a secret greeting stays as it was.
+            greeting_text: secrets::var("GREETING_TEXT").unwrap_or_else(|_| settings.greeting_text.clone()),
+            rate_limit: RateLimit::from_settings(settings),
+            log_level: match &settings.log.level {
+                Some(level) => logging::parse_level(level)?,
+                None => LevelFilter::INFO,
+            },
+            flags: settings.flags.clone(),
+        })
+    }
+}
+
+/// The [`Live`] settings in effect, swapped whole by [`Reloadable::reload`].
+pub struct Reloadable(RwLock<Arc<Live>>);
+
+impl Reloadable {
+    pub fn new(live: Live) -> Self {
+        Reloadable(RwLock::new(Arc::new(live)))
+    }
+
+    pub fn current(&self) -> Arc<Live> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Reads `Rocket.toml` and the environment again and applies their [`Live`] settings;
+    /// the others, such as the port or the store, wait for a restart. A bad configuration
+    /// changes nothing.
+    pub fn reload(&self) -> Result<Arc<Live>, String> {
+        let (_, settings) = settings::load(&Overrides::default())?;
+        let live = Arc::new(Live::from_settings(&settings)?);
+        logging::set_level(live.log_level)?;
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = live.clone();
+        info!("Reloaded the configuration");
+        Ok(live)
+    }
+}
+
+/// Reloads the [`Reloadable`] settings on every `SIGHUP`.
+#[cfg(unix)]
+pub struct ReloadOnHangup(pub Arc<Reloadable>);
+
+#[cfg(unix)]
+#[rocket::async_trait]
+impl Fairing for ReloadOnHangup {
+    fn info(&self) -> Info {
+        Info { name: "Reload on SIGHUP", kind: Kind::Liftoff }
+    }
+
+    async fn on_liftoff(&self, _: &Rocket<Orbit>) {
+        use rocket::tokio::signal::unix::{signal, SignalKind};
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+        let live = self.0.clone();
+        rocket::tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                if let Err(e) = live.reload() {
+                    error!("Failed to reload the configuration: {}", e);
+                }
+            }
+        });
+    }
+}
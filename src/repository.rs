@@ -0,0 +1,243 @@
+mod memory;
+mod wal;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "mongodb")]
+mod mongo;
+#[cfg(feature = "sled")]
+mod sled;
+
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use rocket::async_trait;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::person::Person;
+
+pub use memory::InMemoryRepository;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    NotFound,
+    Conflict,
+    /// Another person, with this id, already has the name; see [`UniqueNames`](crate::unique::UniqueNames).
+    NameTaken(u32),
+    Unavailable(String),
+    Internal(String),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryError::NotFound => write!(f, "not found"),
+            RepositoryError::Conflict => write!(f, "conflict"),
+            RepositoryError::NameTaken(id) => write!(f, "name already used by person {}", id),
+            RepositoryError::Unavailable(message) => write!(f, "storage unavailable: {}", message),
+            RepositoryError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(any(feature = "sqlite", feature = "postgres"), derive(sqlx::FromRow))]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub success: bool,
+}
+
+/// The outcome of checking one part of a store, such as its `database` connection or
+/// the `disk` it writes to; see [`PersonRepository::check`].
+pub struct ComponentCheck {
+    pub component: &'static str,
+    pub result: Result<(), RepositoryError>,
+}
+
+impl ComponentCheck {
+    pub fn new(component: &'static str, result: Result<(), RepositoryError>) -> Self {
+        ComponentCheck { component, result }
+    }
+}
+
+/// How long callers have waited for one kind of lock on a store.
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct LockWaits {
+    pub acquired: u64,
+    pub total_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+/// Lock waits of a store that serializes access itself; see [`PersonRepository::lock_waits`].
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct LockStats {
+    pub read: LockWaits,
+    pub write: LockWaits,
+}
+
+/// Every person lives in a tenant's partition; single-tenant deployments use
+/// [`DEFAULT_TENANT`](crate::tenant::DEFAULT_TENANT) throughout.
+#[async_trait]
+pub trait PersonRepository: Send + Sync {
+    /// Schema migrations applied to the backing store; empty for stores without a schema.
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        Ok(Vec::new())
+    }
+
+    /// Checks that the store can serve reads and writes right now, for `/health/ready`.
+    async fn check(&self) -> Vec<ComponentCheck>;
+
+    /// Waits on the store's own lock, for `/admin/stats`; `None` for stores a database
+    /// serializes, whose waits happen out of sight.
+    fn lock_waits(&self) -> Option<LockStats> {
+        None
+    }
+
+    /// Writes out anything still pending and lets go of the store's connections, once the
+    /// server has stopped taking requests; nothing calls the store afterwards.
+    async fn close(&self) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+
+    /// Tenants that currently hold at least one person.
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError>;
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError>;
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError>;
+    /// Looks a person up by its server-assigned UUID; stores without an index for it scan the tenant.
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        Ok(self.list(tenant).await?.into_iter().find(|p| p.uuid == Some(uuid)))
+    }
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError>;
+    /// Inserts all of `persons` or none of them, failing with `Conflict` if any id is taken.
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError>;
+    /// Replaces a stored person. `person.version` must be exactly one past the
+    /// stored version, otherwise someone else got there first and this fails with `Conflict`.
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError>;
+    /// Updates all of `persons` or none of them, failing with `NotFound` if any id is
+    /// missing and `Conflict` if any version is stale (see [`update`](Self::update)).
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError>;
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError>;
+    /// Deletes whichever of `ids` exist in one atomic write and returns those ids.
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError>;
+    /// Atomically swaps the tenant's whole collection for `persons`.
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError>;
+}
+
+/// Opens the `backend` store, configured by its own variables such as `SQLITE_PATH`.
+pub async fn from_env(backend: &str, seed: Vec<Person>) -> Result<Arc<dyn PersonRepository>, RepositoryError> {
+    match backend {
+        "memory" => {
+            let persist_path = env::var("PERSIST_PATH").ok().map(Into::into);
+            let wal_path = env::var("WAL_PATH").ok().map(Into::into);
+            Ok(Arc::new(InMemoryRepository::open(seed, persist_path, wal_path)?))
+        }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "persons.db".to_string());
+            Ok(Arc::new(sqlite::SqliteRepository::connect(&path).await?))
+        }
+        #[cfg(feature = "postgres")]
+        "postgres" => {
+            let url = crate::secrets::var("DATABASE_URL")
+                .map_err(|_| RepositoryError::Internal("DATABASE_URL must be set for the postgres backend".to_string()))?;
+            let max_connections = env::var("DATABASE_MAX_CONNECTIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10);
+            Ok(Arc::new(postgres::PostgresRepository::connect(&url, max_connections).await?))
+        }
+        #[cfg(feature = "redis")]
+        "redis" => {
+            let url = crate::secrets::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+            let prefix = env::var("REDIS_KEY_PREFIX").unwrap_or_default();
+            Ok(Arc::new(redis::RedisRepository::connect(&url, prefix).await?))
+        }
+        #[cfg(feature = "mongodb")]
+        "mongodb" => {
+            let uri = crate::secrets::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://127.0.0.1:27017".to_string());
+            let database = env::var("MONGODB_DATABASE").unwrap_or_else(|_| "persons".to_string());
+            Ok(Arc::new(mongo::MongoRepository::connect(&uri, &database).await?))
+        }
+        #[cfg(feature = "sled")]
+        "sled" => {
+            let path = env::var("SLED_PATH").unwrap_or_else(|_| "persons.sled".to_string());
+            Ok(Arc::new(sled::SledRepository::open(&path)?))
+        }
+        other => Err(RepositoryError::Internal(format!("unsupported storage backend '{}' (STORAGE_BACKEND)", other))),
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn map_sqlx_error(error: sqlx::Error) -> RepositoryError {
+    match error {
+        sqlx::Error::Database(ref e) if e.is_unique_violation() => RepositoryError::Conflict,
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+            RepositoryError::Unavailable(error.to_string())
+        }
+        e => RepositoryError::Internal(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::person::create_person_collection;
+
+    const TENANT: &str = "test";
+
+    fn ids(persons: &[Person]) -> Vec<u32> {
+        persons.iter().map(|p| p.id).collect()
+    }
+
+    /// The behaviour every store shares, run against each of them below.
+    async fn crud(repository: &dyn PersonRepository) {
+        let persons = create_person_collection();
+        let mut person = persons[0].clone();
+
+        repository.insert(TENANT, person.clone()).await.unwrap();
+        assert!(matches!(repository.insert(TENANT, person.clone()).await, Err(RepositoryError::Conflict)));
+        assert_eq!(repository.get(TENANT, person.id).await.unwrap().map(|p| p.name), Some(person.name.clone()));
+
+        // Only the version right after the stored one is accepted.
+        person.name = "Renamed".to_string();
+        assert!(matches!(repository.update(TENANT, person.clone()).await, Err(RepositoryError::Conflict)));
+        person.version += 1;
+        repository.update(TENANT, person.clone()).await.unwrap();
+        assert!(matches!(repository.update(TENANT, person.clone()).await, Err(RepositoryError::Conflict)));
+        let stored = repository.get(TENANT, person.id).await.unwrap().unwrap();
+        assert_eq!((stored.name.as_str(), stored.version), ("Renamed", person.version));
+        let missing = Person { id: 999, ..person.clone() };
+        assert!(matches!(repository.update(TENANT, missing).await, Err(RepositoryError::NotFound)));
+
+        repository.delete(TENANT, person.id).await.unwrap();
+        assert!(matches!(repository.delete(TENANT, person.id).await, Err(RepositoryError::NotFound)));
+        assert!(repository.get(TENANT, person.id).await.unwrap().is_none());
+
+        repository.insert(TENANT, persons[1].clone()).await.unwrap();
+        repository.replace_all(TENANT, persons.clone()).await.unwrap();
+        assert_eq!(ids(&repository.list(TENANT).await.unwrap()), ids(&persons));
+        assert_eq!(repository.tenants().await.unwrap(), [TENANT]);
+        repository.replace_all(TENANT, Vec::new()).await.unwrap();
+        assert!(repository.list(TENANT).await.unwrap().is_empty());
+        assert!(repository.tenants().await.unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn memory_store() {
+        crud(&InMemoryRepository::open(Vec::new(), None, None).unwrap()).await;
+    }
+
+    #[cfg(feature = "sled")]
+    #[rocket::async_test]
+    async fn sled_store() {
+        let path = env::temp_dir().join(format!("rocket-app-test-{}.sled", Uuid::new_v4()));
+        crud(&sled::SledRepository::open(path.to_str().unwrap()).unwrap()).await;
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use rocket::async_trait;
+use rocket::tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use rocket::tokio::{task, time};
+use serde::Deserialize;
+use crate::person::Person;
+use crate::tenant::DEFAULT_TENANT;
+use super::wal::{Appended, Operation, WriteAheadLog};
+use super::{ComponentCheck, LockStats, LockWaits, PersonRepository, RepositoryError};
+
+/// How long a health check waits for the collection before calling it stuck.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The least time between two snapshots; the changes made meanwhile go into the next one.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Persons are indexed by id so lookups, updates and conflict checks don't scan the collection.
+type Tenants = HashMap<String, HashMap<u32, Person>>;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Snapshot {
+    Tenants(HashMap<String, Vec<Person>>),
+    // Snapshots written before tenants existed hold a bare list.
+    Legacy(Vec<Person>),
+}
+
+pub struct InMemoryRepository {
+    persons: Arc<RwLock<Tenants>>,
+    wal: Option<Arc<WriteAheadLog>>,
+    snapshots: Option<Snapshots>,
+    waits: Mutex<LockStats>,
+}
+
+/// Writes the snapshot on its own thread, so a mutation only signals a change instead of
+/// rewriting the whole file, with blocking calls, under the write lock.
+struct Snapshots {
+    path: PathBuf,
+    changes: Sender<()>,
+    // Keeps `close` and the thread from writing the same file at once.
+    writing: Arc<Mutex<()>>,
+}
+
+fn record(waits: &mut LockWaits, started: Instant) {
+    let waited_ms = started.elapsed().as_secs_f64() * 1000.0;
+    waits.acquired += 1;
+    waits.total_wait_ms += waited_ms;
+    waits.max_wait_ms = waits.max_wait_ms.max(waited_ms);
+}
+
+impl InMemoryRepository {
+    /// Loads the collection from `persist_path` if it exists (falling back to `seed`
+    /// in the default tenant) and replays `wal_path` on top of it. The log holds every
+    /// mutation before it returns; the snapshot is rewritten at most every
+    /// [`SNAPSHOT_INTERVAL`], and the log emptied of whatever each snapshot holds.
+    pub fn open(seed: Vec<Person>, persist_path: Option<PathBuf>, wal_path: Option<PathBuf>) -> Result<Self, RepositoryError> {
+        let snapshot = match &persist_path {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| RepositoryError::Internal(format!("failed to read {}: {}", path.display(), e)))?;
+                serde_json::from_str(&contents)
+                    .map_err(|e| RepositoryError::Internal(format!("failed to parse {}: {}", path.display(), e)))?
+            }
+            _ => Snapshot::Legacy(seed),
+        };
+        let mut persons: Tenants = match snapshot {
+            Snapshot::Tenants(tenants) => tenants.into_iter()
+                .map(|(tenant, persons)| (tenant, index_by_id(persons)))
+                .collect(),
+            Snapshot::Legacy(persons) => HashMap::from([(DEFAULT_TENANT.to_string(), index_by_id(persons))]),
+        };
+
+        let wal = match wal_path {
+            Some(path) => {
+                let (wal, entries) = WriteAheadLog::open(&path)?;
+                for entry in entries {
+                    entry.replay(&mut persons);
+                }
+                Some(Arc::new(wal))
+            }
+            None => None,
+        };
+
+        if let Some(path) = &persist_path {
+            write_snapshot(path, &persons)?;
+            // The snapshot now holds every replayed entry; keep the log from growing forever.
+            if let Some(wal) = &wal {
+                wal.truncate()?;
+            }
+        }
+        let persons = Arc::new(RwLock::new(persons));
+        let snapshots = persist_path.map(|path| {
+            let (changes, received) = mpsc::channel();
+            let snapshots = Snapshots { path, changes, writing: Arc::default() };
+            let checkpoint = Checkpoint { persons: persons.clone(), path: snapshots.path.clone(), wal: wal.clone(), writing: snapshots.writing.clone() };
+            thread::spawn(move || checkpoint.run(received));
+            snapshots
+        });
+        Ok(InMemoryRepository { persons, wal, snapshots, waits: Mutex::default() })
+    }
+
+    async fn read(&self) -> RwLockReadGuard<'_, Tenants> {
+        let started = Instant::now();
+        let persons = self.persons.read().await;
+        record(&mut self.waits.lock().unwrap_or_else(PoisonError::into_inner).read, started);
+        persons
+    }
+
+    async fn write(&self) -> RwLockWriteGuard<'_, Tenants> {
+        let started = Instant::now();
+        let persons = self.persons.write().await;
+        record(&mut self.waits.lock().unwrap_or_else(PoisonError::into_inner).write, started);
+        persons
+    }
+
+    /// Queues `operation` in the log, if any, before it is applied under the same lock.
+    fn log(&self, tenant: &str, operation: Operation) -> Result<Option<Appended>, RepositoryError> {
+        self.wal.as_ref().map(|wal| wal.append(tenant, operation)).transpose()
+    }
+
+    /// Tells the snapshot thread the collection changed.
+    fn changed(&self) {
+        if let Some(snapshots) = &self.snapshots {
+            // The thread only stops once this repository is dropped.
+            let _ = snapshots.changes.send(());
+        }
+    }
+}
+
+/// What the snapshot thread needs to write the collection and empty the log of it.
+struct Checkpoint {
+    persons: Arc<RwLock<Tenants>>,
+    path: PathBuf,
+    wal: Option<Arc<WriteAheadLog>>,
+    writing: Arc<Mutex<()>>,
+}
+
+impl Checkpoint {
+    fn run(self, changes: Receiver<()>) {
+        while changes.recv().is_ok() {
+            while changes.try_recv().is_ok() {}
+            if let Err(e) = self.write() {
+                error!("Failed to write the snapshot: {}", e);
+            }
+            thread::sleep(SNAPSHOT_INTERVAL);
+        }
+    }
+
+    /// Serializes the collection under the read lock, which also keeps the log still
+    /// while it is set aside, then writes the file outside it.
+    fn write(&self) -> Result<(), RepositoryError> {
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        let contents = {
+            let persons = self.persons.blocking_read();
+            let contents = serialize(&persons)?;
+            if let Some(wal) = &self.wal {
+                wal.rotate()?;
+            }
+            contents
+        };
+        write_file(&self.path, &contents)?;
+        match &self.wal {
+            Some(wal) => wal.drop_rotated(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write_snapshot(path: &Path, persons: &Tenants) -> Result<(), RepositoryError> {
+    write_file(path, &serialize(persons)?)
+}
+
+fn serialize(persons: &Tenants) -> Result<String, RepositoryError> {
+    let snapshot: HashMap<&String, Vec<&Person>> = persons.iter()
+        .map(|(tenant, persons)| (tenant, sorted_by_id(persons.values())))
+        .collect();
+    serde_json::to_string_pretty(&snapshot).map_err(|e| RepositoryError::Internal(e.to_string()))
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), RepositoryError> {
+    // Write to a sibling file first so a crash never leaves a truncated snapshot behind,
+    // and sync it so the log can be emptied once it is renamed.
+    let tmp_path = path.with_extension("tmp");
+    File::create(&tmp_path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|e| RepositoryError::Internal(format!("failed to write {}: {}", path.display(), e)))
+}
+
+/// Creates and removes a file next to `path`, to find out whether the next write there can succeed.
+fn probe_writable(path: &Path) -> Result<(), RepositoryError> {
+    let probe = path.with_extension("health");
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| RepositoryError::Unavailable(format!("{} is not writable: {}", probe.display(), e)))
+}
+
+/// Waits, once the lock is let go, until the log holds a mutation already applied. If that
+/// fails the mutation is reported as failed, though it stays in memory until a restart.
+async fn synced(appended: Option<Appended>) -> Result<(), RepositoryError> {
+    match appended {
+        Some(appended) => appended.synced().await,
+        None => Ok(()),
+    }
+}
+
+fn index_by_id(persons: Vec<Person>) -> HashMap<u32, Person> {
+    persons.into_iter().map(|p| (p.id, p)).collect()
+}
+
+fn sorted_by_id<'a>(persons: impl Iterator<Item = &'a Person>) -> Vec<&'a Person> {
+    let mut persons: Vec<&Person> = persons.collect();
+    persons.sort_by_key(|p| p.id);
+    persons
+}
+
+#[async_trait]
+impl PersonRepository for InMemoryRepository {
+    async fn check(&self) -> Vec<ComponentCheck> {
+        let lock = match time::timeout(LOCK_TIMEOUT, self.persons.read()).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(RepositoryError::Unavailable(format!("the collection stayed locked for {:?}", LOCK_TIMEOUT))),
+        };
+        let mut checks = vec![ComponentCheck::new("lock", lock)];
+        let files: Vec<&Path> = self.snapshots.iter().map(|s| s.path.as_path()).chain(self.wal.as_deref().map(WriteAheadLog::path)).collect();
+        if !files.is_empty() {
+            checks.push(ComponentCheck::new("disk", files.into_iter().try_for_each(probe_writable)));
+        }
+        checks
+    }
+
+    fn lock_waits(&self) -> Option<LockStats> {
+        Some(*self.waits.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        // Every mutation is already in the log, if any; the snapshot may lag behind.
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint { persons: self.persons.clone(), path: snapshots.path.clone(), wal: self.wal.clone(), writing: snapshots.writing.clone() };
+        task::spawn_blocking(move || checkpoint.write()).await
+            .map_err(|e| RepositoryError::Internal(format!("the final snapshot failed: {}", e)))?
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        let persons = self.read().await;
+        let mut tenants: Vec<String> = persons.iter()
+            .filter(|(_, persons)| !persons.is_empty())
+            .map(|(tenant, _)| tenant.clone())
+            .collect();
+        tenants.sort();
+        Ok(tenants)
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        let persons = self.read().await;
+        Ok(persons.get(tenant)
+            .map(|persons| sorted_by_id(persons.values()).into_iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        let persons = self.read().await;
+        Ok(persons.get(tenant).and_then(|persons| persons.get(&id)).cloned())
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let partition = persons.entry(tenant.to_string()).or_default();
+        if partition.contains_key(&person.id) {
+            return Err(RepositoryError::Conflict);
+        }
+        let appended = self.log(tenant, Operation::Create { person: person.clone() })?;
+        partition.insert(person.id, person);
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+
+    async fn insert_many(&self, tenant: &str, created: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let partition = persons.entry(tenant.to_string()).or_default();
+        let mut ids = HashSet::new();
+        if created.iter().any(|p| partition.contains_key(&p.id) || !ids.insert(p.id)) {
+            return Err(RepositoryError::Conflict);
+        }
+        let appended = self.log(tenant, Operation::CreateMany { persons: created.clone() })?;
+        partition.extend(created.into_iter().map(|p| (p.id, p)));
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let existing = persons.get_mut(tenant).and_then(|persons| persons.get_mut(&person.id));
+        let appended = match existing {
+            Some(p) if p.version + 1 != person.version => return Err(RepositoryError::Conflict),
+            Some(p) => {
+                let appended = self.log(tenant, Operation::Update { person: person.clone() })?;
+                *p = person;
+                appended
+            }
+            None => return Err(RepositoryError::NotFound),
+        };
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+
+    async fn update_many(&self, tenant: &str, updated: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let partition = persons.get_mut(tenant).ok_or(RepositoryError::NotFound)?;
+        for person in &updated {
+            match partition.get(&person.id) {
+                Some(p) if p.version + 1 == person.version => {}
+                Some(_) => return Err(RepositoryError::Conflict),
+                None => return Err(RepositoryError::NotFound),
+            }
+        }
+        let appended = self.log(tenant, Operation::UpdateMany { persons: updated.clone() })?;
+        partition.extend(updated.into_iter().map(|p| (p.id, p)));
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let partition = persons.get_mut(tenant).ok_or(RepositoryError::NotFound)?;
+        if !partition.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let appended = self.log(tenant, Operation::Delete { id })?;
+        partition.remove(&id);
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let mut persons = self.write().await;
+        let Some(partition) = persons.get_mut(tenant) else {
+            return Ok(Vec::new());
+        };
+        let mut deleted: Vec<u32> = ids.into_iter().filter(|id| partition.contains_key(id)).collect();
+        deleted.sort();
+        deleted.dedup();
+        if deleted.is_empty() {
+            return Ok(deleted);
+        }
+        let appended = self.log(tenant, Operation::DeleteMany { ids: deleted.clone() })?;
+        for id in &deleted {
+            partition.remove(id);
+        }
+        self.changed();
+        drop(persons);
+        synced(appended).await?;
+        Ok(deleted)
+    }
+
+    async fn replace_all(&self, tenant: &str, replacement: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut persons = self.write().await;
+        let appended = self.log(tenant, Operation::Replace { persons: replacement.clone() })?;
+        persons.insert(tenant.to_string(), index_by_id(replacement));
+        self.changed();
+        drop(persons);
+        synced(appended).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use uuid::Uuid;
+    use super::*;
+    use crate::person::create_person_collection;
+
+    #[rocket::async_test]
+    async fn open_empties_the_replayed_log() {
+        let dir = env::temp_dir().join(format!("rocket-app-test-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let (snapshot, wal) = (dir.join("persons.json"), dir.join("persons.wal"));
+        let person = create_person_collection().remove(0);
+        let id = person.id;
+
+        let repository = InMemoryRepository::open(Vec::new(), None, Some(wal.clone())).unwrap();
+        repository.insert(DEFAULT_TENANT, person).await.unwrap();
+        drop(repository);
+        assert_ne!(fs::metadata(&wal).unwrap().len(), 0);
+
+        let repository = InMemoryRepository::open(Vec::new(), Some(snapshot.clone()), Some(wal.clone())).unwrap();
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        assert!(repository.get(DEFAULT_TENANT, id).await.unwrap().is_some());
+        drop(repository);
+
+        let repository = InMemoryRepository::open(Vec::new(), Some(snapshot), Some(wal)).unwrap();
+        assert!(repository.get(DEFAULT_TENANT, id).await.unwrap().is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[rocket::async_test]
+    async fn close_snapshots_every_change_and_empties_the_log() {
+        let dir = env::temp_dir().join(format!("rocket-app-test-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let (snapshot, wal) = (dir.join("persons.json"), dir.join("persons.wal"));
+        let persons = create_person_collection();
+
+        let repository = InMemoryRepository::open(Vec::new(), Some(snapshot.clone()), Some(wal.clone())).unwrap();
+        for person in &persons {
+            repository.insert(DEFAULT_TENANT, person.clone()).await.unwrap();
+        }
+        repository.close().await.unwrap();
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        drop(repository);
+
+        let repository = InMemoryRepository::open(Vec::new(), Some(snapshot), None).unwrap();
+        assert_eq!(repository.list(DEFAULT_TENANT).await.unwrap().len(), persons.len());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,253 @@
+use std::time::Duration;
+use mongodb::bson::{doc, Bson};
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+use mongodb::options::{ClientOptions, IndexOptions};
+use mongodb::{Client, Collection, IndexModel};
+use rocket::async_trait;
+use rocket::futures::TryStreamExt;
+use rocket::tokio::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use crate::person::{Person, INITIAL_VERSION};
+use super::{ComponentCheck, PersonRepository, RepositoryError};
+
+const DUPLICATE_KEY: i32 = 11000;
+
+#[derive(Serialize, Deserialize)]
+struct PersonDocument {
+    tenant: String,
+    #[serde(flatten)]
+    person: Person,
+}
+
+pub struct MongoRepository {
+    client: Client,
+    persons: Collection<PersonDocument>,
+    index: OnceCell<()>,
+}
+
+impl MongoRepository {
+    /// Builds the client without contacting the server, so an unreachable
+    /// MongoDB surfaces as 503s from the API rather than a failed launch.
+    pub async fn connect(uri: &str, database: &str) -> Result<Self, RepositoryError> {
+        let mut options = ClientOptions::parse(uri).await.map_err(map_mongo_error)?;
+        options.server_selection_timeout = Some(Duration::from_secs(5));
+        let client = Client::with_options(options).map_err(map_mongo_error)?;
+        let persons = client.database(database).collection("persons");
+        Ok(MongoRepository { client, persons, index: OnceCell::new() })
+    }
+
+    // The unique index is created on first use and retried until it succeeds.
+    async fn collection(&self) -> Result<&Collection<PersonDocument>, RepositoryError> {
+        self.index.get_or_try_init(|| async {
+            let index = IndexModel::builder()
+                .keys(doc! { "tenant": 1, "id": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build();
+            self.persons.create_index(index).await.map(|_| ()).map_err(map_mongo_error)
+        }).await?;
+        Ok(&self.persons)
+    }
+}
+
+fn map_mongo_error(error: Error) -> RepositoryError {
+    match *error.kind {
+        ErrorKind::Write(WriteFailure::WriteError(ref e)) if e.code == DUPLICATE_KEY => RepositoryError::Conflict,
+        ErrorKind::InsertMany(ref e) if e.write_errors.iter().flatten().any(|e| e.code == DUPLICATE_KEY) => {
+            RepositoryError::Conflict
+        }
+        ErrorKind::ServerSelection { .. } | ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } => {
+            RepositoryError::Unavailable(error.to_string())
+        }
+        _ if error.contains_label("TransientTransactionError") => RepositoryError::Unavailable(error.to_string()),
+        _ => RepositoryError::Internal(error.to_string()),
+    }
+}
+
+fn id_filter(tenant: &str, id: u32) -> mongodb::bson::Document {
+    doc! { "tenant": tenant, "id": i64::from(id) }
+}
+
+/// Matches the person only while it is still at `version`. Documents stored
+/// before versioning existed have no version field and count as the initial one.
+fn version_filter(tenant: &str, id: u32, version: u32) -> mongodb::bson::Document {
+    let mut filter = id_filter(tenant, id);
+    match version {
+        INITIAL_VERSION => {
+            filter.insert("$or", vec![doc! { "version": i64::from(version) }, doc! { "version": { "$exists": false } }]);
+        }
+        version => {
+            filter.insert("version", i64::from(version));
+        }
+    }
+    filter
+}
+
+#[async_trait]
+impl PersonRepository for MongoRepository {
+    async fn check(&self) -> Vec<ComponentCheck> {
+        let ping = self.client.database("admin").run_command(doc! { "ping": 1 }).await.map(|_| ()).map_err(map_mongo_error);
+        vec![ComponentCheck::new("mongodb", ping)]
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.client.clone().shutdown().await;
+        Ok(())
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        let values = self.collection().await?
+            .distinct("tenant", doc! {})
+            .await
+            .map_err(map_mongo_error)?;
+        let mut tenants: Vec<String> = values.into_iter()
+            .filter_map(|value| match value {
+                Bson::String(tenant) => Some(tenant),
+                _ => None,
+            })
+            .collect();
+        tenants.sort();
+        Ok(tenants)
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        let documents: Vec<PersonDocument> = self.collection().await?
+            .find(doc! { "tenant": tenant })
+            .sort(doc! { "id": 1 })
+            .await
+            .map_err(map_mongo_error)?
+            .try_collect()
+            .await
+            .map_err(map_mongo_error)?;
+        Ok(documents.into_iter().map(|d| d.person).collect())
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        let document = self.collection().await?
+            .find_one(id_filter(tenant, id))
+            .await
+            .map_err(map_mongo_error)?;
+        Ok(document.map(|d| d.person))
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.collection().await?
+            .insert_one(PersonDocument { tenant: tenant.to_string(), person })
+            .await
+            .map_err(map_mongo_error)?;
+        Ok(())
+    }
+
+    /// Runs inside a multi-document transaction, which requires a replica set.
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        if persons.is_empty() {
+            return Ok(());
+        }
+        let collection = self.collection().await?;
+        let mut session = self.client.start_session().await.map_err(map_mongo_error)?;
+        session.start_transaction().await.map_err(map_mongo_error)?;
+        let documents = persons.into_iter()
+            .map(|person| PersonDocument { tenant: tenant.to_string(), person });
+        collection.insert_many(documents)
+            .session(&mut session)
+            .await
+            .map_err(map_mongo_error)?;
+        session.commit_transaction().await.map_err(map_mongo_error)
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let id = person.id;
+        let filter = version_filter(tenant, id, person.version.saturating_sub(1));
+        let collection = self.collection().await?;
+        let result = collection.replace_one(filter, PersonDocument { tenant: tenant.to_string(), person })
+            .await
+            .map_err(map_mongo_error)?;
+        if result.matched_count > 0 {
+            return Ok(());
+        }
+        match collection.find_one(id_filter(tenant, id)).await.map_err(map_mongo_error)? {
+            Some(_) => Err(RepositoryError::Conflict),
+            None => Err(RepositoryError::NotFound),
+        }
+    }
+
+    /// Runs inside a multi-document transaction, which requires a replica set.
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let collection = self.collection().await?;
+        let mut session = self.client.start_session().await.map_err(map_mongo_error)?;
+        session.start_transaction().await.map_err(map_mongo_error)?;
+        for person in persons {
+            let id = person.id;
+            let filter = version_filter(tenant, id, person.version.saturating_sub(1));
+            let result = collection.replace_one(filter, PersonDocument { tenant: tenant.to_string(), person })
+                .session(&mut session)
+                .await
+                .map_err(map_mongo_error)?;
+            // Dropping the session aborts the transaction.
+            if result.matched_count == 0 {
+                let existing = collection.find_one(id_filter(tenant, id))
+                    .session(&mut session)
+                    .await
+                    .map_err(map_mongo_error)?;
+                return Err(match existing {
+                    Some(_) => RepositoryError::Conflict,
+                    None => RepositoryError::NotFound,
+                });
+            }
+        }
+        session.commit_transaction().await.map_err(map_mongo_error)
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let result = self.collection().await?
+            .delete_one(id_filter(tenant, id))
+            .await
+            .map_err(map_mongo_error)?;
+        match result.deleted_count {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs inside a multi-document transaction, which requires a replica set.
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let collection = self.collection().await?;
+        let ids: Vec<i64> = ids.into_iter().map(i64::from).collect();
+        let filter = doc! { "tenant": tenant, "id": { "$in": ids } };
+        let mut session = self.client.start_session().await.map_err(map_mongo_error)?;
+        session.start_transaction().await.map_err(map_mongo_error)?;
+        let documents: Vec<PersonDocument> = collection.find(filter.clone())
+            .session(&mut session)
+            .await
+            .map_err(map_mongo_error)?
+            .stream(&mut session)
+            .try_collect()
+            .await
+            .map_err(map_mongo_error)?;
+        collection.delete_many(filter)
+            .session(&mut session)
+            .await
+            .map_err(map_mongo_error)?;
+        session.commit_transaction().await.map_err(map_mongo_error)?;
+        Ok(documents.into_iter().map(|d| d.person.id).collect())
+    }
+
+    /// Runs inside a multi-document transaction, which requires a replica set.
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let collection = self.collection().await?;
+        let mut session = self.client.start_session().await.map_err(map_mongo_error)?;
+        session.start_transaction().await.map_err(map_mongo_error)?;
+        collection.delete_many(doc! { "tenant": tenant })
+            .session(&mut session)
+            .await
+            .map_err(map_mongo_error)?;
+        if !persons.is_empty() {
+            let documents = persons.into_iter()
+                .map(|person| PersonDocument { tenant: tenant.to_string(), person });
+            collection.insert_many(documents)
+                .session(&mut session)
+                .await
+                .map_err(map_mongo_error)?;
+        }
+        session.commit_transaction().await.map_err(map_mongo_error)
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+use rocket::async_trait;
+use crate::dates;
+use crate::person::{Person, INITIAL_VERSION};
+use crate::tenant::DEFAULT_TENANT;
+use super::{ComponentCheck, PersonRepository, RepositoryError};
+
+const INSERT_SCRIPT: &str = r"
+if redis.call('SADD', KEYS[1], ARGV[1]) == 0 then return 0 end
+for field, value in pairs(cjson.decode(ARGV[2])) do
+    redis.call('HSET', KEYS[2], field, value)
+end
+redis.call('SADD', KEYS[3], ARGV[3])
+return 1
+";
+
+const INSERT_MANY_SCRIPT: &str = r"
+local seen = {}
+for i = 3, #ARGV, 2 do
+    if seen[ARGV[i]] or redis.call('SISMEMBER', KEYS[1], ARGV[i]) == 1 then return 0 end
+    seen[ARGV[i]] = true
+end
+for i = 3, #ARGV, 2 do
+    redis.call('SADD', KEYS[1], ARGV[i])
+    for field, value in pairs(cjson.decode(ARGV[i + 1])) do
+        redis.call('HSET', ARGV[1] .. ARGV[i], field, value)
+    end
+end
+if #ARGV > 2 then redis.call('SADD', KEYS[2], ARGV[2]) end
+return 1
+";
+
+// Hashes written before versioning existed have no version field and count as version 1.
+const UPDATE_SCRIPT: &str = r"
+if redis.call('SISMEMBER', KEYS[1], ARGV[1]) == 0 then return 0 end
+local current = tonumber(redis.call('HGET', KEYS[2], 'version') or '1')
+if current + 1 ~= tonumber(cjson.decode(ARGV[2]).version) then return -1 end
+redis.call('DEL', KEYS[2])
+for field, value in pairs(cjson.decode(ARGV[2])) do
+    redis.call('HSET', KEYS[2], field, value)
+end
+return 1
+";
+
+const UPDATE_MANY_SCRIPT: &str = r"
+for i = 2, #ARGV, 2 do
+    if redis.call('SISMEMBER', KEYS[1], ARGV[i]) == 0 then return 0 end
+    local current = tonumber(redis.call('HGET', ARGV[1] .. ARGV[i], 'version') or '1')
+    if current + 1 ~= tonumber(cjson.decode(ARGV[i + 1]).version) then return -1 end
+end
+for i = 2, #ARGV, 2 do
+    redis.call('DEL', ARGV[1] .. ARGV[i])
+    for field, value in pairs(cjson.decode(ARGV[i + 1])) do
+        redis.call('HSET', ARGV[1] .. ARGV[i], field, value)
+    end
+end
+return 1
+";
+
+const DELETE_SCRIPT: &str = r"
+if redis.call('SREM', KEYS[1], ARGV[1]) == 0 then return 0 end
+redis.call('DEL', KEYS[2])
+if redis.call('SCARD', KEYS[1]) == 0 then redis.call('SREM', KEYS[3], ARGV[2]) end
+return 1
+";
+
+const DELETE_MANY_SCRIPT: &str = r"
+local deleted = {}
+for i = 3, #ARGV do
+    if redis.call('SREM', KEYS[1], ARGV[i]) == 1 then
+        redis.call('DEL', ARGV[1] .. ARGV[i])
+        table.insert(deleted, ARGV[i])
+    end
+end
+if redis.call('SCARD', KEYS[1]) == 0 then redis.call('SREM', KEYS[2], ARGV[2]) end
+return deleted
+";
+
+const REPLACE_SCRIPT: &str = r"
+for _, id in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    redis.call('DEL', ARGV[1] .. id)
+end
+redis.call('DEL', KEYS[1])
+for i = 3, #ARGV, 2 do
+    redis.call('SADD', KEYS[1], ARGV[i])
+    for field, value in pairs(cjson.decode(ARGV[i + 1])) do
+        redis.call('HSET', ARGV[1] .. ARGV[i], field, value)
+    end
+end
+if #ARGV > 2 then redis.call('SADD', KEYS[2], ARGV[2]) else redis.call('SREM', KEYS[2], ARGV[2]) end
+return 1
+";
+
+/// Stores each person as a hash at `<keyspace>person:<id>` and tracks ids in the
+/// `<keyspace>persons` set, where the keyspace is `<prefix>tenant:<tenant>:` (or just
+/// `<prefix>` for the default tenant). Mutations run as Lua scripts so they stay
+/// atomic across replicas.
+pub struct RedisRepository {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisRepository {
+    pub async fn connect(url: &str, prefix: String) -> Result<Self, RepositoryError> {
+        let client = redis::Client::open(url).map_err(map_redis_error)?;
+        let connection = ConnectionManager::new(client).await.map_err(map_redis_error)?;
+        Ok(RedisRepository { connection, prefix })
+    }
+
+    fn keyspace(&self, tenant: &str) -> String {
+        match tenant {
+            DEFAULT_TENANT => self.prefix.clone(),
+            tenant => format!("{}tenant:{}:", self.prefix, tenant),
+        }
+    }
+
+    fn tenants_key(&self) -> String {
+        format!("{}tenants", self.prefix)
+    }
+
+    fn ids_key(&self, tenant: &str) -> String {
+        format!("{}persons", self.keyspace(tenant))
+    }
+
+    fn person_prefix(&self, tenant: &str) -> String {
+        format!("{}person:", self.keyspace(tenant))
+    }
+
+    fn person_key(&self, tenant: &str, id: u32) -> String {
+        format!("{}{}", self.person_prefix(tenant), id)
+    }
+
+    async fn run_person_script(&self, source: &str, tenant: &str, person: Person) -> Result<i32, RepositoryError> {
+        let mut connection = self.connection.clone();
+        let key = self.person_key(tenant, person.id);
+        Script::new(source)
+            .key(self.ids_key(tenant))
+            .key(key)
+            .key(self.tenants_key())
+            .arg(&person_args(person)?)
+            .arg(tenant)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(map_redis_error)
+    }
+}
+
+fn map_redis_error(error: redis::RedisError) -> RepositoryError {
+    if error.is_io_error() || error.is_connection_refusal() || error.is_timeout() || error.is_connection_dropped() {
+        RepositoryError::Unavailable(error.to_string())
+    } else {
+        RepositoryError::Internal(error.to_string())
+    }
+}
+
+// Scripts receive the id plus the hash fields as a JSON object, so they don't
+// need to change when `Person` gains a field.
+fn person_args(person: Person) -> Result<[String; 2], RepositoryError> {
+    let mut fields = HashMap::new();
+    fields.insert("id", person.id.to_string());
+    fields.insert("name", person.name);
+    fields.insert("age", person.age.to_string());
+    fields.insert("date", person.date.to_rfc3339());
+    if let Some(deleted_at) = person.deleted_at {
+        fields.insert("deleted_at", deleted_at.to_rfc3339());
+    }
+    if let Some(expires_at) = person.expires_at {
+        fields.insert("expires_at", expires_at.to_rfc3339());
+    }
+    fields.insert("version", person.version.to_string());
+    if let Some(uuid) = person.uuid {
+        fields.insert("uuid", uuid.to_string());
+    }
+    if !person.addresses.is_empty() {
+        let addresses = serde_json::to_string(&person.addresses).map_err(|e| RepositoryError::Internal(e.to_string()))?;
+        fields.insert("addresses", addresses);
+    }
+    let fields = serde_json::to_string(&fields).map_err(|e| RepositoryError::Internal(e.to_string()))?;
+    Ok([person.id.to_string(), fields])
+}
+
+fn person_from_hash(mut fields: HashMap<String, String>) -> Result<Person, RepositoryError> {
+    let mut field = |name: &str| {
+        fields.remove(name)
+            .ok_or_else(|| RepositoryError::Internal(format!("person hash is missing '{}'", name)))
+    };
+    let invalid = |name: &str| RepositoryError::Internal(format!("person hash has an invalid '{}'", name));
+    Ok(Person {
+        id: field("id")?.parse().map_err(|_| invalid("id"))?,
+        name: field("name")?,
+        age: field("age")?.parse().map_err(|_| invalid("age"))?,
+        date: dates::parse(&field("date")?).ok_or_else(|| invalid("date"))?,
+        deleted_at: field("deleted_at").ok()
+            .map(|value| value.parse())
+            .transpose()
+            .map_err(|_| invalid("deleted_at"))?,
+        expires_at: field("expires_at").ok()
+            .map(|value| value.parse())
+            .transpose()
+            .map_err(|_| invalid("expires_at"))?,
+        version: field("version").ok()
+            .map(|value| value.parse())
+            .transpose()
+            .map_err(|_| invalid("version"))?
+            .unwrap_or(INITIAL_VERSION),
+        uuid: field("uuid").ok()
+            .map(|value| value.parse())
+            .transpose()
+            .map_err(|_| invalid("uuid"))?,
+        addresses: field("addresses").ok()
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(|_| invalid("addresses"))?
+            .unwrap_or_default(),
+    })
+}
+
+#[async_trait]
+impl PersonRepository for RedisRepository {
+    async fn check(&self) -> Vec<ComponentCheck> {
+        let mut connection = self.connection.clone();
+        let ping = redis::cmd("PING").query_async::<String>(&mut connection).await.map(|_| ()).map_err(map_redis_error);
+        vec![ComponentCheck::new("redis", ping)]
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        let mut connection = self.connection.clone();
+        let mut tenants: Vec<String> = connection.smembers(self.tenants_key()).await.map_err(map_redis_error)?;
+        // Data written before tenants existed never registered the default tenant.
+        let default_count: usize = connection.scard(self.ids_key(DEFAULT_TENANT)).await.map_err(map_redis_error)?;
+        if default_count > 0 && !tenants.iter().any(|t| t == DEFAULT_TENANT) {
+            tenants.push(DEFAULT_TENANT.to_string());
+        }
+        tenants.sort();
+        Ok(tenants)
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        let mut connection = self.connection.clone();
+        let ids: Vec<u32> = connection.smembers(self.ids_key(tenant)).await.map_err(map_redis_error)?;
+        let mut pipeline = redis::pipe();
+        for id in &ids {
+            pipeline.hgetall(self.person_key(tenant, *id));
+        }
+        let hashes: Vec<HashMap<String, String>> = pipeline
+            .query_async(&mut connection)
+            .await
+            .map_err(map_redis_error)?;
+        let mut persons = hashes.into_iter()
+            .filter(|fields| !fields.is_empty())
+            .map(person_from_hash)
+            .collect::<Result<Vec<_>, _>>()?;
+        persons.sort_by_key(|p| p.id);
+        Ok(persons)
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        let mut connection = self.connection.clone();
+        let fields: HashMap<String, String> = connection.hgetall(self.person_key(tenant, id)).await.map_err(map_redis_error)?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        person_from_hash(fields).map(Some)
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        match self.run_person_script(INSERT_SCRIPT, tenant, person).await? {
+            1 => Ok(()),
+            _ => Err(RepositoryError::Conflict),
+        }
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut connection = self.connection.clone();
+        let script = Script::new(INSERT_MANY_SCRIPT);
+        let mut invocation = script.key(self.ids_key(tenant));
+        invocation.key(self.tenants_key());
+        invocation.arg(self.person_prefix(tenant));
+        invocation.arg(tenant);
+        for person in persons {
+            invocation.arg(&person_args(person)?);
+        }
+        let inserted: i32 = invocation.invoke_async(&mut connection).await.map_err(map_redis_error)?;
+        match inserted {
+            1 => Ok(()),
+            _ => Err(RepositoryError::Conflict),
+        }
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        match self.run_person_script(UPDATE_SCRIPT, tenant, person).await? {
+            1 => Ok(()),
+            0 => Err(RepositoryError::NotFound),
+            _ => Err(RepositoryError::Conflict),
+        }
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut connection = self.connection.clone();
+        let script = Script::new(UPDATE_MANY_SCRIPT);
+        let mut invocation = script.key(self.ids_key(tenant));
+        invocation.arg(self.person_prefix(tenant));
+        for person in persons {
+            invocation.arg(&person_args(person)?);
+        }
+        let updated: i32 = invocation.invoke_async(&mut connection).await.map_err(map_redis_error)?;
+        match updated {
+            1 => Ok(()),
+            0 => Err(RepositoryError::NotFound),
+            _ => Err(RepositoryError::Conflict),
+        }
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let mut connection = self.connection.clone();
+        let deleted: i32 = Script::new(DELETE_SCRIPT)
+            .key(self.ids_key(tenant))
+            .key(self.person_key(tenant, id))
+            .key(self.tenants_key())
+            .arg(id)
+            .arg(tenant)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(map_redis_error)?;
+        match deleted {
+            1 => Ok(()),
+            _ => Err(RepositoryError::NotFound),
+        }
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let mut connection = self.connection.clone();
+        let script = Script::new(DELETE_MANY_SCRIPT);
+        let mut invocation = script.key(self.ids_key(tenant));
+        invocation.key(self.tenants_key());
+        invocation.arg(self.person_prefix(tenant));
+        invocation.arg(tenant);
+        for id in ids {
+            invocation.arg(id);
+        }
+        invocation.invoke_async(&mut connection).await.map_err(map_redis_error)
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut connection = self.connection.clone();
+        let script = Script::new(REPLACE_SCRIPT);
+        let mut invocation = script.key(self.ids_key(tenant));
+        invocation.key(self.tenants_key());
+        invocation.arg(self.person_prefix(tenant));
+        invocation.arg(tenant);
+        for person in persons {
+            invocation.arg(&person_args(person)?);
+        }
+        let _: i32 = invocation.invoke_async(&mut connection).await.map_err(map_redis_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+use rocket::async_trait;
+use sled::transaction::{abort, TransactionError};
+use crate::person::Person;
+use super::{ComponentCheck, PersonRepository, RepositoryError};
+
+const TREE_PREFIX: &str = "persons:";
+const HEALTH_PROBE_KEY: &[u8] = b"health-probe";
+
+/// Keeps one sled tree per tenant, keyed by the big-endian person id so
+/// iteration order matches id order.
+pub struct SledRepository {
+    db: sled::Db,
+}
+
+impl SledRepository {
+    pub fn open(path: &str) -> Result<Self, RepositoryError> {
+        let db = sled::open(path).map_err(map_sled_error)?;
+        Ok(SledRepository { db })
+    }
+
+    /// The tenant's tree, created on its first write.
+    fn tree(&self, tenant: &str) -> Result<sled::Tree, RepositoryError> {
+        self.db.open_tree(format!("{}{}", TREE_PREFIX, tenant)).map_err(map_sled_error)
+    }
+
+    /// The tenant's tree if it has been written to; reading doesn't create one, so
+    /// made-up `X-Tenant-Id`s leave nothing on disk.
+    fn existing_tree(&self, tenant: &str) -> Result<Option<sled::Tree>, RepositoryError> {
+        let name = format!("{}{}", TREE_PREFIX, tenant);
+        match self.db.tree_names().iter().any(|existing| existing == name.as_bytes()) {
+            true => self.tree(tenant).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    async fn flush(&self, tree: &sled::Tree) -> Result<(), RepositoryError> {
+        tree.flush_async().await.map(|_| ()).map_err(map_sled_error)
+    }
+}
+
+fn map_sled_error(error: sled::Error) -> RepositoryError {
+    RepositoryError::Internal(error.to_string())
+}
+
+fn map_transaction_error(error: TransactionError<RepositoryError>) -> RepositoryError {
+    match error {
+        TransactionError::Abort(e) => e,
+        TransactionError::Storage(e) => map_sled_error(e),
+    }
+}
+
+fn encode(person: &Person) -> Result<Vec<u8>, RepositoryError> {
+    serde_json::to_vec(person).map_err(|e| RepositoryError::Internal(e.to_string()))
+}
+
+fn decode(value: &[u8]) -> Result<Person, RepositoryError> {
+    serde_json::from_slice(value).map_err(|e| RepositoryError::Internal(e.to_string()))
+}
+
+#[async_trait]
+impl PersonRepository for SledRepository {
+    async fn check(&self) -> Vec<ComponentCheck> {
+        // The default tree holds no tenant, so the probe never shows up as a person.
+        let write = async {
+            self.db.insert(HEALTH_PROBE_KEY, &[]).map_err(map_sled_error)?;
+            self.db.remove(HEALTH_PROBE_KEY).map_err(map_sled_error)?;
+            self.db.flush_async().await.map(|_| ()).map_err(map_sled_error)
+        };
+        vec![ComponentCheck::new("disk", write.await)]
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.db.flush_async().await.map(|_| ()).map_err(map_sled_error)
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        let mut tenants = Vec::new();
+        for name in self.db.tree_names() {
+            let Some(tenant) = std::str::from_utf8(&name).ok().and_then(|n| n.strip_prefix(TREE_PREFIX)) else {
+                continue;
+            };
+            if !self.tree(tenant)?.is_empty() {
+                tenants.push(tenant.to_string());
+            }
+        }
+        tenants.sort();
+        Ok(tenants)
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        let Some(tree) = self.existing_tree(tenant)? else {
+            return Ok(Vec::new());
+        };
+        tree.iter()
+            .values()
+            .map(|value| decode(&value.map_err(map_sled_error)?))
+            .collect()
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        let Some(tree) = self.existing_tree(tenant)? else {
+            return Ok(None);
+        };
+        match tree.get(id.to_be_bytes()).map_err(map_sled_error)? {
+            Some(value) => decode(&value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let tree = self.tree(tenant)?;
+        let value = encode(&person)?;
+        tree.compare_and_swap(person.id.to_be_bytes(), None as Option<&[u8]>, Some(value))
+            .map_err(map_sled_error)?
+            .map_err(|_| RepositoryError::Conflict)?;
+        self.flush(&tree).await
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let tree = self.tree(tenant)?;
+        let mut ids = HashSet::new();
+        if !persons.iter().all(|p| ids.insert(p.id)) {
+            return Err(RepositoryError::Conflict);
+        }
+        let values = persons.iter()
+            .map(|p| Ok((p.id.to_be_bytes(), encode(p)?)))
+            .collect::<Result<Vec<_>, RepositoryError>>()?;
+        tree.transaction(|tx| {
+            for (key, value) in &values {
+                if tx.insert(key, value.as_slice())?.is_some() {
+                    return abort(RepositoryError::Conflict);
+                }
+            }
+            Ok(())
+        }).map_err(map_transaction_error)?;
+        self.flush(&tree).await
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.update_many(tenant, vec![person]).await
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let tree = self.existing_tree(tenant)?.ok_or(RepositoryError::NotFound)?;
+        let values = persons.iter()
+            .map(|p| Ok((p.id.to_be_bytes(), p.version, encode(p)?)))
+            .collect::<Result<Vec<_>, RepositoryError>>()?;
+        tree.transaction(|tx| {
+            for (key, version, value) in &values {
+                let current = match tx.get(key)? {
+                    Some(current) => decode(&current).or_else(abort)?,
+                    None => return abort(RepositoryError::NotFound),
+                };
+                if current.version + 1 != *version {
+                    return abort(RepositoryError::Conflict);
+                }
+                tx.insert(key, value.as_slice())?;
+            }
+            Ok(())
+        }).map_err(map_transaction_error)?;
+        self.flush(&tree).await
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let tree = self.existing_tree(tenant)?.ok_or(RepositoryError::NotFound)?;
+        match tree.remove(id.to_be_bytes()).map_err(map_sled_error)? {
+            Some(_) => self.flush(&tree).await,
+            None => Err(RepositoryError::NotFound),
+        }
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let Some(tree) = self.existing_tree(tenant)? else {
+            return Ok(Vec::new());
+        };
+        let deleted = tree.transaction(|tx| {
+            let mut deleted = Vec::new();
+            for id in &ids {
+                if tx.remove(&id.to_be_bytes())?.is_some() {
+                    deleted.push(*id);
+                }
+            }
+            Ok(deleted)
+        }).map_err(map_transaction_error)?;
+        if !deleted.is_empty() {
+            self.flush(&tree).await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let tree = self.tree(tenant)?;
+        let mut batch = sled::Batch::default();
+        for key in tree.iter().keys() {
+            batch.remove(key.map_err(map_sled_error)?);
+        }
+        for person in &persons {
+            batch.insert(&person.id.to_be_bytes(), encode(person)?);
+        }
+        tree.apply_batch(batch).map_err(map_sled_error)?;
+        self.flush(&tree).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use uuid::Uuid;
+    use super::*;
+
+    #[rocket::async_test]
+    async fn reading_an_unknown_tenant_creates_no_tree() {
+        let path = env::temp_dir().join(format!("rocket-app-test-{}.sled", Uuid::new_v4()));
+        let repository = SledRepository::open(path.to_str().unwrap()).unwrap();
+        let trees = repository.db.tree_names().len();
+        assert!(repository.list("made-up").await.unwrap().is_empty());
+        assert!(repository.get("made-up", 1).await.unwrap().is_none());
+        assert!(matches!(repository.delete("made-up", 1).await, Err(RepositoryError::NotFound)));
+        assert_eq!(repository.db.tree_names().len(), trees);
+        drop(repository);
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
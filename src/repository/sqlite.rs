@@ -0,0 +1,226 @@
+use rocket::async_trait;
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteArguments, SqliteConnectOptions, SqliteExecutor, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::types::Json;
+use sqlx::Row;
+use uuid::Uuid;
+use crate::person::{Address, Person};
+use super::{map_sqlx_error, AppliedMigration, ComponentCheck, PersonRepository, RepositoryError};
+
+const SELECT_PERSONS: &str = "SELECT id, name, age, date, deleted_at, expires_at, version, uuid, addresses FROM persons WHERE tenant = ?1";
+const INSERT_PERSON: &str = "INSERT INTO persons (tenant, id, name, age, date, deleted_at, expires_at, version, uuid, addresses) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+const UPDATE_PERSON: &str = "UPDATE persons SET name = ?3, age = ?4, date = ?5, deleted_at = ?6, expires_at = ?7, version = ?8, uuid = ?9, addresses = ?10 WHERE tenant = ?1 AND id = ?2 AND version = ?8 - 1";
+const PERSON_EXISTS: &str = "SELECT EXISTS (SELECT 1 FROM persons WHERE tenant = ?1 AND id = ?2)";
+
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub async fn connect(path: &str) -> Result<Self, RepositoryError> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| RepositoryError::Internal(format!("migration failed: {}", e)))?;
+
+        Ok(SqliteRepository { pool })
+    }
+}
+
+fn row_to_person(row: &SqliteRow) -> Result<Person, sqlx::Error> {
+    Ok(Person {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        age: row.try_get("age")?,
+        date: row.try_get("date")?,
+        deleted_at: row.try_get("deleted_at")?,
+        expires_at: row.try_get("expires_at")?,
+        version: row.try_get("version")?,
+        uuid: row.try_get("uuid")?,
+        addresses: row.try_get::<Json<Vec<Address>>, _>("addresses")?.0,
+    })
+}
+
+fn bind_person<'q>(query: Query<'q, Sqlite, SqliteArguments<'q>>, tenant: &'q str, person: Person) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    query
+        .bind(tenant)
+        .bind(person.id)
+        .bind(person.name)
+        .bind(person.age)
+        .bind(person.date)
+        .bind(person.deleted_at)
+        .bind(person.expires_at)
+        .bind(person.version)
+        .bind(person.uuid)
+        .bind(Json(person.addresses))
+}
+
+// A conditional UPDATE that touched no row either lost a version race or had nothing to update.
+async fn missing_or_stale<'e>(executor: impl SqliteExecutor<'e>, tenant: &str, id: u32) -> RepositoryError {
+    match sqlx::query_scalar(PERSON_EXISTS).bind(tenant).bind(id).fetch_one(executor).await {
+        Ok(true) => RepositoryError::Conflict,
+        Ok(false) => RepositoryError::NotFound,
+        Err(e) => map_sqlx_error(e),
+    }
+}
+
+#[async_trait]
+impl PersonRepository for SqliteRepository {
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        sqlx::query_as("SELECT version, description, installed_on, success FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(map_sqlx_error)
+    }
+
+    async fn check(&self) -> Vec<ComponentCheck> {
+        let ping = sqlx::query("SELECT 1").execute(&self.pool).await.map(|_| ()).map_err(map_sqlx_error);
+        vec![ComponentCheck::new("database", ping)]
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        // Closing the last connection checkpoints SQLite's own write-ahead log.
+        self.pool.close().await;
+        Ok(())
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        sqlx::query_scalar("SELECT DISTINCT tenant FROM persons ORDER BY tenant")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(map_sqlx_error)
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        let rows = sqlx::query(&format!("{} ORDER BY id", SELECT_PERSONS))
+            .bind(tenant)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        rows.iter()
+            .map(row_to_person)
+            .collect::<Result<_, _>>()
+            .map_err(map_sqlx_error)
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        let row = sqlx::query(&format!("{} AND id = ?2", SELECT_PERSONS))
+            .bind(tenant)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        row.as_ref().map(row_to_person).transpose().map_err(map_sqlx_error)
+    }
+
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        let row = sqlx::query(&format!("{} AND uuid = ?2", SELECT_PERSONS))
+            .bind(tenant)
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        row.as_ref().map(row_to_person).transpose().map_err(map_sqlx_error)
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        bind_person(sqlx::query(INSERT_PERSON), tenant, person)
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        Ok(())
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(map_sqlx_error)?;
+        for person in persons {
+            bind_person(sqlx::query(INSERT_PERSON), tenant, person)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_sqlx_error)?;
+        }
+        tx.commit().await.map_err(map_sqlx_error)
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let id = person.id;
+        let result = bind_person(sqlx::query(UPDATE_PERSON), tenant, person)
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        match result.rows_affected() {
+            0 => Err(missing_or_stale(&self.pool, tenant, id).await),
+            _ => Ok(()),
+        }
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(map_sqlx_error)?;
+        for person in persons {
+            let id = person.id;
+            let result = bind_person(sqlx::query(UPDATE_PERSON), tenant, person)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_sqlx_error)?;
+            if result.rows_affected() == 0 {
+                return Err(missing_or_stale(&mut *tx, tenant, id).await);
+            }
+        }
+        tx.commit().await.map_err(map_sqlx_error)
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let result = sqlx::query("DELETE FROM persons WHERE tenant = ?1 AND id = ?2")
+            .bind(tenant)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+        match result.rows_affected() {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(map_sqlx_error)?;
+        let mut deleted = Vec::new();
+        for id in ids {
+            let result = sqlx::query("DELETE FROM persons WHERE tenant = ?1 AND id = ?2")
+                .bind(tenant)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_sqlx_error)?;
+            if result.rows_affected() > 0 {
+                deleted.push(id);
+            }
+        }
+        tx.commit().await.map_err(map_sqlx_error)?;
+        Ok(deleted)
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(map_sqlx_error)?;
+        sqlx::query("DELETE FROM persons WHERE tenant = ?1")
+            .bind(tenant)
+            .execute(&mut *tx)
+            .await
+            .map_err(map_sqlx_error)?;
+        for person in persons {
+            bind_person(sqlx::query(INSERT_PERSON), tenant, person)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_sqlx_error)?;
+        }
+        tx.commit().await.map_err(map_sqlx_error)
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+use chrono::{DateTime, Utc};
+use rocket::tokio::sync::oneshot;
+use serde::{Deserialize, Serialize};
+use crate::person::Person;
+use crate::tenant::DEFAULT_TENANT;
+use super::RepositoryError;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Create { person: Person },
+    #[serde(rename = "create_many")]
+    CreateMany { persons: Vec<Person> },
+    Update { person: Person },
+    #[serde(rename = "update_many")]
+    UpdateMany { persons: Vec<Person> },
+    Delete { id: u32 },
+    #[serde(rename = "delete_many")]
+    DeleteMany { ids: Vec<u32> },
+    Replace { persons: Vec<Person> },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WalEntry {
+    pub at: DateTime<Utc>,
+    // Entries written before tenants existed belong to the default tenant.
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+impl WalEntry {
+    /// Applies the entry idempotently, so replaying a log on top of a
+    /// snapshot that already contains some of its entries is harmless.
+    pub fn replay(self, tenants: &mut HashMap<String, HashMap<u32, Person>>) {
+        let persons = tenants.entry(self.tenant).or_default();
+        match self.operation {
+            Operation::Create { person } | Operation::Update { person } => {
+                persons.insert(person.id, person);
+            }
+            Operation::CreateMany { persons: batch } | Operation::UpdateMany { persons: batch } => {
+                persons.extend(batch.into_iter().map(|p| (p.id, p)));
+            }
+            Operation::Delete { id } => {
+                persons.remove(&id);
+            }
+            Operation::DeleteMany { ids } => {
+                for id in ids {
+                    persons.remove(&id);
+                }
+            }
+            Operation::Replace { persons: replacement } => {
+                *persons = replacement.into_iter().map(|p| (p.id, p)).collect();
+            }
+        }
+    }
+}
+
+/// The log file, written by its own thread so appends never block the runtime or the
+/// store's lock: a mutation queues its entry under the lock, which keeps the log in the
+/// order mutations were applied, and waits for the sync once it has let go.
+pub struct WriteAheadLog {
+    commands: Sender<Command>,
+    path: PathBuf,
+}
+
+enum Command {
+    Append(String, oneshot::Sender<Result<(), RepositoryError>>),
+    Truncate(SyncSender<Result<(), RepositoryError>>),
+    Rotate(SyncSender<Result<(), RepositoryError>>),
+}
+
+/// An entry queued by [`WriteAheadLog::append`].
+pub struct Appended(oneshot::Receiver<Result<(), RepositoryError>>);
+
+impl Appended {
+    /// Waits until the entry is on disk.
+    pub async fn synced(self) -> Result<(), RepositoryError> {
+        self.0.await.unwrap_or_else(|_| Err(stopped()))
+    }
+}
+
+fn stopped() -> RepositoryError {
+    RepositoryError::Internal("the write-ahead log writer has stopped".to_string())
+}
+
+impl WriteAheadLog {
+    /// Opens (or creates) the log at `path` and returns it with the entries already
+    /// recorded, those of a rotated log left behind by [`rotate`](Self::rotate) first.
+    pub fn open(path: &Path) -> Result<(Self, Vec<WalEntry>), RepositoryError> {
+        let rotated = rotated_path(path);
+        let mut entries = Vec::new();
+        if rotated.exists() {
+            read_entries(&open_file(&rotated)?, &rotated, &mut entries)?;
+        }
+        let file = open_file(path)?;
+        read_entries(&file, path, &mut entries)?;
+        let (commands, received) = mpsc::channel();
+        let writer = Writer { file, path: path.to_path_buf() };
+        thread::spawn(move || writer.run(received));
+        Ok((WriteAheadLog { commands, path: path.to_path_buf() }, entries))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Empties the log, a rotated one included, once a snapshot holds everything it recorded.
+    pub fn truncate(&self) -> Result<(), RepositoryError> {
+        self.wait(Command::Truncate)?;
+        self.drop_rotated()
+    }
+
+    /// Moves the entries so far aside, to be dropped with [`drop_rotated`](Self::drop_rotated)
+    /// once a snapshot taken now is written, and starts an empty log. Entries already set
+    /// aside by a snapshot that failed stay where they are, and this log keeps growing.
+    /// Blocks until the entries queued before it are written.
+    pub fn rotate(&self) -> Result<(), RepositoryError> {
+        self.wait(Command::Rotate)
+    }
+
+    fn wait(&self, command: fn(SyncSender<Result<(), RepositoryError>>) -> Command) -> Result<(), RepositoryError> {
+        let (done, result) = mpsc::sync_channel(1);
+        self.commands.send(command(done)).map_err(|_| stopped())?;
+        result.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+
+    pub fn drop_rotated(&self) -> Result<(), RepositoryError> {
+        let rotated = rotated_path(&self.path);
+        match fs::remove_file(&rotated) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(RepositoryError::Internal(format!("failed to remove write-ahead log {}: {}", rotated.display(), e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Queues the entry behind those already queued; [`Appended::synced`] tells when it is written.
+    pub fn append(&self, tenant: &str, operation: Operation) -> Result<Appended, RepositoryError> {
+        let entry = WalEntry { at: Utc::now(), tenant: tenant.to_string(), operation };
+        let mut line = serde_json::to_string(&entry).map_err(|e| RepositoryError::Internal(e.to_string()))?;
+        line.push('\n');
+        let (done, synced) = oneshot::channel();
+        self.commands.send(Command::Append(line, done)).map_err(|_| stopped())?;
+        Ok(Appended(synced))
+    }
+}
+
+struct Writer {
+    file: File,
+    path: PathBuf,
+}
+
+impl Writer {
+    /// Runs until the log is dropped. Appends queued together share one sync.
+    fn run(mut self, commands: Receiver<Command>) {
+        let mut next = commands.recv().ok();
+        while let Some(command) = next.take() {
+            match command {
+                Command::Append(line, done) => {
+                    let mut batch = vec![(line, done)];
+                    loop {
+                        match commands.try_recv() {
+                            Ok(Command::Append(line, done)) => batch.push((line, done)),
+                            Ok(other) => {
+                                next = Some(other);
+                                break;
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    let result = self.write(batch.iter().map(|(line, _)| line.as_str()));
+                    for (_, done) in batch {
+                        let _ = done.send(result.clone().map_err(RepositoryError::Internal));
+                    }
+                }
+                Command::Truncate(done) => {
+                    let _ = done.send(self.truncate());
+                }
+                Command::Rotate(done) => {
+                    let _ = done.send(self.rotate());
+                }
+            }
+            if next.is_none() {
+                next = commands.recv().ok();
+            }
+        }
+    }
+
+    fn write<'a>(&mut self, lines: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        // Each line is written in one call, so a failure never leaves half an entry behind.
+        lines.into_iter()
+            .try_for_each(|line| self.file.write_all(line.as_bytes()))
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("failed to append to write-ahead log: {}", e))
+    }
+
+    fn truncate(&mut self) -> Result<(), RepositoryError> {
+        self.file.set_len(0)
+            .and_then(|_| self.file.sync_all())
+            .map_err(|e| RepositoryError::Internal(format!("failed to truncate write-ahead log {}: {}", self.path.display(), e)))
+    }
+
+    fn rotate(&mut self) -> Result<(), RepositoryError> {
+        let rotated = rotated_path(&self.path);
+        if rotated.exists() {
+            return Ok(());
+        }
+        fs::rename(&self.path, &rotated)
+            .map_err(|e| RepositoryError::Internal(format!("failed to rotate write-ahead log {}: {}", self.path.display(), e)))?;
+        self.file = open_file(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Where [`WriteAheadLog::rotate`] moves the log at `path`: beside it, with `.old` appended.
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".old");
+    name.into()
+}
+
+fn open_file(path: &Path) -> Result<File, RepositoryError> {
+    OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| RepositoryError::Internal(format!("write-ahead log {}: {}", path.display(), e)))
+}
+
+fn read_entries(file: &File, path: &Path, entries: &mut Vec<WalEntry>) -> Result<(), RepositoryError> {
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| RepositoryError::Internal(format!("write-ahead log {}: {}", path.display(), e)))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            RepositoryError::Internal(format!("write-ahead log {} line {}: {}", path.display(), number + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(())
+}
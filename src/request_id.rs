@@ -0,0 +1,43 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::Data;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+struct RequestId(String);
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_graphic())
+}
+
+/// The id of this request: the caller's `X-Request-Id` when it is sane, otherwise a fresh UUID.
+pub fn of<'r>(request: &'r Request<'_>) -> &'r str {
+    &request.local_cache(|| {
+        let id = request.headers().get_one(REQUEST_ID_HEADER)
+            .filter(|id| is_valid(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        RequestId(id)
+    }).0
+}
+
+/// Settles every request's id as it arrives and echoes it in the response's `X-Request-Id`,
+/// so callers can match their requests to the logs across services.
+pub struct RequestIds;
+
+#[rocket::async_trait]
+impl Fairing for RequestIds {
+    fn info(&self) -> Info {
+        Info { name: "Request ids", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.set_raw_header(REQUEST_ID_HEADER, of(request).to_string());
+    }
+}
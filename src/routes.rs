@@ -1,85 +1,1109 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::seq::IteratorRandom;
 use rocket::{State, Route};
+use rocket::tokio::time;
 use rocket::serde::json::Json;
 use rocket::http::Status;
+use rocket::response::status::{Created, Custom};
 use rocket::response::content::RawHtml;
-use crate::person::Person;
+use rocket_dyn_templates::{context, Template};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::audit::{AuditEntry, AuditFilter, AuditTrail};
+use crate::auth::{Admin, AdminSession, Editor, Reader};
+use crate::backup;
+use crate::bulk::{BulkDeleteResult, BulkPatch, BulkResult, BulkStatus};
+use crate::clock::{self, TimeReport, WorldClock};
+use crate::duplicates::{self, DuplicateCluster};
+use crate::errors::ApiError;
+use crate::etag::{IfMatch, Tag, Tagged};
+use crate::export::{Export, ExportFormat};
+use crate::fields::{FieldSet, Projected};
+use crate::ids::{self, IdMode, PersonKey};
+use crate::import::{Columns, CsvRecords, ImportMode, ImportReport, ImportRow, ImportStatus, SignedUpload};
+use crate::lifecycle::Phase;
+use crate::maintenance::Notice;
+use crate::metrics::{RouteCounts, RouteRequests};
+use crate::links::{self, Hypermedia, PersonBody};
+use crate::listing::{PersonFilter, Sorting};
+use crate::negotiate::Negotiated;
+use crate::pagination::{Page, Pagination, MAX_LIMIT};
+use crate::person::{Address, Person, INITIAL_VERSION};
+use crate::rate_limit::RateLimit;
+use crate::repository::{AppliedMigration, ComponentCheck, LockStats, RepositoryError};
+use crate::search;
+use crate::seed;
+use crate::signature::{Signed, SignedJson};
+use crate::stats::PersonStats;
+use crate::tenant::Tenant;
+use crate::validation::{BulkPatchInput, FieldErrors, NewAddress, NewPerson, PatchInput, Valid, Validate};
 use crate::AppState;
 
+/// Where the current API is mounted; `/api` stays an alias until clients have moved.
+pub const API_V1: &str = "/api/v1";
+pub const API_LEGACY: &str = "/api";
+/// Reserved for breaking changes, such as UUID-only ids; a preview while the `api_v2` flag is on.
+pub const API_V2: &str = "/api/v2";
+
 pub fn get_routes() -> Vec<Route> {
-    routes![landing_page, health, persons, single_person, add_person, update_person, delete_person]
+    routes![
+        landing_page, version, current_time, world_clock, uptime, health, livez, health_ready, readyz, admin_page,
+        migrations, runtime_stats, reload_config, feature_flags, maintenance_status, set_maintenance, backup_collection, restore_collection, reset_collection, audit_log,
+    ]
+}
+
+/// Person routes, relative to [`API_V1`].
+pub fn v1_routes() -> Vec<Route> {
+    routes![
+        persons, export_persons, search_persons, persons_by_date, person_duplicates, person_stats, random_person, single_person, add_person, add_persons, import_persons, import_persons_json, import_unsupported, update_person, upsert_person, patch_person, patch_persons, delete_person, delete_persons, restore_person,
+        person_addresses, add_address, delete_address,
+    ]
+}
+
+/// Routes relative to [`API_V2`], served behind the `api_v2` flag. Handlers that
+/// differ from v1 go here; the rest are [`v1_routes`] for now.
+pub fn v2_routes() -> Vec<Route> {
+    v1_routes()
+}
+
+/// Rendered from `templates/index.html.tera`, so the page can be restyled without a rebuild.
+/// `?tz=` or a `tz_default` adds the time on that zone's clocks.
+#[get("/?<tz>")]
+async fn landing_page(tz: Option<&str>, tenant: Tenant, state: &State<AppState>) -> Result<Template, ApiError> {
+    let zone = clock::zone(tz, state.tz_default)?;
+    let now = Utc::now();
+    let persons = state.repository.list(&tenant.0).await?.iter().filter(|p| p.deleted_at.is_none() && !p.is_expired(now)).count();
+    Ok(Template::render("index", context! {
+        greeting: state.live.current().greeting_text.clone(),
+        time: now.to_rfc3339(),
+        zone: zone.map(|zone| zone.name()),
+        local_time: zone.map(|zone| now.with_timezone(&zone).to_rfc3339()),
+        version: env!("CARGO_PKG_VERSION"),
+        persons,
+    }))
+}
+
+/// Start of the admin pages, for browsers signed in as an admin.
+#[get("/admin")]
+fn admin_page(session: AdminSession) -> RawHtml<String> {
+    RawHtml(format!(
+        "<!DOCTYPE html><title>Admin</title><p>Signed in as {}.</p>\
+         <form method=\"post\" action=\"/logout\"><button>Sign out</button></form>",
+        quick_xml::escape::escape(session.0.subject.as_str()),
+    ))
+}
+
+#[derive(Serialize)]
+struct Version {
+    version: &'static str,
+    /// Suffixed `-dirty` when built with uncommitted changes.
+    commit: &'static str,
+    built_at: Option<DateTime<Utc>>,
+    rustc: &'static str,
 }
 
-#[get("/")]
-fn landing_page(state: &State<AppState>) -> RawHtml<String> {
-    use chrono::Utc;
-    let current_time = Utc::now().to_rfc3339();
-    let response_body = format!("Rust-Rocket {} <br> Current UTC time: {}", state.greeting_text, current_time);
-    RawHtml(response_body)
+/// Which build is running, as `build.rs` recorded it.
+#[get("/version")]
+fn version() -> Json<Version> {
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("BUILD_GIT_COMMIT"),
+        built_at: env!("BUILD_UNIX_TIME").parse().ok().and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+        rustc: env!("BUILD_RUSTC_VERSION"),
+    })
 }
 
+/// The time the landing page shows, for clients that would otherwise scrape it; with
+/// `?tz=` or a `tz_default`, on that zone's clocks too.
+#[get("/api/time?<tz>")]
+fn current_time(tz: Option<&str>, state: &State<AppState>) -> Result<Json<TimeReport>, ApiError> {
+    Ok(Json(TimeReport::at(Utc::now(), clock::zone(tz, state.tz_default)?)))
+}
+
+/// The time in each of `?zones=`, with their offsets and whether daylight saving time is on.
+#[get("/api/time/world?<zones>")]
+fn world_clock(zones: Option<&str>) -> Result<Json<WorldClock>, ApiError> {
+    Ok(Json(WorldClock::at(Utc::now(), &clock::zones(zones)?)))
+}
+
+#[derive(Serialize)]
+struct Uptime {
+    started_at: DateTime<Utc>,
+    uptime_seconds: u64,
+    /// As an ISO 8601 duration, such as `PT26H3M12S`.
+    uptime: String,
+    requests_served: u64,
+}
+
+/// Since when the process has served, and how many responses it has sent since.
+#[get("/api/uptime")]
+fn uptime(state: &State<AppState>) -> Json<Uptime> {
+    let seconds = state.lifecycle.uptime().as_secs();
+    Json(Uptime {
+        started_at: state.lifecycle.started_at(),
+        uptime_seconds: seconds,
+        uptime: format!("PT{}H{}M{}S", seconds / 3600, seconds / 60 % 60, seconds % 60),
+        requests_served: state.lifecycle.served(),
+    })
+}
+
+/// Liveness too, so it stays `200` during maintenance, but says so.
 #[get("/health")]
-fn health() -> &'static str {
+fn health(state: &State<AppState>) -> &'static str {
+    match state.maintenance.current() {
+        Some(_) => "MAINTENANCE",
+        None => "OK",
+    }
+}
+
+/// Liveness: the process is up and serving, whatever its dependencies are doing.
+#[get("/livez")]
+fn livez() -> &'static str {
     "OK"
 }
 
-#[get("/api/persons")]
-fn persons(state: &State<AppState>) -> Result<Json<Vec<Person>>, Status> {
-    let persons = state.person_collection.read()
-        .map_err(|_| Status::InternalServerError)?;
-    Ok(Json(persons.clone()))
+/// How long readiness waits for the store's checks before failing them.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct Readiness {
+    /// `ok`, `failing`, or `starting` and `draining` before launch and during shutdown.
+    /// `maintenance` is ready too: the notices should still reach clients.
+    status: &'static str,
+    /// Each checked component's `ok` or `failing`; the reasons go to the log.
+    components: BTreeMap<&'static str, &'static str>,
+}
+
+#[get("/health/ready")]
+async fn health_ready(state: &State<AppState>) -> Custom<Json<Readiness>> {
+    readiness(state).await
+}
+
+/// Readiness: launched, not shutting down, and the store passes its checks.
+#[get("/readyz")]
+async fn readyz(state: &State<AppState>) -> Custom<Json<Readiness>> {
+    readiness(state).await
+}
+
+async fn readiness(state: &AppState) -> Custom<Json<Readiness>> {
+    let checks = match time::timeout(READINESS_TIMEOUT, state.repository.check()).await {
+        Ok(checks) => checks,
+        Err(_) => vec![ComponentCheck::new("storage", Err(RepositoryError::Unavailable("checks timed out".to_string())))],
+    };
+    let mut components = BTreeMap::new();
+    for check in checks {
+        let status = match check.result {
+            Ok(()) => "ok",
+            Err(e) => {
+                warn!("Readiness check of {} failed: {}", check.component, e);
+                "failing"
+            }
+        };
+        components.insert(check.component, status);
+    }
+    let status = match state.lifecycle.phase() {
+        Phase::Starting => "starting",
+        Phase::Draining => "draining",
+        Phase::Serving if components.values().any(|status| *status != "ok") => "failing",
+        Phase::Serving if state.maintenance.current().is_some() => "maintenance",
+        Phase::Serving => "ok",
+    };
+    match status {
+        "ok" | "maintenance" => Custom(Status::Ok, Json(Readiness { status, components })),
+        _ => Custom(Status::ServiceUnavailable, Json(Readiness { status, components })),
+    }
+}
+
+#[get("/persons?<include_deleted>&<fields>&<page..>")]
+#[allow(clippy::too_many_arguments)]
+async fn persons(
+    include_deleted: Option<bool>,
+    fields: Option<&str>,
+    filter: PersonFilter,
+    sorting: Sorting,
+    page: Pagination,
+    hypermedia: Hypermedia,
+    _reader: Reader,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Tagged<Page<Projected<PersonBody>>>, ApiError> {
+    let fields = fields.map(FieldSet::parse).transpose()?;
+    let persons = listed(state, &tenant, include_deleted, &filter, &sorting).await?;
+    let page = page.apply(persons)?.map(|person| {
+        let body = hypermedia.wrap(person);
+        match &fields {
+            Some(fields) => fields.project(body),
+            None => Projected::Full(body),
+        }
+    });
+    let tag = Tag::of(&(&page.items, page.total));
+    Ok(Tagged::new(page, tag))
+}
+
+/// The tenant's live persons as the listing shows them: filtered and sorted, before pagination.
+async fn listed(
+    state: &AppState,
+    tenant: &Tenant,
+    include_deleted: Option<bool>,
+    filter: &PersonFilter,
+    sorting: &Sorting,
+) -> Result<Vec<Person>, ApiError> {
+    let now = Utc::now();
+    let mut persons = state.repository.list(&tenant.0).await?;
+    persons.retain(|p| !p.is_expired(now) && filter.matches(p));
+    if !include_deleted.unwrap_or(false) {
+        persons.retain(|p| p.deleted_at.is_none());
+    }
+    sorting.apply(&mut persons);
+    Ok(persons)
+}
+
+#[get("/persons/export?<format>&<include_deleted>")]
+async fn export_persons(
+    format: Option<ExportFormat>,
+    include_deleted: Option<bool>,
+    filter: PersonFilter,
+    sorting: Sorting,
+    _reader: Reader,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Export, ApiError> {
+    let persons = listed(state, &tenant, include_deleted, &filter, &sorting).await?;
+    Ok(Export { format: format.unwrap_or(ExportFormat::Csv), persons })
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+#[derive(Serialize)]
+struct SearchHit {
+    score: u32,
+    #[serde(flatten)]
+    person: PersonBody,
+}
+
+#[get("/persons/search?<q>&<limit>")]
+async fn search_persons(q: &str, limit: Option<usize>, hypermedia: Hypermedia, _reader: Reader, tenant: Tenant, state: &State<AppState>) -> Result<Negotiated<Vec<SearchHit>>, ApiError> {
+    if !search::is_searchable(q) {
+        return Err(ApiError::query("q", "must contain a letter or digit"));
+    }
+    let ranked = state.search_index.search(&tenant.0, q);
+    if ranked.is_empty() {
+        return Ok(Negotiated::many("hits", "hit", Vec::new()));
+    }
+    let now = Utc::now();
+    let mut persons: HashMap<u32, Person> = state.repository.list(&tenant.0).await?
+        .into_iter()
+        .filter(|p| p.deleted_at.is_none() && !p.is_expired(now))
+        .map(|p| (p.id, p))
+        .collect();
+    let hits = ranked.into_iter()
+        .filter_map(|(id, score)| persons.remove(&id).map(|person| SearchHit { score, person: hypermedia.wrap(person) }))
+        .take(limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_LIMIT))
+        .collect();
+    Ok(Negotiated::many("hits", "hit", hits))
+}
+
+fn rfc3339_date(parameter: &str, value: Option<&str>) -> Result<Option<NaiveDate>, ApiError> {
+    Ok(rfc3339_timestamp(parameter, value)?.map(|t| t.date_naive()))
+}
+
+fn rfc3339_timestamp(parameter: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, ApiError> {
+    value.map(|value| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|_| ApiError::query(parameter, "must be an RFC 3339 timestamp"))
+    }).transpose()
+}
+
+/// Live persons whose `date` falls on or between the UTC days of the RFC 3339
+/// bounds, oldest first. Either bound may be left out.
+#[get("/persons/by-date?<from>&<to>")]
+async fn persons_by_date(
+    from: Option<&str>,
+    to: Option<&str>,
+    hypermedia: Hypermedia,
+    _reader: Reader,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Negotiated<Vec<PersonBody>>, ApiError> {
+    let (from, to) = (rfc3339_date("from", from)?, rfc3339_date("to", to)?);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ApiError::query("from", "must not be after to"));
+        }
+    }
+    let now = Utc::now();
+    let mut persons = state.repository.list(&tenant.0).await?;
+    persons.retain(|p| {
+        p.deleted_at.is_none()
+            && !p.is_expired(now)
+            && from.map_or(true, |from| p.date.date_naive() >= from)
+            && to.map_or(true, |to| p.date.date_naive() <= to)
+    });
+    persons.sort_by_key(|p| (p.date, p.id));
+    Ok(Negotiated::many("persons", "person", persons.into_iter().map(|p| hypermedia.wrap(p)).collect()))
+}
+
+#[get("/persons/duplicates?<by_age>")]
+async fn person_duplicates(by_age: Option<bool>, _reader: Reader, tenant: Tenant, state: &State<AppState>) -> Result<Json<Vec<DuplicateCluster>>, ApiError> {
+    let now = Utc::now();
+    let mut persons = state.repository.list(&tenant.0).await?;
+    persons.retain(|p| p.deleted_at.is_none() && !p.is_expired(now));
+    Ok(Json(duplicates::find(persons, by_age.unwrap_or(false))))
+}
+
+/// Aggregates over the live persons of the tenant, narrowed by the same filters as the listing.
+#[get("/persons/stats")]
+async fn person_stats(filter: PersonFilter, _reader: Reader, tenant: Tenant, state: &State<AppState>) -> Result<Negotiated<PersonStats>, ApiError> {
+    let now = Utc::now();
+    let mut persons = state.repository.list(&tenant.0).await?;
+    persons.retain(|p| p.deleted_at.is_none() && !p.is_expired(now) && filter.matches(p));
+    Ok(Negotiated::one("stats", PersonStats::compute(&persons)))
 }
 
-#[get("/api/person/<id>")]
-fn single_person(id: u32, state: &State<AppState>) -> Result<Json<Person>, Status> {
-    let persons_guard = state.person_collection.read()
-        .map_err(|_| Status::InternalServerError)?;
-    let filtered = persons_guard.iter().find(|t| t.id == id);
-    match filtered {
-        Some(filtered) => Ok(Json(filtered.clone())),
-        None => Err(Status::NotFound),
+#[derive(Responder)]
+enum RandomPick {
+    One(Box<Negotiated<PersonBody>>),
+    Sample(Negotiated<Vec<PersonBody>>),
+}
+
+/// One uniformly random live person, or with `?count=` a random sample of up to
+/// that many without repeats.
+#[get("/person/random?<count>")]
+async fn random_person(
+    count: Option<usize>,
+    hypermedia: Hypermedia,
+    _reader: Reader,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<RandomPick, ApiError> {
+    let now = Utc::now();
+    let persons = state.repository.list(&tenant.0).await?
+        .into_iter()
+        .filter(|p| p.deleted_at.is_none() && !p.is_expired(now));
+    let mut rng = rand::thread_rng();
+    match count {
+        None => {
+            let person = persons.choose(&mut rng).ok_or(ApiError::NotFound("person"))?;
+            Ok(RandomPick::One(Box::new(Negotiated::one("person", hypermedia.wrap(person)))))
+        }
+        Some(0) => Err(ApiError::query("count", "must be at least 1")),
+        Some(count) => {
+            let mut sample = persons.choose_multiple(&mut rng, count.min(MAX_LIMIT));
+            if sample.is_empty() {
+                return Err(ApiError::NotFound("person"));
+            }
+            // `choose_multiple` keeps the collection's order; shuffle so position carries no meaning.
+            rand::seq::SliceRandom::shuffle(sample.as_mut_slice(), &mut rng);
+            Ok(RandomPick::Sample(Negotiated::many("persons", "person", sample.into_iter().map(|p| hypermedia.wrap(p)).collect())))
+        }
+    }
+}
+
+#[get("/person/<id>?<include_deleted>")]
+async fn single_person(
+    id: PersonKey,
+    include_deleted: Option<bool>,
+    hypermedia: Hypermedia,
+    _reader: Reader,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Tagged<Negotiated<PersonBody>>, ApiError> {
+    match ids::find(state.repository.as_ref(), &tenant.0, &id).await? {
+        Some(person) if person.is_expired(Utc::now()) => Err(ApiError::NotFound("person")),
+        Some(person) if person.deleted_at.is_none() || include_deleted.unwrap_or(false) => {
+            let body = hypermedia.wrap(person);
+            let tag = Tag::person(&body, &body.person);
+            Ok(Tagged::new(Negotiated::one("person", body), tag))
+        }
+        _ => Err(ApiError::NotFound("person")),
     }
 }
 
-#[post("/api/person", data = "<person>")]
-fn add_person(person: Json<Person>, state: &State<AppState>) -> Result<Status, Status> {
-    let mut persons_guard = state.person_collection.write()
-        .map_err(|_| Status::InternalServerError)?;
-    let filtered = persons_guard.iter().any(|t| t.id == person.id);
-    if !filtered {
-        persons_guard.push(person.into_inner());
-        Ok(Status::Created)
+#[post("/person", data = "<person>")]
+async fn add_person(person: Valid<NewPerson>, hypermedia: Hypermedia, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Created<Negotiated<PersonBody>>, ApiError> {
+    let mut person = person.0;
+    person.deleted_at = None;
+    person.version = INITIAL_VERSION;
+    person.uuid = None;
+    person.renumber_addresses();
+    if state.id_mode == IdMode::Uuid || person.id == 0 {
+        let mut persons = [person];
+        state.ids.insert_new(state.repository.as_ref(), &tenant.0, &mut persons, state.id_mode == IdMode::Uuid).await?;
+        [person] = persons;
     } else {
-        Err(Status::Conflict)
+        state.repository.insert(&tenant.0, person.clone()).await?;
+        state.ids.observe(&tenant.0, person.id);
     }
+    trail.record(person.id);
+    let location = links::person_path(&person);
+    Ok(Created::new(location).body(Negotiated::one("person", hypermedia.wrap(person))))
 }
 
-#[put("/api/person", data = "<person>")]
-fn update_person(person: Json<Person>, state: &State<AppState>) -> Result<Status, Status> {
-    let mut persons_guard = state.person_collection.write()
-        .map_err(|_| Status::InternalServerError)?;
-    let person = person.into_inner();
-    let filtered = persons_guard.iter_mut().find(|t| t.id == person.id);
-    match filtered {
-        Some(p) => {
-            p.age = person.age;
-            p.date = person.date;
-            p.name = person.name;
-            Ok(Status::NoContent)
+/// Creates every person or none: if any id is taken (or repeated in the body)
+/// nothing is written and the per-item results say which ones were at fault.
+#[post("/persons", data = "<persons>")]
+async fn add_persons(persons: Valid<Vec<NewPerson>>, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Custom<Json<Vec<BulkResult>>>, ApiError> {
+    let mut persons = persons.0;
+    for person in &mut persons {
+        person.deleted_at = None;
+        person.version = INITIAL_VERSION;
+        person.uuid = None;
+        person.renumber_addresses();
+    }
+    if state.id_mode == IdMode::Uuid {
+        if !persons.is_empty() {
+            state.ids.insert_new(state.repository.as_ref(), &tenant.0, &mut persons, true).await?;
+        }
+        trail.record_all(persons.iter().map(|p| p.id));
+        let results = persons.iter()
+            .map(|p| BulkResult { id: p.id, uuid: p.uuid, status: BulkStatus::Created, errors: None })
+            .collect();
+        return Ok(Custom(Status::Created, Json(results)));
+    }
+
+    // Persons sent without an id (or with 0) get fresh ones from the counter.
+    let unassigned = persons.iter().filter(|p| p.id == 0).count();
+    if unassigned > 0 {
+        let first = state.ids.reserve(state.repository.as_ref(), &tenant.0, unassigned).await?;
+        for (id, person) in (first..).zip(persons.iter_mut().filter(|p| p.id == 0)) {
+            person.id = id;
         }
-        None => Err(Status::NotFound),
     }
+    let highest = persons.iter().map(|p| p.id).max();
+    let mut seen = HashSet::new();
+    let mut results: Vec<BulkResult> = persons.iter()
+        .map(|p| BulkResult {
+            id: p.id,
+            uuid: None,
+            status: if seen.insert(p.id) { BulkStatus::Created } else { BulkStatus::Duplicate },
+            errors: None,
+        })
+        .collect();
+
+    let has_duplicates = results.iter().any(|r| r.status == BulkStatus::Duplicate);
+    let outcome = match has_duplicates {
+        true => Err(RepositoryError::Conflict),
+        false if persons.is_empty() => Ok(()),
+        false => state.repository.insert_many(&tenant.0, persons).await,
+    };
+    match outcome {
+        Ok(()) => {
+            if let Some(highest) = highest {
+                state.ids.observe(&tenant.0, highest);
+            }
+            trail.record_all(results.iter().map(|r| r.id));
+            Ok(Custom(Status::Created, Json(results)))
+        }
+        Err(RepositoryError::Conflict) => {
+            for result in &mut results {
+                if result.status != BulkStatus::Created {
+                    continue;
+                }
+                result.status = match state.repository.get(&tenant.0, result.id).await? {
+                    Some(_) => BulkStatus::Conflict,
+                    None => BulkStatus::Skipped,
+                };
+            }
+            Ok(Custom(Status::Conflict, Json(results)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Inserts each valid row of an uploaded CSV file (columns as in the export) on its
+/// own, reporting per row. Rows are read and written one at a time.
+#[post("/persons/import", format = "multipart/form-data", data = "<upload>")]
+async fn import_persons(upload: SignedUpload<'_>, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Json<ImportReport>, ApiError> {
+    let reader = upload.0.file.open().await.map_err(|e| ApiError::Internal(e.to_string()))?;
+    let mut records = CsvRecords::new(reader);
+    let header = records.next().await.map_err(unreadable_csv)?;
+    let columns = header.and_then(|(_, header)| Columns::from_header(&header))
+        .ok_or_else(|| ApiError::BadRequest("the CSV header must name the name, age and date columns".to_string()))?;
+    let mut report = ImportReport::default();
+    while let Some((line, fields)) = records.next().await.map_err(unreadable_csv)? {
+        let mut person = match columns.person(&fields) {
+            Ok(person) => person,
+            Err(error) => {
+                report.push(ImportRow { line, id: None, status: ImportStatus::Invalid, error: Some(error) });
+                continue;
+            }
+        };
+        let outcome = if state.id_mode == IdMode::Uuid || person.id == 0 {
+            let mut persons = [person];
+            let outcome = state.ids.insert_new(state.repository.as_ref(), &tenant.0, &mut persons, state.id_mode == IdMode::Uuid).await;
+            [person] = persons;
+            outcome
+        } else {
+            let outcome = state.repository.insert(&tenant.0, person.clone()).await;
+            state.ids.observe(&tenant.0, person.id);
+            outcome
+        };
+        let (status, error) = match outcome {
+            Ok(()) => {
+                trail.record(person.id);
+                (ImportStatus::Created, None)
+            }
+            Err(RepositoryError::Conflict) => (ImportStatus::Conflict, None),
+            Err(RepositoryError::NameTaken(id)) => (ImportStatus::Conflict, Some(format!("name already used by person {}", id))),
+            Err(e) => return Err(e.into()),
+        };
+        report.push(ImportRow { line, id: Some(person.id), status, error });
+    }
+    Ok(Json(report))
+}
+
+/// Uploads in any other format than the two import routes accept.
+#[post("/persons/import", rank = 3)]
+fn import_unsupported() -> ApiError {
+    ApiError::UnsupportedMediaType("multipart/form-data (CSV) or application/json")
 }
 
-#[delete("/api/person/<id>")]
-fn delete_person(id: u32, state: &State<AppState>) -> Result<Status, Status> {
-    let mut persons_guard = state.person_collection.write()
-        .map_err(|_| Status::InternalServerError)?;
-    let index = persons_guard.iter().position(|t| t.id == id);
-    match index {
-        Some(index) => {
-            persons_guard.remove(index);
+fn unreadable_csv(error: std::io::Error) -> ApiError {
+    ApiError::BadRequest(format!("the upload is not readable CSV: {}", error))
+}
+
+/// Loads a JSON array in the listing/backup shape, each person checked as `POST /person`
+/// bodies are. Ids and UUIDs are kept as sent; versions and deletions are the server's, so
+/// every person starts over at the first version. `replace` swaps the collection atomically
+/// if every person is valid; `merge` (the default) adds each valid person whose id is free
+/// and reports the rest as invalid or conflicts.
+#[post("/persons/import?<mode>", format = "json", data = "<persons>")]
+async fn import_persons_json(
+    mode: Option<ImportMode>,
+    persons: SignedJson<Vec<NewPerson>>,
+    _editor: Editor,
+    trail: AuditTrail<'_>,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Custom<Json<Vec<BulkResult>>>, ApiError> {
+    let mut rows: Vec<(u32, Result<Person, FieldErrors>)> = persons.0.into_iter()
+        .map(|person| (person.id(), person.validate()))
+        .collect();
+    let mut persons: Vec<&mut Person> = rows.iter_mut().filter_map(|(_, row)| row.as_mut().ok()).collect();
+    for person in &mut persons {
+        person.renumber_addresses();
+    }
+    let unassigned = persons.iter().filter(|p| p.id == 0).count();
+    if unassigned > 0 {
+        let first = state.ids.reserve(state.repository.as_ref(), &tenant.0, unassigned).await?;
+        for (id, person) in (first..).zip(persons.iter_mut().filter(|p| p.id == 0)) {
+            person.id = id;
+            if state.id_mode == IdMode::Uuid && person.uuid.is_none() {
+                person.uuid = Some(Uuid::new_v4());
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    let mut results: Vec<BulkResult> = rows.iter()
+        .map(|(id, row)| match row {
+            Ok(p) => BulkResult {
+                id: p.id,
+                uuid: p.uuid,
+                status: if seen.insert(p.id) { BulkStatus::Created } else { BulkStatus::Duplicate },
+                errors: None,
+            },
+            Err(errors) => BulkResult { id: *id, uuid: None, status: BulkStatus::Invalid, errors: Some(errors.clone()) },
+        })
+        .collect();
+    let mode = mode.unwrap_or(ImportMode::Merge);
+    let failure = if results.iter().any(|r| r.status == BulkStatus::Duplicate) {
+        Some(Status::Conflict)
+    } else if mode == ImportMode::Replace && results.iter().any(|r| r.status == BulkStatus::Invalid) {
+        Some(Status::UnprocessableEntity)
+    } else {
+        None
+    };
+    if let Some(status) = failure {
+        for result in &mut results {
+            if result.status == BulkStatus::Created {
+                result.status = BulkStatus::Skipped;
+            }
+        }
+        return Ok(Custom(status, Json(results)));
+    }
+    let persons: Vec<Person> = rows.into_iter().filter_map(|(_, row)| row.ok()).collect();
+    if let Some(highest) = persons.iter().map(|p| p.id).max() {
+        state.ids.observe(&tenant.0, highest);
+    }
+
+    if mode == ImportMode::Replace {
+        trail.record_all(persons.iter().map(|p| p.id));
+        state.repository.replace_all(&tenant.0, persons).await?;
+        return Ok(Custom(Status::Ok, Json(results)));
+    }
+    let mut created = results.iter_mut().filter(|r| r.status == BulkStatus::Created);
+    for (person, result) in persons.into_iter().zip(&mut created) {
+        result.status = match state.repository.insert(&tenant.0, person).await {
+            Ok(()) => {
+                trail.record(result.id);
+                BulkStatus::Created
+            }
+            Err(RepositoryError::Conflict | RepositoryError::NameTaken(_)) => BulkStatus::Conflict,
+            Err(e) => return Err(e.into()),
+        };
+    }
+    Ok(Custom(Status::Ok, Json(results)))
+}
+
+#[put("/person", data = "<person>")]
+async fn update_person(person: Valid<NewPerson>, if_match: IfMatch, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Status, ApiError> {
+    let mut person = person.0;
+    let key = match person.uuid {
+        Some(uuid) => PersonKey::Uuid(uuid),
+        None => PersonKey::Id(person.id),
+    };
+    let existing = match ids::find(state.repository.as_ref(), &tenant.0, &key).await? {
+        Some(existing) if existing.deleted_at.is_none() => existing,
+        _ => return Err(ApiError::NotFound("person")),
+    };
+    if_match.check(&existing)?;
+    person.id = existing.id;
+    person.uuid = existing.uuid;
+    person.addresses = existing.addresses.clone();
+    person.deleted_at = None;
+    person.version = existing.version + 1;
+    trail.record_change(&existing, &person);
+    state.repository.update(&tenant.0, person).await?;
+    Ok(Status::NoContent)
+}
+
+#[derive(Responder)]
+enum Upserted {
+    Created(Created<Negotiated<PersonBody>>),
+    Updated(Negotiated<PersonBody>),
+}
+
+/// Idempotent create-or-replace at a known key: `201` when the person didn't
+/// exist (or was soft-deleted), `200` when it was replaced.
+#[put("/person/<id>", data = "<person>")]
+#[allow(clippy::too_many_arguments)]
+async fn upsert_person(
+    id: PersonKey,
+    person: Valid<NewPerson>,
+    if_match: IfMatch,
+    hypermedia: Hypermedia,
+    _editor: Editor,
+    trail: AuditTrail<'_>,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Upserted, ApiError> {
+    let mut person = person.0;
+    match ids::find(state.repository.as_ref(), &tenant.0, &id).await? {
+        Some(existing) => {
+            let created = existing.deleted_at.is_some();
+            match created {
+                true => if_match.check_absent()?,
+                false => if_match.check(&existing)?,
+            }
+            person.id = existing.id;
+            person.uuid = existing.uuid;
+            match created {
+                true => person.renumber_addresses(),
+                false => person.addresses = existing.addresses.clone(),
+            }
+            person.deleted_at = None;
+            person.version = existing.version + 1;
+            trail.record_change(&existing, &person);
+            state.repository.update(&tenant.0, person.clone()).await?;
+            let location = links::person_path(&person);
+            let body = Negotiated::one("person", hypermedia.wrap(person));
+            Ok(match created {
+                true => Upserted::Created(Created::new(location).body(body)),
+                false => Upserted::Updated(body),
+            })
+        }
+        None => {
+            if_match.check_absent()?;
+            person.deleted_at = None;
+            person.version = INITIAL_VERSION;
+            person.renumber_addresses();
+            match id {
+                PersonKey::Id(id) => {
+                    person.id = id;
+                    person.uuid = (state.id_mode == IdMode::Uuid).then(Uuid::new_v4);
+                    state.repository.insert(&tenant.0, person.clone()).await?;
+                    state.ids.observe(&tenant.0, id);
+                }
+                PersonKey::Uuid(uuid) => {
+                    person.uuid = Some(uuid);
+                    let mut persons = [person];
+                    state.ids.insert_new(state.repository.as_ref(), &tenant.0, &mut persons, false).await?;
+                    [person] = persons;
+                }
+            }
+            trail.record(person.id);
+            let location = links::person_path(&person);
+            Ok(Upserted::Created(Created::new(location).body(Negotiated::one("person", hypermedia.wrap(person)))))
+        }
+    }
+}
+
+#[patch("/person/<id>", data = "<patch>")]
+#[allow(clippy::too_many_arguments)]
+async fn patch_person(
+    id: PersonKey,
+    patch: Valid<PatchInput>,
+    if_match: IfMatch,
+    hypermedia: Hypermedia,
+    _editor: Editor,
+    trail: AuditTrail<'_>,
+    tenant: Tenant,
+    state: &State<AppState>,
+) -> Result<Negotiated<PersonBody>, ApiError> {
+    let mut person = match ids::find(state.repository.as_ref(), &tenant.0, &id).await? {
+        Some(person) if person.deleted_at.is_none() && !person.is_expired(Utc::now()) => person,
+        _ => return Err(ApiError::NotFound("person")),
+    };
+    if_match.check(&person)?;
+    let before = person.clone();
+    patch.0.apply(&mut person);
+    person.version += 1;
+    trail.record_change(&before, &person);
+    state.repository.update(&tenant.0, person.clone()).await?;
+    Ok(Negotiated::one("person", hypermedia.wrap(person)))
+}
+
+/// Applies every patch in one atomic write, or none of them if any id is
+/// missing or repeated.
+#[patch("/persons", data = "<patches>")]
+async fn patch_persons(patches: Valid<Vec<BulkPatchInput>>, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Custom<Json<Vec<BulkResult>>>, ApiError> {
+    let now = Utc::now();
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+    let mut updated = Vec::new();
+    for BulkPatch { id, patch } in patches.0 {
+        let status = if !seen.insert(id) {
+            BulkStatus::Duplicate
+        } else {
+            match state.repository.get(&tenant.0, id).await? {
+                Some(mut person) if person.deleted_at.is_none() && !person.is_expired(now) => {
+                    let before = person.clone();
+                    patch.apply(&mut person);
+                    person.version += 1;
+                    updated.push((before, person));
+                    BulkStatus::Updated
+                }
+                _ => BulkStatus::NotFound,
+            }
+        };
+        results.push(BulkResult { id, uuid: None, status, errors: None });
+    }
+
+    let failure = if results.iter().any(|r| r.status == BulkStatus::Duplicate) {
+        Some(Status::Conflict)
+    } else if results.iter().any(|r| r.status == BulkStatus::NotFound) {
+        Some(Status::NotFound)
+    } else {
+        None
+    };
+    if let Some(status) = failure {
+        for result in &mut results {
+            if result.status == BulkStatus::Updated {
+                result.status = BulkStatus::Skipped;
+            }
+        }
+        return Ok(Custom(status, Json(results)));
+    }
+    if !updated.is_empty() {
+        for (before, after) in &updated {
+            trail.record_change(before, after);
+        }
+        state.repository.update_many(&tenant.0, updated.into_iter().map(|(_, after)| after).collect()).await?;
+    }
+    Ok(Custom(Status::Ok, Json(results)))
+}
+
+#[delete("/person/<id>")]
+async fn delete_person(id: PersonKey, if_match: IfMatch, _editor: Editor, _signed: Signed, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Status, ApiError> {
+    let mut person = match ids::find(state.repository.as_ref(), &tenant.0, &id).await? {
+        Some(person) if person.deleted_at.is_none() || !state.soft_delete => person,
+        _ => return Err(ApiError::NotFound("person")),
+    };
+    if_match.check(&person)?;
+    trail.record(person.id);
+    if state.soft_delete {
+        person.deleted_at = Some(Utc::now());
+        person.version += 1;
+        state.repository.update(&tenant.0, person).await?;
+    } else {
+        state.repository.delete(&tenant.0, person.id).await?;
+    }
+    Ok(Status::NoContent)
+}
+
+#[delete("/persons", data = "<ids>")]
+async fn delete_persons(ids: Valid<Vec<u32>>, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Json<BulkDeleteResult>, ApiError> {
+    let mut ids = ids.0;
+    ids.sort();
+    ids.dedup();
+    let deleted = if state.soft_delete {
+        let now = Utc::now();
+        let mut persons = Vec::new();
+        for id in &ids {
+            if let Some(mut person) = state.repository.get(&tenant.0, *id).await? {
+                if person.deleted_at.is_none() {
+                    person.deleted_at = Some(now);
+                    person.version += 1;
+                    persons.push(person);
+                }
+            }
+        }
+        let deleted = persons.iter().map(|p| p.id).collect();
+        if !persons.is_empty() {
+            state.repository.update_many(&tenant.0, persons).await?;
+        }
+        deleted
+    } else {
+        state.repository.delete_many(&tenant.0, ids.clone()).await?
+    };
+    trail.record_all(deleted.iter().copied());
+    let not_found = ids.into_iter().filter(|id| !deleted.contains(id)).collect();
+    Ok(Json(BulkDeleteResult { deleted, not_found }))
+}
+
+#[post("/person/<id>/restore")]
+async fn restore_person(id: PersonKey, _editor: Editor, _signed: Signed, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Status, ApiError> {
+    match ids::find(state.repository.as_ref(), &tenant.0, &id).await? {
+        Some(mut person) if person.deleted_at.is_some() => {
+            person.deleted_at = None;
+            person.version += 1;
+            trail.record(person.id);
+            state.repository.update(&tenant.0, person).await?;
             Ok(Status::NoContent)
         }
-        None => Err(Status::NotFound),
+        Some(_) => Err(ApiError::Conflict("the person is not deleted")),
+        None => Err(ApiError::NotFound("person")),
+    }
+}
+
+/// The person behind `key` unless it is soft-deleted or expired.
+async fn live_person(state: &AppState, tenant: &Tenant, key: &PersonKey) -> Result<Person, ApiError> {
+    match ids::find(state.repository.as_ref(), &tenant.0, key).await? {
+        Some(person) if person.deleted_at.is_none() && !person.is_expired(Utc::now()) => Ok(person),
+        _ => Err(ApiError::NotFound("person")),
     }
 }
+
+#[get("/person/<id>/addresses")]
+async fn person_addresses(id: PersonKey, _reader: Reader, tenant: Tenant, state: &State<AppState>) -> Result<Json<Vec<Address>>, ApiError> {
+    let person = live_person(state, &tenant, &id).await?;
+    Ok(Json(person.addresses))
+}
+
+/// Addresses are part of their person, so adding or removing one bumps the
+/// person's version and honours `If-Match` like any other write.
+#[post("/person/<id>/addresses", data = "<address>")]
+async fn add_address(id: PersonKey, address: Valid<NewAddress>, if_match: IfMatch, _editor: Editor, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Created<Json<Address>>, ApiError> {
+    let mut person = live_person(state, &tenant, &id).await?;
+    if_match.check(&person)?;
+    let address = person.add_address(address.0);
+    let location = format!("{}/address/{}", links::person_path(&person), address.id);
+    person.version += 1;
+    trail.record(person.id);
+    state.repository.update(&tenant.0, person).await?;
+    Ok(Created::new(location).body(Json(address)))
+}
+
+#[delete("/person/<id>/address/<address_id>")]
+#[allow(clippy::too_many_arguments)]
+async fn delete_address(id: PersonKey, address_id: u32, if_match: IfMatch, _editor: Editor, _signed: Signed, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Status, ApiError> {
+    let mut person = live_person(state, &tenant, &id).await?;
+    if_match.check(&person)?;
+    let position = person.addresses.iter().position(|a| a.id == address_id).ok_or(ApiError::NotFound("address"))?;
+    person.addresses.remove(position);
+    person.version += 1;
+    trail.record(person.id);
+    state.repository.update(&tenant.0, person).await?;
+    Ok(Status::NoContent)
+}
+
+#[get("/admin/migrations")]
+async fn migrations(_admin: Admin, state: &State<AppState>) -> Result<Json<Vec<AppliedMigration>>, ApiError> {
+    let migrations = state.repository.migrations().await?;
+    Ok(Json(migrations))
+}
+
+#[derive(Serialize)]
+struct RuntimeStats {
+    uptime_seconds: u64,
+    /// Resident set size; only known on Linux.
+    memory_bytes: Option<u64>,
+    /// Across tenants, soft-deleted ones included.
+    persons: usize,
+    /// Only for stores that hold their own lock, such as the in-memory one.
+    lock_waits: Option<LockStats>,
+    /// Empty with `METRICS=false`.
+    requests: Vec<RouteRequests>,
+}
+
+/// The process' resident memory, from `/proc/self/status`.
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[get("/admin/stats")]
+async fn runtime_stats(_admin: Admin, requests: RouteCounts, state: &State<AppState>) -> Result<Json<RuntimeStats>, ApiError> {
+    let mut persons = 0;
+    for tenant in state.repository.tenants().await? {
+        persons += state.repository.list(&tenant).await?.len();
+    }
+    Ok(Json(RuntimeStats {
+        uptime_seconds: state.lifecycle.uptime().as_secs(),
+        memory_bytes: resident_memory_bytes(),
+        persons,
+        lock_waits: state.repository.lock_waits(),
+        requests: requests.0,
+    }))
+}
+
+#[derive(Serialize)]
+struct Reloaded {
+    greeting_text: String,
+    rate_limit: Option<RateLimit>,
+    log_level: String,
+    flags: BTreeMap<String, bool>,
+}
+
+/// Applies the settings that can change without a restart, as `SIGHUP` does.
+#[post("/admin/reload")]
+fn reload_config(_admin: Admin, _signed: Signed, state: &State<AppState>) -> Result<Json<Reloaded>, ApiError> {
+    let live = state.live.reload().map_err(|e| ApiError::BadRequest(format!("the configuration is invalid: {}", e)))?;
+    Ok(Json(Reloaded {
+        greeting_text: live.greeting_text.clone(),
+        rate_limit: live.rate_limit,
+        log_level: live.log_level.to_string(),
+        flags: live.flags.list(),
+    }))
+}
+
+const DEFAULT_MAINTENANCE_MESSAGE: &str = "The service is down for maintenance.";
+const DEFAULT_RETRY_AFTER_SECS: u64 = 300;
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    enabled: bool,
+    message: Option<String>,
+    retry_after: Option<u64>,
+}
+
+impl Validate for MaintenanceRequest {
+    type Output = Option<Notice>;
+
+    fn validate(self) -> Result<Option<Notice>, FieldErrors> {
+        let message = self.message.as_deref().map_or(DEFAULT_MAINTENANCE_MESSAGE, str::trim);
+        if message.is_empty() {
+            let mut errors = FieldErrors::default();
+            errors.add("message", "must not be empty");
+            return Err(errors);
+        }
+        Ok(self.enabled.then(|| Notice {
+            message: message.to_string(),
+            retry_after: self.retry_after.unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+            since: Utc::now(),
+        }))
+    }
+}
+
+/// The maintenance notice in effect, or `null`.
+#[get("/admin/maintenance")]
+fn maintenance_status(_admin: Admin, state: &State<AppState>) -> Json<Option<Notice>> {
+    Json(state.maintenance.current())
+}
+
+/// Turns maintenance on, answering everything but the admin, sign-in and probe routes with
+/// a `503`, or off again.
+#[put("/admin/maintenance", data = "<request>")]
+fn set_maintenance(request: Valid<MaintenanceRequest>, _admin: Admin, state: &State<AppState>) -> Json<Option<Notice>> {
+    state.maintenance.set(request.0);
+    Json(state.maintenance.current())
+}
+
+/// The known feature flags and the configured ones, on or off.
+#[get("/admin/flags")]
+fn feature_flags(_admin: Admin, state: &State<AppState>) -> Json<BTreeMap<String, bool>> {
+    Json(state.live.current().flags.list())
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    file: String,
+    #[serde(default)]
+    count: usize,
+}
+
+impl Validate for BackupFile {
+    type Output = BackupFile;
+
+    fn validate(self) -> Result<BackupFile, FieldErrors> {
+        Ok(self)
+    }
+}
+
+#[post("/admin/backup")]
+async fn backup_collection(_admin: Admin, _signed: Signed, tenant: Tenant, state: &State<AppState>) -> Result<Json<BackupFile>, ApiError> {
+    let persons = state.repository.list(&tenant.0).await?;
+    let file = backup::write_backup(&state.backup_dir, &tenant.0, &persons)?;
+    Ok(Json(BackupFile { file, count: persons.len() }))
+}
+
+#[post("/admin/restore", data = "<request>")]
+async fn restore_collection(request: Valid<BackupFile>, _admin: Admin, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Json<BackupFile>, ApiError> {
+    let persons = backup::read_backup(&state.backup_dir, &request.0.file, &tenant.0)?;
+    let count = persons.len();
+    trail.record_all(persons.iter().map(|p| p.id));
+    state.repository.replace_all(&tenant.0, persons).await?;
+    // New ids go after the restored ones, not after those they replaced.
+    state.ids.forget(&tenant.0);
+    Ok(Json(BackupFile { file: request.0.file, count }))
+}
+
+#[derive(Serialize)]
+struct Reset {
+    removed: usize,
+    seeded: usize,
+}
+
+/// Empties the tenant, and with `seed=true` fills it from the seed file again (or with the
+/// sample persons), so integration tests can start each run from a known state. New ids
+/// start over after the highest one left.
+#[post("/admin/reset?<seed>")]
+async fn reset_collection(seed: Option<bool>, _admin: Admin, _signed: Signed, trail: AuditTrail<'_>, tenant: Tenant, state: &State<AppState>) -> Result<Json<Reset>, ApiError> {
+    let persons = match seed.unwrap_or(false) {
+        true => seed::persons(state.seed_file.as_deref(), state.sample_persons).map_err(|e| ApiError::BadRequest(format!("the seed file is invalid: {}", e)))?,
+        false => Vec::new(),
+    };
+    let removed = state.repository.list(&tenant.0).await?;
+    trail.record_all(removed.iter().chain(&persons).map(|p| p.id));
+    let (removed, seeded) = (removed.len(), persons.len());
+    state.repository.replace_all(&tenant.0, persons).await?;
+    state.ids.forget(&tenant.0);
+    Ok(Json(Reset { removed, seeded }))
+}
+
+/// Recorded writes, newest first; `person` keeps those that touched that person,
+/// `from` and `to` (RFC 3339) bound when they happened.
+#[get("/admin/audit?<person>&<from>&<to>&<limit>")]
+async fn audit_log(
+    person: Option<u32>,
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<usize>,
+    _admin: Admin,
+    state: &State<AppState>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let filter = AuditFilter { person_id: person, from: rfc3339_timestamp("from", from)?, to: rfc3339_timestamp("to", to)? };
+    let limit = match limit {
+        Some(0) => return Err(ApiError::query("limit", "must be at least 1")),
+        Some(limit) => limit.min(MAX_LIMIT),
+        None => MAX_LIMIT,
+    };
+    let entries = state.audit.query(filter, limit).await
+        .map_err(|e| ApiError::Internal(format!("reading the audit log: {}", e)))?;
+    Ok(Json(entries))
+}
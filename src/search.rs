@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, PoisonError, RwLock};
+use rocket::async_trait;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+use crate::person::Person;
+use crate::repository::{AppliedMigration, ComponentCheck, LockStats, PersonRepository, RepositoryError};
+
+const EXACT_SCORE: u32 = 3;
+const PREFIX_SCORE: u32 = 2;
+const SUBSTRING_SCORE: u32 = 1;
+
+/// Text fields of a person that take part in full-text search.
+fn searchable_fields(person: &Person) -> [&str; 1] {
+    [&person.name]
+}
+
+/// Lowercases and strips diacritics so "José" and "jose" index to the same term.
+fn normalize(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).flat_map(char::to_lowercase).collect()
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Default)]
+struct TenantIndex {
+    terms: BTreeMap<String, HashSet<u32>>,
+    documents: HashMap<u32, Vec<String>>,
+}
+
+impl TenantIndex {
+    fn add(&mut self, person: &Person) {
+        self.remove(person.id);
+        let mut tokens: Vec<String> = searchable_fields(person).iter().flat_map(|field| tokenize(field)).collect();
+        tokens.sort();
+        tokens.dedup();
+        for token in &tokens {
+            self.terms.entry(token.clone()).or_default().insert(person.id);
+        }
+        self.documents.insert(person.id, tokens);
+    }
+
+    fn remove(&mut self, id: u32) {
+        for token in self.documents.remove(&id).unwrap_or_default() {
+            if let Some(ids) = self.terms.get_mut(&token) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.terms.remove(&token);
+                }
+            }
+        }
+    }
+
+    /// Best score of every id matching `query`: exact term, then term prefix, then substring.
+    fn matches(&self, query: &str) -> HashMap<u32, u32> {
+        let mut scores = HashMap::new();
+        for (term, ids) in &self.terms {
+            let score = if term == query {
+                EXACT_SCORE
+            } else if term.starts_with(query) {
+                PREFIX_SCORE
+            } else if term.contains(query) {
+                SUBSTRING_SCORE
+            } else {
+                continue;
+            };
+            for id in ids {
+                let best = scores.entry(*id).or_insert(0);
+                *best = score.max(*best);
+            }
+        }
+        scores
+    }
+}
+
+/// Inverted index over the searchable fields of every person, per tenant.
+///
+/// The index is built from the store at startup and kept current by
+/// [`IndexedRepository`]; writes made by other replicas against a shared
+/// backend are only picked up after a restart.
+///
+/// A panic while the lock is held can at worst leave one person's entry stale,
+/// so a poisoned lock is taken over instead of failing every later search.
+#[derive(Default)]
+pub struct SearchIndex {
+    tenants: RwLock<HashMap<String, TenantIndex>>,
+}
+
+impl SearchIndex {
+    fn add(&self, tenant: &str, person: &Person) {
+        self.tenants.write().unwrap_or_else(PoisonError::into_inner).entry(tenant.to_string()).or_default().add(person);
+    }
+
+    fn remove(&self, tenant: &str, id: u32) {
+        if let Some(index) = self.tenants.write().unwrap_or_else(PoisonError::into_inner).get_mut(tenant) {
+            index.remove(id);
+        }
+    }
+
+    fn replace(&self, tenant: &str, persons: &[Person]) {
+        let mut index = TenantIndex::default();
+        for person in persons {
+            index.add(person);
+        }
+        self.tenants.write().unwrap_or_else(PoisonError::into_inner).insert(tenant.to_string(), index);
+    }
+
+    /// Ids matching any token of `query`, best first. A person's score is the
+    /// sum of its best match per query token, so matching more tokens ranks higher.
+    pub fn search(&self, tenant: &str, query: &str) -> Vec<(u32, u32)> {
+        let tenants = self.tenants.read().unwrap_or_else(PoisonError::into_inner);
+        let Some(index) = tenants.get(tenant) else {
+            return Vec::new();
+        };
+        let mut totals: HashMap<u32, u32> = HashMap::new();
+        for token in tokenize(query) {
+            for (id, score) in index.matches(&token) {
+                *totals.entry(id).or_insert(0) += score;
+            }
+        }
+        let mut ranked: Vec<(u32, u32)> = totals.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+/// Whether `query` contains anything searchable once normalized.
+pub fn is_searchable(query: &str) -> bool {
+    !tokenize(query).is_empty()
+}
+
+/// Wraps a repository so every successful write is mirrored into a [`SearchIndex`].
+pub struct IndexedRepository {
+    inner: Arc<dyn PersonRepository>,
+    index: Arc<SearchIndex>,
+}
+
+impl IndexedRepository {
+    pub async fn build(inner: Arc<dyn PersonRepository>, index: Arc<SearchIndex>) -> Result<Self, RepositoryError> {
+        for tenant in inner.tenants().await? {
+            let persons = inner.list(&tenant).await?;
+            index.replace(&tenant, &persons);
+        }
+        Ok(IndexedRepository { inner, index })
+    }
+}
+
+#[async_trait]
+impl PersonRepository for IndexedRepository {
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        self.inner.migrations().await
+    }
+
+    async fn check(&self) -> Vec<ComponentCheck> {
+        self.inner.check().await
+    }
+
+    fn lock_waits(&self) -> Option<LockStats> {
+        self.inner.lock_waits()
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.inner.close().await
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        self.inner.tenants().await
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        self.inner.list(tenant).await
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        self.inner.get(tenant, id).await
+    }
+
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        self.inner.find_by_uuid(tenant, uuid).await
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.inner.insert(tenant, person.clone()).await?;
+        self.index.add(tenant, &person);
+        Ok(())
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.insert_many(tenant, persons.clone()).await?;
+        for person in &persons {
+            self.index.add(tenant, person);
+        }
+        Ok(())
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        self.inner.update(tenant, person.clone()).await?;
+        self.index.add(tenant, &person);
+        Ok(())
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.update_many(tenant, persons.clone()).await?;
+        for person in &persons {
+            self.index.add(tenant, person);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        self.inner.delete(tenant, id).await?;
+        self.index.remove(tenant, id);
+        Ok(())
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let deleted = self.inner.delete_many(tenant, ids).await?;
+        for id in &deleted {
+            self.index.remove(tenant, *id);
+        }
+        Ok(deleted)
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        self.inner.replace_all(tenant, persons.clone()).await?;
+        self.index.replace(tenant, &persons);
+        Ok(())
+    }
+}
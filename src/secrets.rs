@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Settings that hold credentials. Besides the variable itself, each may come from
+/// the file named by its `_FILE` variant (Docker and Kubernetes secrets) or from Vault.
+const SECRETS: [&str; 10] = [
+    "API_KEYS", "DATABASE_URL", "FIELD_ENCRYPTION_KEYS", "GREETING_TEXT", "JWT_SECRET", "MONGODB_URI",
+    "OIDC_CLIENT_SECRET", "REDIS_URL", "REQUEST_SIGNING_SECRET", "SESSION_SECRET",
+];
+
+static RESOLVED: OnceLock<HashMap<&'static str, String>> = OnceLock::new();
+
+/// Like [`env::var`], but secrets are looked up as [`load`] resolved them.
+pub fn var(name: &str) -> Result<String, env::VarError> {
+    match RESOLVED.get() {
+        Some(resolved) if SECRETS.contains(&name) => resolved.get(name).cloned().ok_or(env::VarError::NotPresent),
+        _ => env::var(name),
+    }
+}
+
+/// The variable itself, else the contents of the file its `_FILE` variant names.
+fn from_env_or_file(name: &str) -> Result<Option<String>, String> {
+    if let Ok(value) = env::var(name) {
+        return Ok(Some(value));
+    }
+    let file_variable = format!("{}_FILE", name);
+    let Ok(path) = env::var(&file_variable) else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(&path).map_err(|e| format!("{} {}: {}", file_variable, path, e))?;
+    // Editors and `echo` leave a trailing newline that isn't part of the secret.
+    Ok(Some(contents.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Resolves every secret once at startup, so a missing file or an unreachable Vault
+/// stops the service instead of surfacing later as an unset setting.
+pub async fn load() -> Result<(), String> {
+    let mut resolved = HashMap::new();
+    let mut missing = Vec::new();
+    for name in SECRETS {
+        match from_env_or_file(name)? {
+            Some(value) => {
+                resolved.insert(name, value);
+            }
+            None => missing.push(name),
+        }
+    }
+    #[cfg(feature = "vault")]
+    if let Some(vault) = vault::Vault::from_env()? {
+        let mut stored = vault.read().await?;
+        for name in missing {
+            if let Some(value) = stored.remove(name) {
+                resolved.insert(name, value);
+            }
+        }
+    }
+    #[cfg(not(feature = "vault"))]
+    let _ = missing;
+    // Only `main` loads, once.
+    let _ = RESOLVED.set(resolved);
+    Ok(())
+}
+
+#[cfg(feature = "vault")]
+mod vault {
+    use std::collections::HashMap;
+    use std::env;
+    use std::time::Duration;
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    /// A HashiCorp Vault secret at `VAULT_SECRET_PATH` (e.g. `secret/data/rocket-app` for KV
+    /// version 2) on `VAULT_ADDR`, read with `VAULT_TOKEN`; its keys are the setting names.
+    pub struct Vault {
+        url: String,
+        token: String,
+    }
+
+    /// How long startup waits on Vault before giving up.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[derive(Deserialize)]
+    struct Response {
+        data: HashMap<String, Value>,
+    }
+
+    impl Vault {
+        pub fn from_env() -> Result<Option<Self>, String> {
+            let Ok(address) = env::var("VAULT_ADDR") else {
+                return Ok(None);
+            };
+            let setting = |name: &str| super::from_env_or_file(name)?.ok_or_else(|| format!("{} is required with VAULT_ADDR", name));
+            let (token, path) = (setting("VAULT_TOKEN")?, setting("VAULT_SECRET_PATH")?);
+            let url = format!("{}/v1/{}", address.trim_end_matches('/'), path.trim_start_matches('/'));
+            Ok(Some(Vault { url, token }))
+        }
+
+        pub async fn read(&self) -> Result<HashMap<String, String>, String> {
+            let failed = |e: reqwest::Error| format!("reading {} from Vault: {}", self.url, e);
+            let http = reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .build()
+                .map_err(failed)?;
+            let response: Response = http
+                .get(&self.url)
+                .header("X-Vault-Token", &self.token)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(failed)?
+                .json()
+                .await
+                .map_err(failed)?;
+            let mut data = response.data;
+            // KV version 2 nests the values, next to their metadata, one level deeper.
+            if let (Some(Value::Object(nested)), true) = (data.get("data"), data.contains_key("metadata")) {
+                data = nested.clone().into_iter().collect();
+            }
+            Ok(data.into_iter()
+                .filter_map(|(name, value)| match value {
+                    Value::String(value) => Some((name, value)),
+                    Value::Null | Value::Array(_) | Value::Object(_) => None,
+                    value => Some((name, value.to_string())),
+                })
+                .collect())
+        }
+    }
+}
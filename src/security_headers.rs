@@ -0,0 +1,49 @@
+use std::env;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::Request;
+use rocket::response::Response;
+use crate::assets::STATIC_PATH;
+
+/// The API serves no scripts, styles or frames, so nothing needs loading.
+const DEFAULT_CSP: &str = "default-src 'none'; frame-ancestors 'none'";
+/// The frontend under [`STATIC_PATH`] loads its own files and calls the API.
+const STATIC_CSP: &str = "default-src 'self'; frame-ancestors 'none'";
+const HSTS: &str = "max-age=31536000; includeSubDomains";
+
+/// Hardening headers on every response. On unless `SECURITY_HEADERS=false`, e.g.
+/// for local development; `CONTENT_SECURITY_POLICY` replaces the default policies.
+pub struct SecurityHeaders {
+    content_security_policy: Option<String>,
+}
+
+impl SecurityHeaders {
+    pub fn from_env() -> Option<Self> {
+        if env::var("SECURITY_HEADERS").is_ok_and(|v| v == "false" || v == "0") {
+            return None;
+        }
+        Some(SecurityHeaders { content_security_policy: env::var("CONTENT_SECURITY_POLICY").ok() })
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info { name: "Security Headers", kind: Kind::Response }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Browsers only honour this over HTTPS, so it is harmless behind plain HTTP.
+        response.set_raw_header("Strict-Transport-Security", HSTS);
+        response.set_raw_header("X-Content-Type-Options", "nosniff");
+        response.set_raw_header("X-Frame-Options", "DENY");
+        response.set_raw_header("Referrer-Policy", "no-referrer");
+        let policy = match &self.content_security_policy {
+            Some(policy) => policy.as_str(),
+            None if request.uri().path().starts_with(STATIC_PATH) => STATIC_CSP,
+            None => DEFAULT_CSP,
+        };
+        if !policy.is_empty() {
+            response.set_raw_header("Content-Security-Policy", policy.to_string());
+        }
+    }
+}
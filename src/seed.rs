@@ -0,0 +1,44 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use crate::person::{self, Person};
+use crate::settings::Settings;
+use crate::validation::{NewPerson, Validate};
+
+/// What the in-memory store starts with when it has no snapshot: nothing with `--no-seed`,
+/// the persons in `seed_file` (`SEED_FILE`) if set, or else the two sample persons unless
+/// `sample_persons` is off.
+pub fn initial_persons(settings: &Settings, no_seed: bool) -> Result<Vec<Person>, String> {
+    match no_seed {
+        true => Ok(Vec::new()),
+        false => persons(settings.seed_file.as_deref(), settings.sample_persons),
+    }
+}
+
+/// The persons in `seed_file`, read again on every call, or the two sample persons if `samples`.
+pub fn persons(seed_file: Option<&Path>, samples: bool) -> Result<Vec<Person>, String> {
+    match seed_file {
+        Some(path) => load(path),
+        None if samples => Ok(person::create_person_collection()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Reads a JSON array of persons and checks them as `POST /persons` does; persons without
+/// an id are numbered after the highest one in the file.
+pub fn load(path: &Path) -> Result<Vec<Person>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let persons: Vec<NewPerson> = serde_json::from_str(&contents).map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+    let mut persons = persons.validate().map_err(|errors| format!("invalid persons in {}: {}", path.display(), errors.summary()))?;
+
+    let mut ids = HashSet::new();
+    if let Some(id) = persons.iter().map(|p| p.id).filter(|&id| id != 0).find(|&id| !ids.insert(id)) {
+        return Err(format!("invalid persons in {}: id {} appears more than once", path.display(), id));
+    }
+    let highest = ids.into_iter().max().unwrap_or(0);
+    for (id, person) in (highest + 1..).zip(persons.iter_mut().filter(|p| p.id == 0)) {
+        person.id = id;
+    }
+    persons.iter_mut().for_each(Person::renumber_addresses);
+    Ok(persons)
+}
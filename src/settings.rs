@@ -0,0 +1,235 @@
+use std::env;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use chrono_tz::Tz;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::{Figment, Profile};
+use rocket::Config;
+use serde::{Deserialize, Serialize};
+use crate::flags::Flags;
+use crate::import;
+use crate::logging;
+
+/// Settings of ours, read from the same sources as Rocket's own; see [`load`].
+#[derive(Deserialize, Serialize)]
+pub struct Settings {
+    pub greeting_text: String,
+    pub storage_backend: String,
+    /// Persons for the in-memory store to start with; see [`seed`](crate::seed).
+    pub seed_file: Option<PathBuf>,
+    /// Whether the in-memory store starts with the sample persons when there's no `seed_file`.
+    pub sample_persons: bool,
+    /// Whether 5xx responses say what went wrong, rather than only the status's reason.
+    pub error_detail: bool,
+    /// `CORS_ALLOWED_ORIGINS`; see [`Cors`](crate::cors::Cors).
+    pub cors_allowed_origins: String,
+    /// The zone `/` and `/api/time` show besides UTC when not asked for one (`TZ_DEFAULT`).
+    pub tz_default: Option<Tz>,
+    /// Where the page templates are; relative to the working directory.
+    pub template_dir: PathBuf,
+    /// Files served under `/static`, unless they're embedded; see [`assets`](crate::assets).
+    pub static_dir: PathBuf,
+    /// Replaces the `json` limit when set.
+    pub max_body_kib: Option<u64>,
+    pub rate_limit_rps: Option<f64>,
+    pub rate_limit_burst: Option<f64>,
+    /// Requests handled at once before others get a `503`; see [`concurrency`](crate::concurrency).
+    pub max_concurrent_requests: Option<usize>,
+    /// Apart from Rocket's own `log_level`, which knows other levels.
+    pub log: LogSettings,
+    pub flags: Flags,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct LogSettings {
+    pub level: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            greeting_text: "Hi!".to_string(),
+            storage_backend: "memory".to_string(),
+            seed_file: None,
+            sample_persons: true,
+            error_detail: false,
+            cors_allowed_origins: String::new(),
+            tz_default: None,
+            template_dir: "templates".into(),
+            static_dir: "static".into(),
+            max_body_kib: None,
+            rate_limit_rps: None,
+            rate_limit_burst: None,
+            max_concurrent_requests: None,
+            log: LogSettings::default(),
+            flags: Flags::default(),
+        }
+    }
+}
+
+/// The deployment `APP_ENV` names, which picks the defaults below and, unless
+/// `ROCKET_PROFILE` says otherwise, the `Rocket.toml` profile.
+#[derive(Clone, Copy)]
+pub enum AppEnv {
+    Dev,
+    Staging,
+    Prod,
+}
+
+/// What an [`AppEnv`] changes from the defaults; every source still overrides it.
+#[derive(Serialize)]
+struct EnvDefaults {
+    log: LogSettings,
+    sample_persons: bool,
+    error_detail: bool,
+    cors_allowed_origins: &'static str,
+}
+
+impl AppEnv {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match env::var("APP_ENV").as_deref() {
+            Ok("dev") => Ok(Some(AppEnv::Dev)),
+            Ok("staging") => Ok(Some(AppEnv::Staging)),
+            Ok("prod") => Ok(Some(AppEnv::Prod)),
+            Ok(name) => Err(format!("APP_ENV must be dev, staging or prod, not '{}'", name)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn profile(self) -> Profile {
+        match self {
+            AppEnv::Dev => Profile::const_new("dev"),
+            AppEnv::Staging => Profile::const_new("staging"),
+            AppEnv::Prod => Profile::const_new("prod"),
+        }
+    }
+
+    /// Rocket takes `dev` and `prod` for its old names of these and would warn of them.
+    fn rocket_profile(self) -> Profile {
+        match self {
+            AppEnv::Dev => Config::DEBUG_PROFILE,
+            AppEnv::Staging | AppEnv::Prod => Config::RELEASE_PROFILE,
+        }
+    }
+
+    /// Chatty, open to any origin and explaining errors in dev; samples everywhere but in prod.
+    fn defaults(self) -> EnvDefaults {
+        let level = match self {
+            AppEnv::Dev => "debug",
+            AppEnv::Staging | AppEnv::Prod => "info",
+        };
+        EnvDefaults {
+            log: LogSettings { level: Some(level.to_string()) },
+            sample_persons: !matches!(self, AppEnv::Prod),
+            error_detail: matches!(self, AppEnv::Dev),
+            cors_allowed_origins: match self {
+                AppEnv::Dev => "*",
+                AppEnv::Staging | AppEnv::Prod => "",
+            },
+        }
+    }
+}
+
+/// Command-line flags, over every other source.
+#[derive(Default, Serialize)]
+pub struct Overrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Not a setting: the store starts empty instead of seeded.
+    #[serde(skip)]
+    pub no_seed: bool,
+}
+
+/// Variables read without Rocket's `ROCKET_` prefix, as they were before `Rocket.toml`.
+const PLAIN_VARIABLES: [&str; 11] = [
+    "STORAGE_BACKEND", "SEED_FILE", "SAMPLE_PERSONS", "ERROR_DETAIL", "CORS_ALLOWED_ORIGINS", "TZ_DEFAULT", "MAX_BODY_KIB",
+    "RATE_LIMIT_RPS", "RATE_LIMIT_BURST", "MAX_CONCURRENT_REQUESTS", "WORKERS",
+];
+/// Seconds a shutdown waits for requests in flight, as Rocket's `shutdown.grace` (default 2).
+const SHUTDOWN_GRACE: &str = "SHUTDOWN_GRACE_SECS";
+/// Seconds an idle connection is kept open, as Rocket's `keep_alive` (default 5; 0 turns it off).
+const KEEP_ALIVE: &str = "KEEP_ALIVE_SECS";
+
+/// Rocket's defaults and ours, then those of `app_env`, then `Rocket.toml` (or the file
+/// `ROCKET_CONFIG` names) in the `ROCKET_PROFILE` profile (or else the `app_env` one, or
+/// `debug` or `release` by build), then `ROCKET_` variables,
+/// then [`PLAIN_VARIABLES`], `BIND_ADDRESS`, `PORT`, `LOG_LEVEL`, [`SHUTDOWN_GRACE`],
+/// [`KEEP_ALIVE`] and `FEATURE_` variables, then `overrides`.
+fn figment(overrides: &Overrides, app_env: Option<AppEnv>) -> Figment {
+    let uploads = import::UPLOAD_LIMIT_MIB.mebibytes();
+    let profile = match (Profile::from_env("ROCKET_PROFILE"), app_env) {
+        (Some(profile), _) => profile,
+        (None, Some(app_env)) => app_env.profile(),
+        (None, None) => Config::DEFAULT_PROFILE,
+    };
+    let figment = Figment::from(Config::default()).merge(Serialized::defaults(Settings::default()));
+    let figment = match app_env {
+        Some(app_env) => figment.merge(Serialized::defaults(app_env.defaults())),
+        None => figment,
+    };
+    figment
+        .merge(Serialized::default("address", IpAddr::V4(Ipv4Addr::UNSPECIFIED)))
+        .merge(Serialized::default("port", 8080))
+        .merge(Serialized::default("limits", Limits::default().limit("data-form", uploads).limit("file", uploads)))
+        .merge(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+        .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global())
+        .merge(Env::raw().only(&PLAIN_VARIABLES).global())
+        .merge(Env::raw().only(&["BIND_ADDRESS"]).map(|_| "address".into()).global())
+        .merge(Env::raw().only(&["PORT"]).global())
+        .merge(Env::raw().only(&["LOG_LEVEL"]).map(|_| "log.level".into()).global())
+        .merge(Env::raw().only(&[SHUTDOWN_GRACE]).map(|_| "shutdown.grace".into()).global())
+        .merge(Env::raw().only(&[KEEP_ALIVE]).map(|_| "keep_alive".into()).global())
+        .merge(Env::prefixed("FEATURE_").map(|name| format!("flags.{}", name.as_str().to_ascii_lowercase()).into()).global())
+        .merge(Serialized::globals(overrides))
+        .select(profile)
+}
+
+/// Rocket's configuration and ours, merged and checked. The errors name each bad key and
+/// where its value came from.
+pub fn load(overrides: &Overrides) -> Result<(Config, Settings), String> {
+    // Figment would name these by the keys they fill in, not by the variables.
+    if let Ok(address) = env::var("BIND_ADDRESS") {
+        address.parse::<IpAddr>().map_err(|_| format!("BIND_ADDRESS must be an IP address such as 0.0.0.0, not '{}'", address))?;
+    }
+    if let Ok(port) = env::var("PORT") {
+        port.parse::<u16>().map_err(|_| format!("PORT must be a number from 0 to 65535, not '{}'", port))?;
+    }
+    let app_env = AppEnv::from_env()?;
+    let figment = figment(overrides, app_env);
+    let describe = |e: rocket::figment::Error| e.into_iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; ");
+    let mut config: Config = figment.extract().map_err(describe)?;
+    // Rocket checks release builds more strictly, such as for a `secret_key`.
+    config.profile = match app_env {
+        Some(app_env) if *figment.profile() == app_env.profile() => app_env.rocket_profile(),
+        _ => figment.profile().clone(),
+    };
+    let settings: Settings = figment.extract().map_err(describe)?;
+    // Tokio can't start a runtime without them.
+    if config.workers == 0 || config.max_blocking == 0 {
+        return Err("workers and max_blocking must be at least 1".to_string());
+    }
+    if settings.max_concurrent_requests == Some(0) {
+        return Err("max_concurrent_requests must be at least 1".to_string());
+    }
+    if settings.greeting_text.trim().is_empty() {
+        return Err("greeting_text must not be empty".to_string());
+    }
+    if let Some(level) = &settings.log.level {
+        logging::parse_level(level)?;
+    }
+    if settings.rate_limit_rps.is_some_and(|rate| rate <= 0.0) {
+        return Err("rate_limit_rps must be more than 0".to_string());
+    }
+    if settings.rate_limit_burst.is_some_and(|burst| burst < 1.0) {
+        return Err("rate_limit_burst must be at least 1".to_string());
+    }
+    match settings.max_body_kib {
+        Some(0) => return Err("max_body_kib must be at least 1".to_string()),
+        Some(kib) => config.limits = config.limits.limit("json", kib.kibibytes()),
+        None => {}
+    }
+    Ok((config, settings))
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rocket::data::{self, Data, FromData, Limits};
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::tokio::io::{AsyncRead, AsyncReadExt};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+use crate::auth::AuthError;
+use crate::secrets;
+use crate::AppState;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+const DEFAULT_TOLERANCE_SECS: i64 = 300;
+
+/// Writes must carry an HMAC-SHA256 of `"{timestamp}\n{method}\n{path and query}\n{body}"`
+/// keyed with `REQUEST_SIGNING_SECRET`, hex-encoded in [`SIGNATURE_HEADER`], and the Unix
+/// time it was made in [`TIMESTAMP_HEADER`]. Timestamps further than
+/// `REQUEST_SIGNING_TOLERANCE_SECS` from now are refused, and so is any signature seen
+/// within that window before.
+pub struct RequestSigning {
+    secret: Vec<u8>,
+    tolerance: i64,
+    /// Signatures accepted lately, with their timestamps.
+    seen: Mutex<HashMap<Vec<u8>, i64>>,
+}
+
+impl RequestSigning {
+    pub fn from_env() -> Option<Self> {
+        let secret = secrets::var("REQUEST_SIGNING_SECRET").ok().filter(|secret| !secret.is_empty())?;
+        let tolerance = env::var("REQUEST_SIGNING_TOLERANCE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TOLERANCE_SECS);
+        Some(RequestSigning { secret: secret.into_bytes(), tolerance, seen: Mutex::new(HashMap::new()) })
+    }
+
+    fn begin(&self, request: &Request<'_>) -> Result<Pending<'_>, AuthError> {
+        let headers = request.headers();
+        let (Some(signature), Some(timestamp)) = (headers.get_one(SIGNATURE_HEADER), headers.get_one(TIMESTAMP_HEADER)) else {
+            return Err(AuthError::MissingSignature);
+        };
+        let timestamp: i64 = timestamp.trim().parse().map_err(|_| AuthError::StaleSignature)?;
+        if (Utc::now().timestamp() - timestamp).abs() > self.tolerance {
+            return Err(AuthError::StaleSignature);
+        }
+        let signature = signature.trim();
+        let signature = hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature)).map_err(|_| AuthError::InvalidSignature)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(format!("{}\n{}\n{}\n", timestamp, request.method(), request.uri()).as_bytes());
+        Ok(Pending { signing: self, mac, signature, timestamp })
+    }
+}
+
+/// A signature being checked while the body streams through.
+struct Pending<'a> {
+    signing: &'a RequestSigning,
+    mac: Hmac<Sha256>,
+    signature: Vec<u8>,
+    timestamp: i64,
+}
+
+impl Pending<'_> {
+    fn finish(self) -> Result<(), AuthError> {
+        self.mac.verify_slice(&self.signature).map_err(|_| AuthError::InvalidSignature)?;
+        let now = Utc::now().timestamp();
+        let tolerance = self.signing.tolerance;
+        let mut seen = self.signing.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.retain(|_, at| (now - *at).abs() <= tolerance);
+        if seen.insert(self.signature, self.timestamp).is_some() {
+            return Err(AuthError::ReplayedSignature);
+        }
+        Ok(())
+    }
+}
+
+fn signing<'r>(request: &'r Request<'_>) -> Option<&'r RequestSigning> {
+    request.rocket().state::<AppState>().and_then(|state| state.signing.as_ref())
+}
+
+fn record(request: &Request<'_>, outcome: Result<(), AuthError>) -> Result<(), AuthError> {
+    outcome.inspect_err(|error| {
+        request.local_cache(|| Some(*error));
+    })
+}
+
+/// Checks `body` against the request's signature when signing is on; refusals are
+/// cached for the `401` catcher.
+pub fn verify(request: &Request<'_>, body: &[u8]) -> Result<(), AuthError> {
+    let Some(signing) = signing(request) else {
+        return Ok(());
+    };
+    record(request, signing.begin(request).and_then(|mut pending| {
+        pending.mac.update(body);
+        pending.finish()
+    }))
+}
+
+/// [`verify`] for a body read from `reader`, which is left unread when signing is off.
+pub async fn verify_stream(request: &Request<'_>, mut reader: impl AsyncRead + Unpin) -> io::Result<Result<(), AuthError>> {
+    let Some(signing) = signing(request) else {
+        return Ok(Ok(()));
+    };
+    let mut pending = match signing.begin(request) {
+        Ok(pending) => pending,
+        Err(error) => return Ok(record(request, Err(error))),
+    };
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut chunk).await? {
+            0 => break,
+            read => pending.mac.update(&chunk[..read]),
+        }
+    }
+    Ok(record(request, pending.finish()))
+}
+
+/// A JSON body, like [`Json`](rocket::serde::json::Json), that must be signed when signing is on.
+pub struct SignedJson<T>(pub T);
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for SignedJson<T> {
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let body = match data.open(limit).into_bytes().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Outcome::Error((Status::PayloadTooLarge, ())),
+            Err(_) => return Outcome::Error((Status::BadRequest, ())),
+        };
+        if verify(request, &body).is_err() {
+            return Outcome::Error((Status::Unauthorized, ()));
+        }
+        // The same statuses as `Json` answers.
+        match serde_json::from_slice(&body) {
+            Ok(value) => Outcome::Success(SignedJson(value)),
+            Err(e) if e.is_data() => Outcome::Error((Status::UnprocessableEntity, ())),
+            Err(_) => Outcome::Error((Status::BadRequest, ())),
+        }
+    }
+}
+
+/// Guard of writes without a body, whose signature covers an empty one.
+pub struct Signed;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Signed {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match verify(request, &[]) {
+            Ok(()) => Outcome::Success(Signed),
+            Err(error) => Outcome::Error((Status::Unauthorized, error)),
+        }
+    }
+}
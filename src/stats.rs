@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::person::Person;
+
+const BUCKET_WIDTH: u8 = 10;
+
+#[derive(Serialize)]
+pub struct AgeBucket {
+    pub from: u8,
+    pub to: u8,
+    pub count: usize,
+}
+
+/// Aggregates over a set of persons; the optional fields are `null` when it is empty.
+#[derive(Serialize)]
+pub struct PersonStats {
+    pub count: usize,
+    pub min_age: Option<u8>,
+    pub max_age: Option<u8>,
+    pub average_age: Option<f64>,
+    /// Ages in buckets of ten years, from the youngest bucket to the oldest, skipping empty ones.
+    pub age_histogram: Vec<AgeBucket>,
+    pub oldest_date: Option<DateTime<Utc>>,
+    pub newest_date: Option<DateTime<Utc>>,
+}
+
+impl PersonStats {
+    pub fn compute(persons: &[Person]) -> Self {
+        let mut counts = [0usize; (u8::MAX / BUCKET_WIDTH) as usize + 1];
+        for person in persons {
+            counts[(person.age / BUCKET_WIDTH) as usize] += 1;
+        }
+        let age_histogram = counts.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| {
+                let from = bucket as u8 * BUCKET_WIDTH;
+                AgeBucket { from, to: from.saturating_add(BUCKET_WIDTH - 1), count: *count }
+            })
+            .collect();
+        let total_age: u64 = persons.iter().map(|p| u64::from(p.age)).sum();
+        PersonStats {
+            count: persons.len(),
+            min_age: persons.iter().map(|p| p.age).min(),
+            max_age: persons.iter().map(|p| p.age).max(),
+            average_age: (!persons.is_empty()).then(|| total_age as f64 / persons.len() as f64),
+            age_histogram,
+            oldest_date: persons.iter().map(|p| p.date).min(),
+            newest_date: persons.iter().map(|p| p.date).max(),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use rocket::async_trait;
+use rocket::fairing::{AdHoc, Fairing, Info, Kind};
+use rocket::http::HeaderMap;
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::route::{Handler, Outcome};
+use rocket::{tokio, Data, Route};
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::{Layer, Registry};
+use uuid::Uuid;
+use crate::person::Person;
+use crate::repository::{AppliedMigration, ComponentCheck, LockStats, PersonRepository, RepositoryError};
+use crate::request_id;
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Exports spans over OTLP/HTTP to `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`), such as Jaeger's or Tempo's port 4318, under
+/// `OTEL_SERVICE_NAME`. Without an endpoint nothing is traced.
+pub fn layer() -> Result<Option<impl Layer<Registry>>, String> {
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() && env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_err() {
+        return Ok(None);
+    }
+    let exporter = SpanExporter::builder().with_http().build().map_err(|e| format!("OTLP exporter: {}", e))?;
+    let resource = match env::var("OTEL_SERVICE_NAME") {
+        Ok(_) => Resource::builder().build(),
+        Err(_) => Resource::builder().with_service_name(env!("CARGO_PKG_NAME")).build(),
+    };
+    let provider = SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource).build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    // Only `logging::init` calls this, once.
+    let _ = PROVIDER.set(provider);
+    // Our spans only; Rocket's and its dependencies' logs would become noise on them.
+    let filter = Targets::new().with_target(env!("CARGO_CRATE_NAME"), LevelFilter::INFO);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter)))
+}
+
+pub fn enabled() -> bool {
+    PROVIDER.get().is_some()
+}
+
+/// Sends the spans still buffered before the service exits.
+pub fn shutdown() -> AdHoc {
+    AdHoc::on_shutdown("OpenTelemetry", |_| Box::pin(async {
+        if let Some(provider) = PROVIDER.get().cloned() {
+            // The exporter's HTTP client blocks.
+            let _ = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+        }
+    }))
+}
+
+struct Headers<'a>(&'a HeaderMap<'a>);
+
+impl Extractor for Headers<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        // The map lends out its own names, borrowed.
+        self.0.iter()
+            .filter_map(|header| match header.name.into_cow() {
+                Cow::Borrowed(name) => Some(name),
+                Cow::Owned(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// The request's span, taken when the response ends it.
+#[derive(Default)]
+struct RequestSpan(Mutex<Option<Span>>);
+
+impl RequestSpan {
+    fn current(&self) -> Span {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).clone().unwrap_or_else(Span::none)
+    }
+}
+
+/// Opens a server span for every request, continuing the trace of an incoming W3C
+/// `traceparent`, and closes it with the route and status once the response is ready.
+pub struct TraceRequests;
+
+#[rocket::async_trait]
+impl Fairing for TraceRequests {
+    fn info(&self) -> Info {
+        Info { name: "OpenTelemetry", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let parent = global::get_text_map_propagator(|propagator| propagator.extract(&Headers(request.headers())));
+        let span = tracing::info_span!(
+            "request",
+            otel.name = %request.method(),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.request.method = %request.method(),
+            url.path = request.uri().path().as_str(),
+            http.route = Empty,
+            http.response.status_code = Empty,
+            request_id = request_id::of(request),
+        );
+        let _ = span.set_parent(parent);
+        request.local_cache(|| RequestSpan(Mutex::new(Some(span))));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let span = request.local_cache(RequestSpan::default).0.lock().unwrap_or_else(PoisonError::into_inner).take();
+        let Some(span) = span else {
+            return;
+        };
+        if let Some(route) = request.route() {
+            // Renaming through `otel.name` only works until a child span has started it.
+            span.context().span().update_name(format!("{} {}", request.method(), route.uri.path()));
+            span.record("http.route", route.uri.path().to_string());
+        }
+        span.record("http.response.status_code", response.status().code);
+        if response.status().code >= 500 {
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let span = request.local_cache(RequestSpan::default).current();
+        self.0.handle(request, data).instrument(span).await
+    }
+}
+
+/// Runs the handlers of `routes` inside their request's span, so the spans opened
+/// while handling it, such as the repository's, become its children.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
+
+/// Opens a span around every storage call.
+pub struct TracedRepository {
+    inner: Arc<dyn PersonRepository>,
+}
+
+impl TracedRepository {
+    pub fn new(inner: Arc<dyn PersonRepository>) -> Self {
+        TracedRepository { inner }
+    }
+}
+
+#[async_trait]
+impl PersonRepository for TracedRepository {
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        self.inner.migrations().instrument(tracing::info_span!("repository.migrations")).await
+    }
+
+    async fn check(&self) -> Vec<ComponentCheck> {
+        self.inner.check().instrument(tracing::info_span!("repository.check")).await
+    }
+
+    fn lock_waits(&self) -> Option<LockStats> {
+        self.inner.lock_waits()
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.inner.close().instrument(tracing::info_span!("repository.close")).await
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        self.inner.tenants().instrument(tracing::info_span!("repository.tenants")).await
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        self.inner.list(tenant).instrument(tracing::info_span!("repository.list", tenant)).await
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        self.inner.get(tenant, id).instrument(tracing::info_span!("repository.get", tenant, id)).await
+    }
+
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        self.inner.find_by_uuid(tenant, uuid).instrument(tracing::info_span!("repository.find_by_uuid", tenant, %uuid)).await
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let span = tracing::info_span!("repository.insert", tenant, id = person.id);
+        self.inner.insert(tenant, person).instrument(span).await
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let span = tracing::info_span!("repository.insert_many", tenant, count = persons.len());
+        self.inner.insert_many(tenant, persons).instrument(span).await
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let span = tracing::info_span!("repository.update", tenant, id = person.id);
+        self.inner.update(tenant, person).instrument(span).await
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let span = tracing::info_span!("repository.update_many", tenant, count = persons.len());
+        self.inner.update_many(tenant, persons).instrument(span).await
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        self.inner.delete(tenant, id).instrument(tracing::info_span!("repository.delete", tenant, id)).await
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let span = tracing::info_span!("repository.delete_many", tenant, count = ids.len());
+        self.inner.delete_many(tenant, ids).instrument(span).await
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let span = tracing::info_span!("repository.replace_all", tenant, count = persons.len());
+        self.inner.replace_all(tenant, persons).instrument(span).await
+    }
+}
@@ -0,0 +1,39 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use crate::AppState;
+
+pub const DEFAULT_TENANT: &str = "default";
+pub const TENANT_HEADER: &str = "X-Tenant-Id";
+
+/// The tenant whose partition a request operates on. With multi-tenancy
+/// disabled every request resolves to [`DEFAULT_TENANT`].
+pub struct Tenant(pub String);
+
+#[derive(Debug)]
+pub enum TenantError {
+    Missing,
+    Invalid,
+}
+
+pub fn is_valid(tenant: &str) -> bool {
+    !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Tenant {
+    type Error = TenantError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let multi_tenant = request.rocket().state::<AppState>().is_some_and(|state| state.multi_tenant);
+        if !multi_tenant {
+            return Outcome::Success(Tenant(DEFAULT_TENANT.to_string()));
+        }
+        match request.headers().get_one(TENANT_HEADER) {
+            Some(tenant) if is_valid(tenant) => Outcome::Success(Tenant(tenant.to_string())),
+            Some(_) => Outcome::Error((Status::BadRequest, TenantError::Invalid)),
+            None => Outcome::Error((Status::BadRequest, TenantError::Missing)),
+        }
+    }
+}
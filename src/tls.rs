@@ -0,0 +1,92 @@
+use std::env;
+use std::path::Path;
+use rocket::config::TlsConfig;
+use rocket::fairing::AdHoc;
+use rocket::http::Method;
+use rocket::response::Redirect;
+use rocket::route::{Handler, Outcome};
+use rocket::{tokio, Config, Data, Request, Route};
+use crate::errors::ApiError;
+
+/// Certificate chain and private key (PEM) from `TLS_CERT_PATH` and `TLS_KEY_PATH`;
+/// `None` when neither is set, so the service speaks plain HTTP.
+pub fn from_env() -> Result<Option<TlsConfig>, String> {
+    let (cert, key) = match (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        (Err(_), Err(_)) => return Ok(None),
+        _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string()),
+    };
+    for path in [&cert, &key] {
+        if !Path::new(path).is_file() {
+            return Err(format!("{} is not a readable file", path));
+        }
+    }
+    let tls = TlsConfig::from_paths(cert, key);
+    #[cfg(feature = "mtls")]
+    let tls = match client_ca_from_env()? {
+        Some(mutual) => tls.with_mutual(mutual),
+        None => tls,
+    };
+    Ok(Some(tls))
+}
+
+/// Client certificates are asked for once `TLS_CLIENT_CA_PATH` names the PEM bundle of
+/// CAs that may issue them; connections without one are refused unless
+/// `TLS_CLIENT_CERT_OPTIONAL` is set.
+#[cfg(feature = "mtls")]
+fn client_ca_from_env() -> Result<Option<rocket::config::MutualTls>, String> {
+    let Ok(ca) = env::var("TLS_CLIENT_CA_PATH") else {
+        return Ok(None);
+    };
+    if !Path::new(&ca).is_file() {
+        return Err(format!("{} is not a readable file", ca));
+    }
+    let optional = env::var("TLS_CLIENT_CERT_OPTIONAL").is_ok_and(|v| v == "true" || v == "1");
+    Ok(Some(rocket::config::MutualTls::from_path(ca).mandatory(!optional)))
+}
+
+/// Every method a client may send, each routed to [`ToHttps`].
+const METHODS: [Method; 9] = [
+    Method::Get, Method::Head, Method::Post, Method::Put, Method::Patch,
+    Method::Delete, Method::Options, Method::Trace, Method::Connect,
+];
+
+/// With TLS configured and `HTTP_REDIRECT_PORT` set, a second, plain HTTP server
+/// on that port sends every request to the same URL over HTTPS.
+pub fn redirect_from_env(https: &Config) -> Option<AdHoc> {
+    https.tls.as_ref()?;
+    let port = env::var("HTTP_REDIRECT_PORT").ok()?.parse().ok()?;
+    let config = Config { address: https.address, port, secret_key: https.secret_key.clone(), ..Config::default() };
+    let https_port = https.port;
+    Some(AdHoc::on_liftoff("HTTP Redirect", move |_| Box::pin(async move {
+        let routes: Vec<Route> = METHODS.iter()
+            .map(|method| Route::new(*method, "/<_..>", ToHttps { port: https_port }))
+            .collect();
+        let server = rocket::custom(config).mount("/", routes);
+        tokio::spawn(async move {
+            if let Err(e) = server.launch().await {
+                error!("HTTP redirect server failed: {}", e);
+            }
+        });
+    })))
+}
+
+#[derive(Clone)]
+struct ToHttps {
+    port: u16,
+}
+
+#[rocket::async_trait]
+impl Handler for ToHttps {
+    async fn handle<'r>(&self, request: &'r Request<'_>, _: Data<'r>) -> Outcome<'r> {
+        let Some(host) = request.host() else {
+            return Outcome::from(request, ApiError::BadRequest("the request has no Host header".to_string()));
+        };
+        let authority = match self.port {
+            443 => host.domain().to_string(),
+            port => format!("{}:{}", host.domain(), port),
+        };
+        // Permanent and method-preserving, so clients retry writes as they were.
+        Outcome::from(request, Redirect::permanent(format!("https://{}{}", authority, request.uri())))
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use rocket::async_trait;
+use rocket::tokio::sync::Mutex;
+use uuid::Uuid;
+use crate::person::Person;
+use crate::repository::{AppliedMigration, ComponentCheck, LockStats, PersonRepository, RepositoryError};
+
+/// The first person of `written` whose name, ignoring case, is already used by
+/// another person, as the id of that other person. `existing` persons that are
+/// rewritten are judged by their new version; soft-deleted persons don't hold a name.
+fn clash(existing: &[Person], written: &[Person]) -> Option<u32> {
+    let key = |person: &Person| person.name.to_lowercase();
+    let mut names: HashMap<String, u32> = existing.iter()
+        .filter(|p| p.deleted_at.is_none() && !written.iter().any(|w| w.id == p.id))
+        .map(|p| (key(p), p.id))
+        .collect();
+    for person in written.iter().filter(|p| p.deleted_at.is_none()) {
+        match names.get(&key(person)) {
+            Some(id) if *id != person.id => return Some(*id),
+            _ => {
+                names.insert(key(person), person.id);
+            }
+        }
+    }
+    None
+}
+
+/// Enforces case-insensitive unique names, enabled with `UNIQUE_NAMES`. Writes
+/// are serialized so the check and the write see the same collection; replicas
+/// sharing a backend don't see each other's lock.
+pub struct UniqueNames {
+    inner: Arc<dyn PersonRepository>,
+    writes: Mutex<()>,
+}
+
+impl UniqueNames {
+    pub fn new(inner: Arc<dyn PersonRepository>) -> Self {
+        UniqueNames { inner, writes: Mutex::new(()) }
+    }
+
+    async fn check(&self, tenant: &str, written: &[Person]) -> Result<(), RepositoryError> {
+        let existing = self.inner.list(tenant).await?;
+        match clash(&existing, written) {
+            Some(id) => Err(RepositoryError::NameTaken(id)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl PersonRepository for UniqueNames {
+    async fn migrations(&self) -> Result<Vec<AppliedMigration>, RepositoryError> {
+        self.inner.migrations().await
+    }
+
+    async fn check(&self) -> Vec<ComponentCheck> {
+        self.inner.check().await
+    }
+
+    fn lock_waits(&self) -> Option<LockStats> {
+        self.inner.lock_waits()
+    }
+
+    async fn close(&self) -> Result<(), RepositoryError> {
+        self.inner.close().await
+    }
+
+    async fn tenants(&self) -> Result<Vec<String>, RepositoryError> {
+        self.inner.tenants().await
+    }
+
+    async fn list(&self, tenant: &str) -> Result<Vec<Person>, RepositoryError> {
+        self.inner.list(tenant).await
+    }
+
+    async fn get(&self, tenant: &str, id: u32) -> Result<Option<Person>, RepositoryError> {
+        self.inner.get(tenant, id).await
+    }
+
+    async fn find_by_uuid(&self, tenant: &str, uuid: Uuid) -> Result<Option<Person>, RepositoryError> {
+        self.inner.find_by_uuid(tenant, uuid).await
+    }
+
+    async fn insert(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.check(tenant, std::slice::from_ref(&person)).await?;
+        self.inner.insert(tenant, person).await
+    }
+
+    async fn insert_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.check(tenant, &persons).await?;
+        self.inner.insert_many(tenant, persons).await
+    }
+
+    async fn update(&self, tenant: &str, person: Person) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.check(tenant, std::slice::from_ref(&person)).await?;
+        self.inner.update(tenant, person).await
+    }
+
+    async fn update_many(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.check(tenant, &persons).await?;
+        self.inner.update_many(tenant, persons).await
+    }
+
+    async fn delete(&self, tenant: &str, id: u32) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.inner.delete(tenant, id).await
+    }
+
+    async fn delete_many(&self, tenant: &str, ids: Vec<u32>) -> Result<Vec<u32>, RepositoryError> {
+        let _writes = self.writes.lock().await;
+        self.inner.delete_many(tenant, ids).await
+    }
+
+    async fn replace_all(&self, tenant: &str, persons: Vec<Person>) -> Result<(), RepositoryError> {
+        let _writes = self.writes.lock().await;
+        if let Some(id) = clash(&[], &persons) {
+            return Err(RepositoryError::NameTaken(id));
+        }
+        self.inner.replace_all(tenant, persons).await
+    }
+}
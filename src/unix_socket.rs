@@ -0,0 +1,139 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::tokio::io::copy_bidirectional;
+use rocket::tokio::net::{TcpStream, UnixListener, UnixStream};
+use rocket::tokio::task::JoinHandle;
+use rocket::tokio::time;
+use rocket::{Build, Config, Orbit, Rocket};
+
+const DEFAULT_MODE: u32 = 0o660;
+
+/// Serves on the Unix socket `LISTEN_SOCKET` instead of TCP, e.g. behind nginx on the
+/// same host. Rocket 0.5 only listens on TCP, so it is bound to an ephemeral port on the
+/// loopback interface and every connection to the socket is relayed to it; clients
+/// therefore appear as `127.0.0.1`, and the proxy's `X-Forwarded-For` tells them apart.
+/// The socket gets the mode `LISTEN_SOCKET_MODE` (octal, default 660), a stale one left
+/// by a crash is replaced, and it is removed on shutdown.
+pub struct UnixSocket {
+    path: PathBuf,
+    mode: u32,
+    listener: Mutex<Option<UnixListener>>,
+    relay: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl UnixSocket {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(path) = env::var("LISTEN_SOCKET") else {
+            return Ok(None);
+        };
+        let mode = match env::var("LISTEN_SOCKET_MODE") {
+            Ok(mode) => u32::from_str_radix(&mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .ok_or_else(|| format!("LISTEN_SOCKET_MODE must be an octal mode such as 660, not '{}'", mode))?,
+            Err(_) => DEFAULT_MODE,
+        };
+        Ok(Some(UnixSocket { path: path.into(), mode, listener: Mutex::default(), relay: Mutex::default() }))
+    }
+
+    /// Rocket's own listener, reachable only from this host.
+    pub fn tcp_config(config: Config) -> Config {
+        Config { address: Ipv4Addr::LOCALHOST.into(), port: 0, ..config }
+    }
+
+    async fn bind(&self) -> Result<UnixListener, String> {
+        let describe = |e: std::io::Error| format!("{}: {}", self.path.display(), e);
+        match fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if UnixStream::connect(&self.path).await.is_ok() {
+                    return Err(format!("{}: another process is listening on it", self.path.display()));
+                }
+                fs::remove_file(&self.path).map_err(describe)?;
+            }
+            Ok(_) => return Err(format!("{}: exists and is not a socket", self.path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(describe(e)),
+        }
+        let listener = UnixListener::bind(&self.path).map_err(describe)?;
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(self.mode)).map_err(describe)?;
+        Ok(listener)
+    }
+}
+
+async fn relay(mut client: UnixStream, server: SocketAddr) {
+    let mut server = match TcpStream::connect(server).await {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Failed to relay a connection from the Unix socket: {}", e);
+            return;
+        }
+    };
+    // Either side hanging up ends the connection; nothing is left to report.
+    let _ = copy_bidirectional(&mut client, &mut server).await;
+}
+
+fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != ErrorKind::NotFound {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for UnixSocket {
+    fn info(&self) -> Info {
+        Info { name: "Unix socket", kind: Kind::Ignite | Kind::Liftoff | Kind::Shutdown }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        match self.bind().await {
+            Ok(listener) => {
+                *self.listener.lock().unwrap_or_else(PoisonError::into_inner) = Some(listener);
+                Ok(rocket)
+            }
+            Err(e) => {
+                error!("Failed to listen on the Unix socket {}", e);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(listener) = self.listener.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+            return;
+        };
+        let server = SocketAddr::from((rocket.config().address, rocket.config().port));
+        info!("Listening on {}", self.path.display());
+        let relay_loop = rocket::tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((client, _)) => {
+                        rocket::tokio::spawn(relay(client, server));
+                    }
+                    Err(e) => {
+                        error!("Failed to accept a connection on the Unix socket: {}", e);
+                        // Such as running out of file descriptors; give others time to close.
+                        time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        });
+        *self.relay.lock().unwrap_or_else(PoisonError::into_inner) = Some(relay_loop);
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        // New connections are refused from here on; those relayed already finish.
+        if let Some(relay_loop) = self.relay.lock().unwrap_or_else(PoisonError::into_inner).take() {
+            relay_loop.abort();
+        }
+        remove(&self.path);
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use rocket::data::{self, Data, FromData};
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::Request;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_ignored::Path;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::bulk::BulkPatch;
+use crate::dates;
+use crate::person::{Address, Person, PersonPatch, INITIAL_VERSION};
+use crate::signature::SignedJson;
+use crate::AppState;
+
+pub const MAX_NAME_CHARS: usize = 100;
+pub const MAX_AGE: i64 = 150;
+
+/// Messages per offending field, keyed by its path in the body (`name`, `[2].age`, ...).
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.entry(field.to_string()).or_default().push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// All messages on one line, e.g. for a CSV import report.
+    pub fn summary(&self) -> String {
+        self.0.iter()
+            .flat_map(|(field, messages)| messages.iter().map(move |message| format!("{} {}", field, message)))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    fn merge(&mut self, other: FieldErrors) {
+        for (field, messages) in other.0 {
+            self.0.entry(field).or_default().extend(messages);
+        }
+    }
+
+    /// Moves `other`'s messages in under `prefix`.
+    fn nest(&mut self, prefix: &str, other: FieldErrors) {
+        for (field, messages) in other.0 {
+            let field = match field.starts_with('[') {
+                true => format!("{}{}", prefix, field),
+                false => format!("{}.{}", prefix, field),
+            };
+            self.0.entry(field).or_default().extend(messages);
+        }
+    }
+}
+
+pub fn check_name(errors: &mut FieldErrors, field: &str, name: &str) -> Option<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        errors.add(field, "must not be empty");
+        return None;
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        errors.add(field, format!("must be at most {} characters", MAX_NAME_CHARS));
+        return None;
+    }
+    Some(name.to_string())
+}
+
+pub fn check_age(errors: &mut FieldErrors, field: &str, age: i64) -> Option<u8> {
+    match (0..=MAX_AGE).contains(&age) {
+        true => u8::try_from(age).ok(),
+        false => {
+            errors.add(field, format!("must be between 0 and {}", MAX_AGE));
+            None
+        }
+    }
+}
+
+/// `date` is an RFC 3339 timestamp; the legacy formats of [`dates::parse`] are still accepted.
+pub fn check_date(errors: &mut FieldErrors, field: &str, date: &str) -> Option<DateTime<Utc>> {
+    let parsed = dates::parse(date);
+    if parsed.is_none() {
+        errors.add(field, "must be an RFC 3339 timestamp");
+    }
+    parsed
+}
+
+pub fn check_timestamp(errors: &mut FieldErrors, field: &str, timestamp: &str) -> Option<DateTime<Utc>> {
+    let parsed = DateTime::parse_from_rfc3339(timestamp).ok().map(|t| t.with_timezone(&Utc));
+    if parsed.is_none() {
+        errors.add(field, "must be an RFC 3339 timestamp");
+    }
+    parsed
+}
+
+fn required<T>(errors: &mut FieldErrors, field: &str, value: Option<T>) -> Option<T> {
+    if value.is_none() {
+        errors.add(field, "is required");
+    }
+    value
+}
+
+/// Request bodies that are checked field by field before a handler sees them.
+pub trait Validate {
+    type Output;
+
+    fn validate(self) -> Result<Self::Output, FieldErrors>;
+}
+
+/// Ids need no checks beyond being numbers.
+impl Validate for u32 {
+    type Output = u32;
+
+    fn validate(self) -> Result<u32, FieldErrors> {
+        Ok(self)
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    type Output = Vec<T::Output>;
+
+    fn validate(self) -> Result<Self::Output, FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let mut valid = Vec::with_capacity(self.len());
+        for (index, item) in self.into_iter().enumerate() {
+            match item.validate() {
+                Ok(item) => valid.push(item),
+                Err(item_errors) => errors.nest(&format!("[{}]", index), item_errors),
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(valid),
+            false => Err(errors),
+        }
+    }
+}
+
+/// A person as sent by clients. Fields are loosely typed so that bad values
+/// are reported per field instead of failing the whole body.
+#[derive(Deserialize)]
+pub struct NewPerson {
+    #[serde(default)]
+    id: u32,
+    name: Option<String>,
+    age: Option<i64>,
+    date: Option<String>,
+    expires_at: Option<String>,
+    uuid: Option<Uuid>,
+    #[serde(default)]
+    addresses: Vec<NewAddress>,
+}
+
+impl NewPerson {
+    /// The id sent, `0` for none.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Validate for NewPerson {
+    type Output = Person;
+
+    fn validate(self) -> Result<Person, FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let name = required(&mut errors, "name", self.name).and_then(|name| check_name(&mut errors, "name", &name));
+        let age = required(&mut errors, "age", self.age).and_then(|age| check_age(&mut errors, "age", age));
+        let date = required(&mut errors, "date", self.date).and_then(|date| check_date(&mut errors, "date", &date));
+        let expires_at = self.expires_at.and_then(|t| check_timestamp(&mut errors, "expires_at", &t));
+        let addresses = self.addresses.validate().map_err(|e| errors.nest("addresses", e)).ok();
+        match (name, age, date, addresses) {
+            (Some(name), Some(age), Some(date), Some(addresses)) if errors.is_empty() => Ok(Person {
+                id: self.id,
+                name,
+                age,
+                date,
+                deleted_at: None,
+                expires_at,
+                version: INITIAL_VERSION,
+                uuid: self.uuid,
+                addresses,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewAddress {
+    street: Option<String>,
+    city: Option<String>,
+    postal_code: Option<String>,
+    country: Option<String>,
+}
+
+impl Validate for NewAddress {
+    type Output = Address;
+
+    fn validate(self) -> Result<Address, FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let mut text = |field: &str, value: Option<String>| {
+            let value = required(&mut errors, field, value)?.trim().to_string();
+            if value.is_empty() {
+                errors.add(field, "must not be empty");
+                return None;
+            }
+            Some(value)
+        };
+        let (street, city) = (text("street", self.street), text("city", self.city));
+        match (street, city) {
+            (Some(street), Some(city)) => Ok(Address {
+                id: 0,
+                street,
+                city,
+                postal_code: self.postal_code,
+                country: self.country,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Body of `PATCH`: any subset of the person's fields, each checked like on create.
+#[derive(Deserialize)]
+pub struct PatchInput {
+    name: Option<String>,
+    age: Option<i64>,
+    date: Option<String>,
+    expires_at: Option<String>,
+}
+
+impl Validate for PatchInput {
+    type Output = PersonPatch;
+
+    fn validate(self) -> Result<PersonPatch, FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let patch = PersonPatch {
+            name: self.name.and_then(|name| check_name(&mut errors, "name", &name)),
+            age: self.age.and_then(|age| check_age(&mut errors, "age", age)),
+            date: self.date.and_then(|date| check_date(&mut errors, "date", &date)),
+            expires_at: self.expires_at.and_then(|t| check_timestamp(&mut errors, "expires_at", &t)),
+        };
+        match errors.is_empty() {
+            true => Ok(patch),
+            false => Err(errors),
+        }
+    }
+}
+
+/// A [`PatchInput`] plus the id it applies to. The fields are spelled out rather
+/// than flattened so that unknown ones are still noticed.
+#[derive(Deserialize)]
+pub struct BulkPatchInput {
+    id: u32,
+    name: Option<String>,
+    age: Option<i64>,
+    date: Option<String>,
+    expires_at: Option<String>,
+}
+
+impl Validate for BulkPatchInput {
+    type Output = BulkPatch;
+
+    fn validate(self) -> Result<BulkPatch, FieldErrors> {
+        let patch = PatchInput { name: self.name, age: self.age, date: self.date, expires_at: self.expires_at };
+        Ok(BulkPatch { id: self.id, patch: patch.validate()? })
+    }
+}
+
+/// [`FieldErrors`] key for a member serde skipped, e.g. `[1].addresses[0].zip`.
+fn field_path(path: &Path) -> String {
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", field_path(parent), index),
+        Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// Whether the body is declared as JSON, `application/json` or any `+json` type.
+fn is_json(request: &Request<'_>) -> bool {
+    request.content_type().is_some_and(|content_type| {
+        content_type.is_json() || (content_type.top() == "application" && content_type.sub().as_str().to_ascii_lowercase().ends_with("+json"))
+    })
+}
+
+/// A JSON body of type `T` that passed validation. Bodies not declared as JSON
+/// get `415`; invalid ones `422`, with the [`FieldErrors`] left in the request's
+/// local cache for the catcher. With `REJECT_UNKNOWN_FIELDS` set, members the
+/// body type doesn't know are errors too instead of being dropped.
+pub struct Valid<T: Validate>(pub T::Output);
+
+#[rocket::async_trait]
+impl<'r, T> FromData<'r> for Valid<T>
+where
+    T: Validate + DeserializeOwned + Send,
+    T::Output: Send,
+{
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        if !is_json(request) {
+            return Outcome::Error((Status::UnsupportedMediaType, ()));
+        }
+        let value = match SignedJson::<Value>::from_data(request, data).await {
+            Outcome::Success(SignedJson(value)) => value,
+            Outcome::Error((status, _)) => return Outcome::Error((status, ())),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+        let mut unknown = FieldErrors::default();
+        let input = serde_ignored::deserialize(value, |path| unknown.add(&field_path(&path), "is not a known field"));
+        let Ok(input) = input.map(T::validate) else {
+            return Outcome::Error((Status::UnprocessableEntity, ()));
+        };
+        let strict = request.rocket().state::<AppState>().is_some_and(|state| state.reject_unknown_fields);
+        let errors = match input {
+            Ok(valid) if !strict || unknown.is_empty() => return Outcome::Success(Valid(valid)),
+            Ok(_) => unknown,
+            Err(mut errors) => {
+                if strict {
+                    errors.merge(unknown);
+                }
+                errors
+            }
+        };
+        request.local_cache(|| Some(errors));
+        Outcome::Error((Status::UnprocessableEntity, ()))
+    }
+}
+
+/// The field errors recorded for this request by [`Valid`], if any.
+pub fn recorded_errors<'r>(request: &'r Request<'_>) -> Option<&'r FieldErrors> {
+    request.local_cache(|| None::<FieldErrors>).as_ref()
+}
@@ -0,0 +1,18 @@
+// Lists the persons from the API; replace with the real frontend's build output.
+async function listPersons() {
+  const status = document.getElementById("status");
+  const response = await fetch("/api/v1/persons", { headers: { Accept: "application/json" } });
+  if (!response.ok) {
+    status.textContent = `Failed to load the persons: ${response.status}`;
+    return;
+  }
+  const rows = document.getElementById("persons");
+  for (const person of await response.json()) {
+    const row = rows.insertRow();
+    for (const value of [person.id, person.name, person.age, person.date]) {
+      row.insertCell().textContent = value;
+    }
+  }
+}
+
+listPersons();